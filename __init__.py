@@ -29,7 +29,11 @@ import os
 
 import breezy
 from ...commands import plugin_cmds
-from ...hooks import install_lazy_named_hook
+from ...help_topics import topic_registry
+from ...hooks import (
+    install_lazy_named_hook,
+    known_hooks,
+    )
 from ... import trace
 
 from .info import (
@@ -47,6 +51,11 @@ commands = {
         "builddeb": ["bd", "debuild"],
         "get_orig_source": [],
         "dep3_patch": [],
+        "deb_config": [],
+        "deb_patch": [],
+        "deb_pq_export": [],
+        "deb_pq_import": [],
+        "deb_verify_source": [],
         "import_dsc": [],
         "import_upstream": [],
         "mark_uploaded": [],
@@ -58,6 +67,10 @@ for command, aliases in commands.items():
     plugin_cmds.register_lazy(
         'cmd_' + command, aliases, __name__ + ".cmds")
 
+topic_registry.register_lazy(
+    'builddeb-options', __name__ + '.config', 'describe_options',
+    'Options in the builddeb configuration files')
+
 
 def global_conf():
     from ...bedding import config_dir
@@ -192,7 +205,7 @@ def tree_debian_tag_name(tree, branch, subpath=''):
         # The changelog still targets 'UNRELEASED', so apparently hasn't been
         # uploaded. XXX: Give a warning of some sort here?
         return None
-    db = DistributionBranch(branch, None)
+    db = DistributionBranch(branch, None, tag_format=config.debian_tag)
     dbs = DistributionBranchSet()
     dbs.add_branch(db)
     return db.tag_name(changelog.version)
@@ -235,6 +248,10 @@ def pre_merge_fix_ancestry(merger):
                 e.version)
 
 
+known_hooks.register_lazy_hook(
+    __name__ + '.hooks', 'builddeb_hooks', 'BuilddebHooks')
+
+
 install_lazy_named_hook(
     "breezy.msgeditor", "hooks", "commit_message_template",
     debian_changelog_commit_message,
@@ -0,0 +1,313 @@
+#    build_deps.py -- Find and install missing build dependencies
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Finding and installing the build dependencies of a package."""
+
+from __future__ import absolute_import
+
+import os
+import shlex
+import subprocess
+
+from debian.deb822 import (
+    Deb822,
+    PkgRelation,
+    )
+from debian.debian_support import version_compare
+
+from ...errors import BzrError
+from ...trace import note
+
+from .util import subprocess_setup
+
+
+BUILD_DEPS_CHECK = 'check'
+BUILD_DEPS_PRINT = 'print'
+BUILD_DEPS_INSTALL = 'install'
+
+BUILD_DEPS_MODES = [BUILD_DEPS_CHECK, BUILD_DEPS_PRINT, BUILD_DEPS_INSTALL]
+
+BUILD_DEPENDS_FIELDS = [
+    'Build-Depends', 'Build-Depends-Arch', 'Build-Depends-Indep']
+
+
+class MissingBuildDependencies(BzrError):
+
+    _fmt = "Missing build dependencies: %(dependencies_text)s"
+
+    def __init__(self, dependencies):
+        """Create a MissingBuildDependencies error.
+
+        :param dependencies: list of the unsatisfied relations, as strings
+        """
+        BzrError.__init__(
+            self, dependencies=dependencies,
+            dependencies_text=", ".join(dependencies))
+
+
+class UnknownBuildDepsMode(BzrError):
+
+    _fmt = ("Unknown build dependency mode %(mode)s; "
+            "valid modes are %(modes)s.")
+
+    def __init__(self, mode):
+        BzrError.__init__(
+            self, mode=mode, modes=", ".join(BUILD_DEPS_MODES))
+
+
+class BuildDepsInstallFailed(BzrError):
+
+    _fmt = "Installing the build dependencies with %(command)s failed."
+
+    def __init__(self, command):
+        BzrError.__init__(self, command=command)
+
+
+def _arch_matches(host_arch, pattern):
+    if pattern in ('any', host_arch):
+        return True
+
+    def split(arch):
+        if '-' in arch:
+            return arch.split('-', 1)
+        return 'linux', arch
+    if '-' not in pattern:
+        return False
+    pattern_os, pattern_cpu = split(pattern)
+    host_os, host_cpu = split(host_arch)
+    return (pattern_os in ('any', host_os) and
+            pattern_cpu in ('any', host_cpu))
+
+
+def relation_applies(relation, host_arch, profiles):
+    """Check whether a relation applies to a build.
+
+    :param relation: a relation as parsed by PkgRelation.parse_relations
+    :param host_arch: the architecture the package is built for
+    :param profiles: the build profiles that are enabled
+    """
+    arch = relation.get('arch')
+    if arch:
+        positive = [a.arch for a in arch if a.enabled]
+        negative = [a.arch for a in arch if not a.enabled]
+        if positive and not any(
+                _arch_matches(host_arch, a) for a in positive):
+            return False
+        if any(_arch_matches(host_arch, a) for a in negative):
+            return False
+    restrictions = relation.get('restrictions')
+    if restrictions:
+        return any(
+            all((term.profile in profiles) == term.enabled
+                for term in formula)
+            for formula in restrictions)
+    return True
+
+
+def build_depends_fields(build_args):
+    """Find the fields with the build dependencies that a build needs.
+
+    Build-Depends-Arch is only needed when architecture dependent packages
+    are built, and Build-Depends-Indep when architecture independent ones
+    are.
+
+    :param build_args: the arguments for dpkg-buildpackage, e.g. ["-B"]
+    :return: list of field names
+    """
+    arch = indep = True
+    for arg in build_args:
+        if arg == '-A':
+            arch = False
+        elif arg == '-B':
+            indep = False
+        elif arg in ('-S', '--build=source'):
+            arch = indep = False
+        elif arg.startswith('--build='):
+            types = arg[len('--build='):].split(',')
+            arch = bool(set(types) & {'any', 'binary', 'full'})
+            indep = bool(set(types) & {'all', 'binary', 'full'})
+    fields = ['Build-Depends']
+    if arch:
+        fields.append('Build-Depends-Arch')
+    if indep:
+        fields.append('Build-Depends-Indep')
+    return fields
+
+
+def get_build_dependencies(control_path, host_arch, profiles=None,
+                           fields=None):
+    """Read the build dependencies from a debian/control file.
+
+    Alternatives and relations that don't apply for the architecture or
+    build profiles are left out, and the architecture and build profile
+    qualifiers are removed from the others.
+
+    :param control_path: path to the debian/control file
+    :param host_arch: the architecture the package is built for
+    :param profiles: list of build profiles that are enabled
+    :param fields: the fields to read, defaults to BUILD_DEPENDS_FIELDS
+    :return: list of relations, each a list of alternatives
+    """
+    if profiles is None:
+        profiles = []
+    if fields is None:
+        fields = BUILD_DEPENDS_FIELDS
+    with open(control_path, 'rb') as f:
+        source = next(Deb822.iter_paragraphs(f))
+    ret = []
+    for field in fields:
+        for alternatives in PkgRelation.parse_relations(
+                source.get(field, '')):
+            # The qualifiers have been evaluated, so drop them.
+            alternatives = [
+                dict(relation, arch=None, restrictions=None)
+                for relation in alternatives
+                if relation_applies(relation, host_arch, profiles)]
+            if alternatives:
+                ret.append(alternatives)
+    return ret
+
+
+def get_installed_packages():
+    """Find the packages that are installed on this system.
+
+    :return: dictionary mapping package names, including virtual packages,
+        to the list of their installed versions; None stands for a virtual
+        package that is provided without a version.
+    """
+    output = subprocess.check_output(
+        ['dpkg-query', '-W', '-f',
+         '${db:Status-Abbrev}\t${Package}\t${Version}\t${Provides}\n'],
+        preexec_fn=subprocess_setup)
+    installed = {}
+    for line in output.decode('utf-8').splitlines():
+        (status, package, version, provides) = line.split('\t')
+        if status.strip() != 'ii':
+            continue
+        installed.setdefault(package, []).append(version)
+        for alternatives in PkgRelation.parse_relations(provides):
+            for provided in alternatives:
+                if provided['version'] is not None:
+                    provided_version = provided['version'][1]
+                else:
+                    provided_version = None
+                installed.setdefault(provided['name'], []).append(
+                    provided_version)
+    return installed
+
+
+_VERSION_OPS = {
+    '<<': lambda c: c < 0,
+    '<=': lambda c: c <= 0,
+    '=': lambda c: c == 0,
+    '>=': lambda c: c >= 0,
+    '>>': lambda c: c > 0,
+    }
+
+
+def relation_satisfied(relation, installed):
+    """Check whether a single relation is satisfied.
+
+    :param relation: a relation as parsed by PkgRelation.parse_relations
+    :param installed: dictionary as returned by get_installed_packages
+    """
+    versions = installed.get(relation['name'], [])
+    if relation['version'] is None:
+        return bool(versions)
+    op, required = relation['version']
+    return any(
+        version is not None and
+        _VERSION_OPS[op](version_compare(version, required))
+        for version in versions)
+
+
+def unsatisfied_build_dependencies(dependencies, installed):
+    """Find the build dependencies that are not satisfied.
+
+    :param dependencies: list of relations as returned by
+        get_build_dependencies
+    :param installed: dictionary as returned by get_installed_packages
+    :return: list of the unsatisfied relations, as strings
+    """
+    return [
+        PkgRelation.str([alternatives])
+        for alternatives in dependencies
+        if not any(relation_satisfied(relation, installed)
+                   for relation in alternatives)]
+
+
+def get_install_command(installer, dependencies):
+    """Get the command to install build dependencies with.
+
+    :param installer: "apt" to use apt-get satisfy, "mk-build-deps" to
+        use mk-build-deps or a command to which the unsatisfied
+        dependencies are passed as arguments.
+    :param dependencies: list of the unsatisfied relations, as strings
+    :return: the shell command to run in the source tree
+    """
+    if installer == 'apt':
+        command = "apt-get satisfy --yes --no-install-recommends " + " ".join(
+            shlex.quote(dependency) for dependency in dependencies)
+    elif installer == 'mk-build-deps':
+        command = ("mk-build-deps --install --remove --tool "
+                   "'apt-get --yes --no-install-recommends' debian/control")
+    else:
+        return installer + " " + " ".join(
+            shlex.quote(dependency) for dependency in dependencies)
+    if os.getuid() != 0:
+        command = "sudo " + command
+    return command
+
+
+def handle_build_dependencies(source_dir, mode, host_arch, profiles=None,
+                              installer='apt', fields=None):
+    """Check that the build dependencies of a package are installed.
+
+    :param source_dir: the exported source tree
+    :param mode: one of BUILD_DEPS_MODES: BUILD_DEPS_CHECK raises
+        MissingBuildDependencies if dependencies are missing,
+        BUILD_DEPS_PRINT just lists them and BUILD_DEPS_INSTALL installs
+        them with the installer.
+    :param host_arch: the architecture the package is built for
+    :param profiles: list of build profiles that are enabled
+    :param installer: the installer to use, see get_install_command
+    :param fields: the fields to check, as returned by
+        build_depends_fields; defaults to BUILD_DEPENDS_FIELDS
+    """
+    if mode not in BUILD_DEPS_MODES:
+        raise UnknownBuildDepsMode(mode)
+    dependencies = get_build_dependencies(
+        os.path.join(source_dir, 'debian', 'control'), host_arch, profiles,
+        fields=fields)
+    missing = unsatisfied_build_dependencies(
+        dependencies, get_installed_packages())
+    if not missing:
+        note("All build dependencies are installed.")
+        return
+    if mode == BUILD_DEPS_CHECK:
+        raise MissingBuildDependencies(missing)
+    note("Missing build dependencies: %s", ", ".join(missing))
+    if mode == BUILD_DEPS_INSTALL:
+        command = get_install_command(installer, missing)
+        note("Installing the build dependencies using %s", command)
+        if subprocess.call(
+                command, shell=True, cwd=source_dir,
+                preexec_fn=subprocess_setup) != 0:
+            raise BuildDepsInstallFailed(command)
@@ -0,0 +1,226 @@
+#    build_failure.py -- Classification of common build failures
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Classification of common Debian package build failures."""
+
+from __future__ import absolute_import
+
+import re
+
+
+FAILURE_MISSING_BUILD_DEPENDENCIES = "missing-build-dependencies"
+FAILURE_PATCH_APPLICATION = "patch-application-failed"
+FAILURE_MISSING_FILES = "missing-files"
+FAILURE_TEST_SUITE = "test-suite-failed"
+FAILURE_UNREPRESENTABLE_CHANGES = "unrepresentable-changes"
+FAILURE_MISSING_UPSTREAM_TARBALL = "missing-upstream-tarball"
+
+
+class BuildFailure(object):
+    """The reason a build failed, as determined from its log.
+
+    :ivar kind: one of the FAILURE_* constants
+    :ivar details: dictionary with details specific to the kind of failure
+    :ivar hint: human readable suggestion on how to fix the failure
+    """
+
+    def __init__(self, kind, details=None, hint=None):
+        self.kind = kind
+        if details is None:
+            details = {}
+        self.details = details
+        self.hint = hint
+
+    def __eq__(self, other):
+        return (isinstance(other, type(self)) and
+                self.kind == other.kind and
+                self.details == other.details and
+                self.hint == other.hint)
+
+    def __ne__(self, other):
+        return not self == other
+
+    # details is a mutable dictionary
+    __hash__ = None
+
+    def __repr__(self):
+        return "%s(%r, %r, %r)" % (
+            type(self).__name__, self.kind, self.details, self.hint)
+
+    def __str__(self):
+        return "%s: %s" % (self.kind, self.hint)
+
+
+_RELATION = (
+    r'[^\s|()\[\]<>]+(?:\s*\([^)]*\))?(?:\s*\[[^\]]*\])?'
+    r'(?:\s*<[^>]*>)*')
+_UNMET_DEPENDENCY_RE = re.compile(
+    r'%s(?:\s*\|\s*%s)*' % (_RELATION, _RELATION))
+
+
+def parse_unmet_dependencies(text):
+    """Split the dependency list printed by dpkg-checkbuilddeps.
+
+    :param text: e.g. "debhelper-compat (= 13) libfoo-dev | libbar-dev"
+    :return: list of dependencies, e.g.
+        ["debhelper-compat (= 13)", "libfoo-dev | libbar-dev"]
+    """
+    return [m.group(0) for m in _UNMET_DEPENDENCY_RE.finditer(text)]
+
+
+def _missing_build_dependencies(lines, i, m):
+    deps = parse_unmet_dependencies(m.group(1))
+    return BuildFailure(
+        FAILURE_MISSING_BUILD_DEPENDENCIES, {'dependencies': deps},
+        "Install the missing build dependencies (%s), for instance with "
+        "'apt-get build-dep' or 'mk-build-deps'." % ", ".join(deps))
+
+
+def _dpkg_source_patch_failure(lines, i, m):
+    patch = None
+    for line in reversed(lines[:i]):
+        pm = re.match(r'dpkg-source: info: applying (.*)', line)
+        if pm:
+            patch = pm.group(1).strip()
+            break
+    return BuildFailure(
+        FAILURE_PATCH_APPLICATION, {'patch': patch},
+        "The patch %s no longer applies; refresh it against the current "
+        "upstream source or drop it from debian/patches/series." % (
+            patch or '(unknown)'))
+
+
+def _quilt_patch_failure(lines, i, m):
+    return BuildFailure(
+        FAILURE_PATCH_APPLICATION, {'patch': m.group(1)},
+        "The patch %s no longer applies; refresh it against the current "
+        "upstream source or drop it from debian/patches/series." %
+        m.group(1))
+
+
+def _dh_missing_files(lines, i, m):
+    files = []
+    for line in lines[:i]:
+        fm = re.match(
+            r'dh_missing: (?:warning: )?(.*) exists in debian/tmp but is '
+            r'not installed to anywhere', line)
+        if fm:
+            files.append(fm.group(1))
+    return BuildFailure(
+        FAILURE_MISSING_FILES, {'files': files},
+        "Install the files to a binary package (debian/*.install) or "
+        "list them in debian/not-installed.")
+
+
+def _dh_install_missing_files(lines, i, m):
+    return BuildFailure(
+        FAILURE_MISSING_FILES, {'files': m.group(1).split()},
+        "The files listed in debian/*.install were not built; check the "
+        "upstream build or update the install files.")
+
+
+def _dh_install_cannot_find(lines, i, m):
+    return BuildFailure(
+        FAILURE_MISSING_FILES, {'files': [m.group(1)]},
+        "The files listed in debian/*.install were not built; check the "
+        "upstream build or update the install files.")
+
+
+def _test_suite_failure(lines, i, m):
+    return BuildFailure(
+        FAILURE_TEST_SUITE, {'command': m.group(1)},
+        "The upstream test suite failed; fix the tests, or build with "
+        "DEB_BUILD_OPTIONS=nocheck to skip them.")
+
+
+def _unrepresentable_change(lines, i, m):
+    return BuildFailure(
+        FAILURE_UNREPRESENTABLE_CHANGES,
+        {'path': m.group(1), 'reason': m.group(2)},
+        "dpkg-source can not represent changes to binary files or file "
+        "removals; add the file to debian/source/include-binaries or "
+        "revert the change.")
+
+
+def _unrepresentable_changes(lines, i, m):
+    return BuildFailure(
+        FAILURE_UNREPRESENTABLE_CHANGES, {},
+        "dpkg-source can not represent changes to binary files or file "
+        "removals; add the file to debian/source/include-binaries or "
+        "revert the change.")
+
+
+def _missing_upstream_tarball(lines, i, m):
+    return BuildFailure(
+        FAILURE_MISSING_UPSTREAM_TARBALL, {'path': m.group(1)},
+        "Make the upstream tarball available, e.g. with 'bzr "
+        "get-orig-source', or set orig-dir to where it can be found.")
+
+
+_MATCHERS = [
+    (r'dpkg-checkbuilddeps: (?:error: )?Unmet build dependencies: (.*)',
+     _missing_build_dependencies),
+    (r'Patch (.*) does not apply \(enforce with -f\)',
+     _quilt_patch_failure),
+    (r'dpkg-source: error: LC_ALL=C patch .* (?:gave error|subprocess '
+     r'returned) exit status',
+     _dpkg_source_patch_failure),
+    (r'dpkg-source: error: cannot represent change to (.*): (.*)',
+     _unrepresentable_change),
+    (r'dpkg-source: error: unrepresentable changes to source',
+     _unrepresentable_changes),
+    (r'dpkg-source: error: can\'t build with source format .*: '
+     r'no upstream tarball found at (.*)',
+     _missing_upstream_tarball),
+    (r'dh_missing: (?:error: )?missing files, aborting',
+     _dh_missing_files),
+    (r'dh_install: (?:error: )?missing files: (.*)',
+     _dh_install_missing_files),
+    (r'dh_install: (?:error: )?Cannot find \(any matches for\) "(.*)"',
+     _dh_install_cannot_find),
+    (r'dh_auto_test: (?:error: )?(.*) returned exit code \d+',
+     _test_suite_failure),
+    ]
+
+
+def analyse_build_log(lines):
+    """Find the reason for a build failure in a build log.
+
+    :param lines: the lines of the log, as strings
+    :return: a BuildFailure, or None if the reason could not be determined
+    """
+    lines = [line.rstrip('\n') for line in lines]
+    for i, line in enumerate(lines):
+        for regex, fn in _MATCHERS:
+            m = re.match(regex, line)
+            if m:
+                return fn(lines, i, m)
+    return None
+
+
+def analyse_build_log_file(path):
+    """Find the reason for a build failure in a build log file.
+
+    :param path: path to the build log
+    :return: a BuildFailure, or None if the reason could not be determined
+    """
+    with open(path, 'rb') as f:
+        return analyse_build_log(
+            [line.decode('utf-8', 'replace') for line in f])
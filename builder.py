@@ -20,20 +20,40 @@
 
 from __future__ import absolute_import
 
+import collections
+import concurrent.futures
+import datetime
+import json
 import shutil
 import subprocess
 import os
+import shlex
+import sys
 import tempfile
 
+from debian import deb822
+
 from ...errors import BzrError
-from ...trace import note
+from ...registry import Registry
+from ...trace import note, warning
 
-from .hooks import run_hook
+from .build_failure import analyse_build_log_file
+from .hooks import (
+    BuildHookParams,
+    DistillHookParams,
+    hook_environment,
+    run_builddeb_hooks,
+    run_hook,
+    )
+from .source_distiller import remove_export_record
 from .util import (
+    config_list,
     get_parent_dir,
     subprocess_setup,
     find_changes_files,
     dget_changes,
+    get_build_architecture,
+    sha256sum_filename,
     )
 
 
@@ -49,25 +69,664 @@ class NoSourceDirError(BzrError):
 
 
 class BuildFailedError(BzrError):
-    _fmt = "The build failed."
+    _fmt = "The build failed.%(log_details)s"
+
+    def __init__(self, log_path=None, tail=None, failure=None):
+        """Create a BuildFailedError.
+
+        :param log_path: path to the build log, if one was kept
+        :param tail: the last lines of the build log, as bytes
+        :param failure: a BuildFailure describing the cause, if known
+        """
+        log_details = ""
+        if failure is not None:
+            log_details += " %s" % failure
+        if log_path is not None:
+            log_details += " The full build log is in %s." % log_path
+            if tail:
+                log_details += " Last lines of the log:\n" + "".join(
+                    line.decode(errors='replace') for line in tail)
+        BzrError.__init__(
+            self, log_path=log_path, tail=tail, failure=failure,
+            log_details=log_details)
+
+
+class UnsupportedBuilderOption(BzrError):
+
+    _fmt = 'The builder "%(builder)s" can not set the %(option)s.'
+
+    def __init__(self, builder, option):
+        BzrError.__init__(self, builder=builder, option=option)
+
+
+class ConflictingArchitectures(BzrError):
+
+    _fmt = ('The builder "%(builder)s" can not build for %(architecture)s '
+            'and cross-build for %(host_architecture)s at the same time.')
+
+    def __init__(self, builder, architecture, host_architecture):
+        BzrError.__init__(
+            self, builder=builder, architecture=architecture,
+            host_architecture=host_architecture)
+
+
+class MergeChangesFailed(BzrError):
+
+    _fmt = "Unable to merge changes files: %(error)s"
+
+    def __init__(self, error):
+        BzrError.__init__(self, error=error)
+
+
+class Builder(object):
+    """A way of building a package from an exported source tree.
+
+    :ivar name: the name of the builder in builder_registry
+    """
+
+    name = None
+
+    # Whether the builder installs the build dependencies itself, e.g.
+    # in a chroot.
+    installs_build_dependencies = False
+
+    # Extra arguments that make the builder write a source-only changes
+    # file for uploading, or None if it can't.
+    source_only_changes_args = None
+
+    def __init__(self, distribution=None, architecture=None,
+                 extra_repositories=None, run_tests=True, extra_args=None,
+                 profiles=None, build_options=None, host_architecture=None):
+        """Create a Builder.
+
+        :param distribution: the distribution to build for, or None for
+            the builder's default.
+        :param architecture: the architecture to build for, or None for
+            the builder's default.
+        :param extra_repositories: list of extra apt repositories (in
+            sources.list format) to use for build dependencies.
+        :param run_tests: whether to run the package's test suite.
+        :param extra_args: list of extra arguments for the build command.
+        :param profiles: list of build profiles to enable, e.g. nodoc.
+        :param build_options: list of DEB_BUILD_OPTIONS to set, e.g.
+            parallel=4.
+        :param host_architecture: the architecture to cross-build for, or
+            None to build natively.
+        """
+        self.distribution = distribution
+        self.architecture = architecture
+        if extra_repositories is None:
+            extra_repositories = []
+        self.extra_repositories = extra_repositories
+        self.run_tests = run_tests
+        if extra_args is None:
+            extra_args = []
+        self.extra_args = extra_args
+        if profiles is None:
+            profiles = []
+        self.profiles = profiles
+        if build_options is None:
+            build_options = []
+        self.build_options = build_options
+        self.host_architecture = host_architecture
+
+    def get_profiles(self):
+        """Return the build profiles to enable for the build."""
+        profiles = list(self.profiles)
+        extra = []
+        if self.host_architecture is not None:
+            # The test suite can't usually be run when cross-building
+            extra.extend(['cross', 'nocheck'])
+        if not self.run_tests:
+            extra.append('nocheck')
+        for profile in extra:
+            if profile not in profiles:
+                profiles.append(profile)
+        return profiles
+
+    def get_build_options(self):
+        """Return the DEB_BUILD_OPTIONS to set for the build."""
+        build_options = list(self.build_options)
+        # The nocheck and nodoc profiles are meant to be used together
+        # with the build options of the same name.
+        for profile in self.get_profiles():
+            if profile in ('nocheck', 'nodoc') and (
+                    profile not in build_options):
+                build_options.append(profile)
+        return build_options
+
+    def _unsupported(self, option):
+        warning("The %s builder can not set the %s; ignoring it.",
+                self, option)
+
+    def _dpkg_host_architecture(self, architecture):
+        # dpkg-buildpackage -a is the same option as --host-arch, so it
+        # can only be given one architecture.
+        if architecture is None:
+            return self.host_architecture
+        if self.host_architecture not in (None, architecture):
+            raise ConflictingArchitectures(
+                self, architecture, self.host_architecture)
+        return architecture
+
+    def get_arguments(self, source_dir, architecture):
+        """Return the arguments of the build command.
+
+        :param source_dir: the exported source tree to build.
+        :param architecture: the architecture to build for, or None.
+        """
+        raise NotImplementedError(self.get_arguments)
+
+    def get_command(self, source_dir, architecture=None):
+        """Return the shell command to run in source_dir to build it.
+
+        :param source_dir: the exported source tree to build.
+        :param architecture: the architecture to build for, overriding
+            the architecture the builder was created with.
+        """
+        if architecture is None:
+            architecture = self.architecture
+        return " ".join(
+            shlex.quote(arg)
+            for arg in self.get_arguments(source_dir, architecture))
+
+    def get_environment(self):
+        """Return the environment variables to set for the build."""
+        env = {}
+        for name, values in [
+                ('DEB_BUILD_OPTIONS', self.get_build_options()),
+                ('DEB_BUILD_PROFILES', self.get_profiles())]:
+            if not values:
+                continue
+            current = os.environ.get(name, '').split()
+            env[name] = " ".join(
+                current + [value for value in values if value not in current])
+        return env
+
+    @classmethod
+    def options_from_config(cls, config):
+        """Return the options specific to this builder from a config.
+
+        :param config: a DebBuildConfig
+        :return: dictionary with keyword arguments for the constructor
+        """
+        return {}
+
+    def get_result_dir(self, source_dir):
+        """Return the directory the build results are written to.
+
+        :param source_dir: the exported source tree to build.
+        """
+        return get_parent_dir(source_dir) or '.'
+
+    def __str__(self):
+        return self.name
+
+
+class CommandBuilder(Builder):
+    """A Builder that runs an arbitrary shell command.
+
+    The command is expected to write its results to the parent directory
+    of the source tree, like dpkg-buildpackage does. If the command
+    contains $ARCH it is replaced by the architecture to build for,
+    otherwise -aARCH is appended if the command is debuild or
+    dpkg-buildpackage; other commands can not be told the architecture
+    and raise UnsupportedBuilderOption. Likewise $HOST_ARCH is replaced by
+    the architecture to cross-build for, or the option of the command for
+    it is appended.
+    """
+
+    # Commands that take the architecture to build for, and the format of
+    # the option to pass it with.
+    ARCHITECTURE_OPTIONS = {
+        'debuild': '-a%s',
+        'dpkg-buildpackage': '-a%s',
+        }
+
+    # Commands that can cross-build, and the format of the option to pass
+    # the host architecture with.
+    HOST_ARCHITECTURE_OPTIONS = {
+        'debuild': '--host-arch=%s',
+        'dpkg-buildpackage': '--host-arch=%s',
+        'sbuild': '--host=%s',
+        }
+
+    def __init__(self, command, **kwargs):
+        super(CommandBuilder, self).__init__(**kwargs)
+        self.command = command
+        if self.distribution is not None:
+            self._unsupported("distribution")
+        if self.extra_repositories:
+            self._unsupported("extra repositories")
+
+    def _command_name(self):
+        try:
+            args = shlex.split(self.command)
+        except ValueError:
+            return None
+        if not args:
+            return None
+        return os.path.basename(args[0])
+
+    def get_command(self, source_dir, architecture=None):
+        if architecture is None:
+            architecture = self.architecture
+        command = self.command
+        host_architecture = self.host_architecture
+        if (architecture is not None and host_architecture is not None and
+                self._command_name() in self.ARCHITECTURE_OPTIONS and
+                '$ARCH' not in command and '$HOST_ARCH' not in command):
+            # -a of debuild and dpkg-buildpackage already sets the host
+            # architecture.
+            self._dpkg_host_architecture(architecture)
+            host_architecture = None
+        if architecture is not None:
+            option = self.ARCHITECTURE_OPTIONS.get(self._command_name())
+            if '$ARCH' in command:
+                command = command.replace('$ARCH', architecture)
+            elif option is not None:
+                command = "%s %s" % (command, option % architecture)
+            else:
+                raise UnsupportedBuilderOption(self, "architecture")
+        if host_architecture is not None:
+            option = self.HOST_ARCHITECTURE_OPTIONS.get(self._command_name())
+            if '$HOST_ARCH' in command:
+                command = command.replace('$HOST_ARCH', host_architecture)
+            elif option is not None:
+                command = "%s %s" % (command, option % host_architecture)
+            else:
+                raise UnsupportedBuilderOption(self, "host architecture")
+        if self.extra_args:
+            command += " " + " ".join(self.extra_args)
+        return command
+
+    def __str__(self):
+        return self.command
+
+
+class DpkgBuildpackageBuilder(Builder):
+    """Build on the host with dpkg-buildpackage."""
+
+    name = "dpkg-buildpackage"
+
+    def __init__(self, **kwargs):
+        super(DpkgBuildpackageBuilder, self).__init__(**kwargs)
+        if self.distribution is not None:
+            self._unsupported("distribution")
+        if self.extra_repositories:
+            self._unsupported("extra repositories")
+
+    def get_arguments(self, source_dir, architecture):
+        args = [self.name]
+        args.extend(self._get_wrapper_arguments())
+        host_architecture = self._dpkg_host_architecture(architecture)
+        if architecture is not None:
+            args.append("-a%s" % architecture)
+        elif host_architecture is not None:
+            args.append("--host-arch=%s" % host_architecture)
+        profiles = self.get_profiles()
+        if profiles:
+            args.append("-P%s" % ",".join(profiles))
+        return args + self.extra_args
+
+    def _get_wrapper_arguments(self):
+        return []
+
+
+class DebuildBuilder(DpkgBuildpackageBuilder):
+    """Build on the host with debuild, which also runs lintian."""
+
+    name = "debuild"
+
+    def _get_wrapper_arguments(self):
+        # Make sure DEB_BUILD_OPTIONS survives debuild cleaning up the
+        # environment.
+        build_options = self.get_environment().get('DEB_BUILD_OPTIONS')
+        if build_options is None:
+            return []
+        return ["--set-envvar=DEB_BUILD_OPTIONS=%s" % build_options]
+
+
+class SbuildBuilder(Builder):
+    """Build in a clean chroot with sbuild."""
+
+    name = "sbuild"
+    installs_build_dependencies = True
+    source_only_changes_args = ["--source", "--source-only-changes"]
+
+    def get_arguments(self, source_dir, architecture):
+        args = [self.name, "--build-dir=%s" % os.path.abspath(
+            self.get_result_dir(source_dir))]
+        if self.distribution is not None:
+            args.append("--dist=%s" % self.distribution)
+        if architecture is not None:
+            args.append("--arch=%s" % architecture)
+        if self.host_architecture is not None:
+            args.append("--host=%s" % self.host_architecture)
+        for repository in self.extra_repositories:
+            args.append("--extra-repository=%s" % repository)
+        profiles = self.get_profiles()
+        if profiles:
+            args.append("--profiles=%s" % ",".join(profiles))
+        return args + self.extra_args
+
+
+class PbuilderBuilder(Builder):
+    """Build in a clean chroot with pdebuild and pbuilder.
+
+    The distribution is passed on in the DIST environment variable, which
+    is what the usual pbuilderrc setups use to pick a base tarball.
+    """
+
+    name = "pbuilder"
+    installs_build_dependencies = True
+
+    def get_arguments(self, source_dir, architecture):
+        args = ["pdebuild"]
+        args.extend(self._pdebuild_arguments())
+        args.extend([
+            "--buildresult",
+            os.path.abspath(self.get_result_dir(source_dir))])
+        args.extend(self.extra_args)
+        pbuilder_args = []
+        if architecture is not None:
+            pbuilder_args.extend(["--architecture", architecture])
+        if self.host_architecture is not None:
+            pbuilder_args.extend(["--host-arch", self.host_architecture])
+        profiles = self.get_profiles()
+        if profiles:
+            pbuilder_args.extend(["--profiles", ",".join(profiles)])
+        if self.extra_repositories:
+            pbuilder_args.extend(
+                ["--othermirror", " | ".join(self.extra_repositories)])
+        if pbuilder_args:
+            args.append("--")
+            args.extend(pbuilder_args)
+        return args
+
+    def _pdebuild_arguments(self):
+        return []
+
+    def get_environment(self):
+        env = super(PbuilderBuilder, self).get_environment()
+        if self.distribution is not None:
+            env['DIST'] = self.distribution
+        return env
+
+
+class CowbuilderBuilder(PbuilderBuilder):
+    """Build in a clean chroot with pdebuild and cowbuilder."""
+
+    name = "cowbuilder"
+
+    def _pdebuild_arguments(self):
+        return ["--pbuilder", "cowbuilder"]
+
+
+class ContainerBuilder(Builder):
+    """Build in a throwaway container from a local OCI image.
+
+    The parent directory of the source tree is mounted in the container.
+    The source tree and any upstream tarballs are copied to the home
+    directory of an unprivileged user, the build dependencies from
+    debian/control are installed and the package is built with
+    dpkg-buildpackage as that user. The resulting files are then copied
+    back next to the source tree.
+
+    :ivar runtime: the command to run containers with
+    """
+
+    runtime = None
+    installs_build_dependencies = True
+    default_image = "debian:unstable"
+
+    # Whether the files copied back from the container have to be chowned
+    # to the invoking user.
+    chown_results = True
+
+    def __init__(self, image=None, setup_commands=None, **kwargs):
+        """Create a ContainerBuilder.
+
+        :param image: the image to build in; defaults to debian:DISTRIBUTION
+            if a distribution was given, or debian:unstable otherwise.
+        :param setup_commands: list of shell commands to run as root in the
+            container before installing the build dependencies.
+        """
+        super(ContainerBuilder, self).__init__(**kwargs)
+        if image is None:
+            if self.distribution is not None:
+                image = "debian:%s" % self.distribution
+            else:
+                image = self.default_image
+        self.image = image
+        if setup_commands is None:
+            setup_commands = []
+        self.setup_commands = setup_commands
+
+    @classmethod
+    def options_from_config(cls, config):
+        return {
+            'image': config.container_image,
+            'setup_commands': config_list(config.container_setup),
+            }
+
+    def get_script(self, source_dir, architecture):
+        """Return the shell script to run as root inside the container.
+
+        :param source_dir: the exported source tree to build.
+        :param architecture: the architecture to build for, or None.
+        """
+        basename = os.path.basename(source_dir.rstrip('/'))
+        lines = ["set -e"]
+        for repository in self.extra_repositories:
+            lines.append(
+                "echo %s >> /etc/apt/sources.list.d/builddeb.list" %
+                shlex.quote(repository))
+        for arch in [architecture, self.host_architecture]:
+            if arch is not None:
+                lines.append("dpkg --add-architecture %s" % shlex.quote(arch))
+        lines.append("apt-get update")
+        lines.extend(self.setup_commands)
+        toolchain = "build-essential dpkg-dev"
+        if self.host_architecture is not None:
+            toolchain += " crossbuild-essential-%s" % shlex.quote(
+                self.host_architecture)
+        lines.extend([
+            "apt-get install -y --no-install-recommends %s" % toolchain,
+            "useradd --create-home builder",
+            "mkdir /home/builder/build",
+            "cp -a /build/%s /home/builder/build/" % shlex.quote(basename),
+            "cp -a /build/*.orig*.tar.* /home/builder/build/ 2>/dev/null "
+            "|| true",
+            "chown -R builder: /home/builder/build",
+            "cd /home/builder/build/%s" % shlex.quote(basename),
+            ])
+        build_dep = ["apt-get", "build-dep", "-y"]
+        build = ["runuser", "-u", "builder", "--", "dpkg-buildpackage",
+                 "-us", "-uc"]
+        host_architecture = self._dpkg_host_architecture(architecture)
+        if architecture is not None:
+            build_dep.extend(["-a", architecture])
+            build.append("-a%s" % architecture)
+        elif host_architecture is not None:
+            build_dep.append("--host-architecture=%s" % host_architecture)
+            build.append("--host-arch=%s" % host_architecture)
+        profiles = self.get_profiles()
+        if profiles:
+            build_dep.extend(["-P", ",".join(profiles)])
+            build.append("-P%s" % ",".join(profiles))
+        build_dep.append("./")
+        build.extend(self.extra_args)
+        lines.append(" ".join(shlex.quote(arg) for arg in build_dep))
+        lines.append(" ".join(shlex.quote(arg) for arg in build))
+        # Copy back the results, replacing those of an earlier build of the
+        # same version, but leave the upstream tarballs alone.
+        copy = ("cd /home/builder/build && for f in *; do "
+                "case \"$f\" in *.orig*.tar.*) continue;; esac; "
+                "if [ -f \"$f\" ]; then "
+                "cp \"$f\" /build/")
+        if self.chown_results:
+            copy += " && chown %d:%d \"/build/$f\"" % (
+                os.getuid(), os.getgid())
+        copy += "; fi; done"
+        lines.append(copy)
+        return "\n".join(lines)
+
+    def get_arguments(self, source_dir, architecture):
+        args = [self.runtime, "run", "--rm",
+                "--volume=%s:/build" % os.path.abspath(
+                    self.get_result_dir(source_dir))]
+        for name, value in sorted(self.get_environment().items()):
+            args.append("--env=%s=%s" % (name, value))
+        args.extend([self.image, "sh", "-c",
+                     self.get_script(source_dir, architecture)])
+        return args
+
+
+class PodmanBuilder(ContainerBuilder):
+    """Build in a container with podman."""
+
+    name = "podman"
+    runtime = "podman"
+
+    # Rootless podman maps root in the container to the invoking user.
+    chown_results = False
+
+
+class DockerBuilder(ContainerBuilder):
+    """Build in a container with docker."""
+
+    name = "docker"
+    runtime = "docker"
+
+
+builder_registry = Registry()
+builder_registry.register(
+    DebuildBuilder.name, DebuildBuilder,
+    help="Build on the host with debuild.")
+builder_registry.register(
+    DpkgBuildpackageBuilder.name, DpkgBuildpackageBuilder,
+    help="Build on the host with dpkg-buildpackage.")
+builder_registry.register(
+    SbuildBuilder.name, SbuildBuilder,
+    help="Build in a chroot with sbuild.")
+builder_registry.register(
+    PbuilderBuilder.name, PbuilderBuilder,
+    help="Build in a chroot with pbuilder.")
+builder_registry.register(
+    CowbuilderBuilder.name, CowbuilderBuilder,
+    help="Build in a chroot with cowbuilder.")
+builder_registry.register(
+    PodmanBuilder.name, PodmanBuilder,
+    help="Build in a local container image with podman.")
+builder_registry.register(
+    DockerBuilder.name, DockerBuilder,
+    help="Build in a local container image with docker.")
+
+
+def _split_words(value):
+    return [
+        word for item in config_list(value)
+        for word in item.replace(',', ' ').split()]
+
+
+def get_builder(builder, config=None, extra_args=None, **overrides):
+    """Get the Builder for a builder name or a build command.
+
+    :param builder: the name of a builder in builder_registry, or a shell
+        command to build with.
+    :param config: a DebBuildConfig to take the builder options from, or
+        None to use the defaults.
+    :param extra_args: list of extra arguments for the build command.
+    :param overrides: options for the Builder that take precedence over
+        the ones from config, e.g. from the command line. Options that are
+        None are ignored.
+    :return: a Builder
+    """
+    kwargs = {}
+    if config is not None:
+        kwargs = {
+            'distribution': config.builder_distribution,
+            'architecture': config.builder_architecture,
+            'extra_repositories': config_list(
+                config.builder_extra_repositories),
+            'run_tests': config.builder_run_tests,
+            'profiles': _split_words(config.build_profiles),
+            'build_options': _split_words(config.build_options),
+            'host_architecture': config.host_arch,
+            }
+    kwargs.update(
+        (name, value) for (name, value) in overrides.items()
+        if value is not None)
+    try:
+        builder_cls = builder_registry.get(builder)
+    except KeyError:
+        return CommandBuilder(builder, extra_args=extra_args, **kwargs)
+    if config is not None:
+        kwargs.update(builder_cls.options_from_config(config))
+    return builder_cls(extra_args=extra_args, **kwargs)
 
 
 class DebBuild(object):
     """The object that does the building work."""
 
-    def __init__(self, distiller, target_dir, builder, use_existing=False):
+    LOG_TAIL_LINES = 20
+
+    def __init__(self, distiller, target_dir, builder, use_existing=False,
+                 architecture=None, log_path=None, environment=None,
+                 umask=None, quiet=False):
         """Create a builder.
 
         :param distiller: the SourceDistiller that will get the source to
             build.
         :param target_dir: the directory in which to do all the work.
-        :param builder: the build command to use.
+        :param builder: the Builder to use, or a build command.
         :param use_existing: whether to re-use the target_dir if it exists.
+        :param architecture: the architecture to build for, or None to
+            build for the architecture the builder picks by default.
+        :param log_path: path of the file to write the output of the
+            builder to, or None to not keep a log.
+        :param environment: dictionary with extra environment variables
+            to set for the build.
+        :param umask: the umask to run the build with, or None to inherit
+            it.
+        :param quiet: whether to only write the output of the builder to
+            the log rather than also to stdout, if there is a log.
         """
         self.distiller = distiller
         self.target_dir = target_dir
+        if isinstance(builder, str):
+            builder = CommandBuilder(builder)
         self.builder = builder
         self.use_existing = use_existing
+        self.architecture = architecture
+        self.log_path = log_path
+        self.environment = environment
+        self.umask = umask
+        self.quiet = quiet
+        self.returncode = None
+
+    @property
+    def result_dir(self):
+        """The directory the builder writes the build results to."""
+        return self.builder.get_result_dir(self.target_dir)
+
+    def _get_build_command(self):
+        return self.builder.get_command(self.target_dir, self.architecture)
+
+    def _get_build_environment(self):
+        env = self.builder.get_environment()
+        if self.environment:
+            env.update(self.environment)
+        if not env:
+            return None
+        env = dict(os.environ, **env)
+        return env
+
+    def _build_setup(self):
+        subprocess_setup()
+        if self.umask is not None:
+            os.umask(self.umask)
 
     def prepare(self):
         """Do any preparatory steps that should be run before the build.
@@ -90,7 +749,15 @@ class DebBuild(object):
                 raise NoSourceDirError
 
     def export(self):
+        upstream_provider = getattr(
+            self.distiller, 'upstream_provider', None)
+        params = DistillHookParams(
+            self.distiller, self.target_dir, upstream_provider)
+        run_builddeb_hooks('pre_distill', params)
         self.distiller.distill(self.target_dir)
+        params.tarballs = sorted(
+            getattr(upstream_provider, 'tarball_sources', {}).items())
+        run_builddeb_hooks('post_distill', params)
 
     def before_build(self):
         subprocess.check_call(
@@ -104,39 +771,353 @@ class DebBuild(object):
 
     def build(self):
         """This builds the package using the supplied command."""
+        params = BuildHookParams(
+            self.builder, self.target_dir, self.result_dir,
+            dict(self.environment or {}))
+        run_builddeb_hooks('pre_build', params)
+        self.environment = params.environment
+        self._build()
+        run_builddeb_hooks('post_build', params)
+
+    def _build(self):
+        build_command = self._get_build_command()
         note("Building the package in %s, using %s", self.target_dir,
-             self.builder)
-        proc = subprocess.Popen(
-            self.builder, shell=True, cwd=self.target_dir,
-            preexec_fn=subprocess_setup)
-        proc.wait()
+             build_command)
+        if self.log_path is None:
+            proc = subprocess.Popen(
+                build_command, shell=True, cwd=self.target_dir,
+                env=self._get_build_environment(),
+                preexec_fn=self._build_setup)
+            proc.wait()
+            self.returncode = proc.returncode
+            if proc.returncode != 0:
+                raise BuildFailedError
+            return
+        note("Writing the build log to %s", self.log_path)
+        tail = collections.deque(maxlen=self.LOG_TAIL_LINES)
+        if self.quiet:
+            outf = None
+        else:
+            outf = getattr(sys.stdout, 'buffer', None)
+        with open(self.log_path, 'wb') as log:
+            proc = subprocess.Popen(
+                build_command, shell=True, cwd=self.target_dir,
+                env=self._get_build_environment(),
+                stdout=subprocess.PIPE, stderr=subprocess.STDOUT,
+                preexec_fn=self._build_setup)
+            for line in proc.stdout:
+                log.write(line)
+                tail.append(line)
+                if outf is not None:
+                    outf.write(line)
+                    outf.flush()
+            proc.stdout.close()
+            proc.wait()
+        self.returncode = proc.returncode
         if proc.returncode != 0:
-            raise BuildFailedError
+            raise BuildFailedError(
+                self.log_path, list(tail),
+                failure=analyse_build_log_file(self.log_path))
 
     def clean(self):
         """This removes the build directory."""
         note("Cleaning build dir: %s", self.target_dir)
         shutil.rmtree(self.target_dir)
+        remove_export_record(self.target_dir)
+
+
+class BuildReport(object):
+    """Machine-readable record of a single package build."""
+
+    def __init__(self, package, version, build_type=None, distiller=None,
+                 builder=None):
+        self.package = package
+        self.version = version
+        self.build_type = build_type
+        self.distiller = distiller
+        self.profiles = getattr(builder, 'get_profiles', list)()
+        self.build_options = getattr(builder, 'get_build_options', list)()
+        if builder is not None:
+            builder = str(builder)
+        self.builder = builder
+        self.start_time = None
+        self.end_time = None
+        self.exit_status = None
+        self.log_path = None
+        self.tarballs = []
+        self.artifacts = []
+
+    def start(self):
+        self.start_time = datetime.datetime.now(datetime.timezone.utc)
+
+    def finish(self, exit_status):
+        self.end_time = datetime.datetime.now(datetime.timezone.utc)
+        self.exit_status = exit_status
+
+    def add_tarballs(self, upstream_provider):
+        """Record the upstream tarballs that were used for the build.
+
+        :param upstream_provider: the UpstreamProvider used by the distiller
+        """
+        tarball_sources = getattr(upstream_provider, 'tarball_sources', {})
+        verified = getattr(upstream_provider, 'verified_tarballs', {})
+        for filename, source in sorted(tarball_sources.items()):
+            self.tarballs.append({
+                'filename': filename, 'source': source,
+                'verified-against': verified.get(filename, [])})
+
+    def add_artifacts(self, changes_path):
+        """Record the changes file and all of the files it references.
+
+        Files that were already recorded for another changes file of the
+        same build are not recorded twice.
+
+        :param changes_path: path to the changes file; the files it
+            references are expected to be in the same directory.
+        """
+        directory = os.path.dirname(changes_path)
+        with open(changes_path, 'rb') as f:
+            changes = deb822.Changes(f)
+        paths = [changes_path] + [
+            os.path.join(directory, file_details['name'])
+            for file_details in changes['files']]
+        recorded = set(
+            artifact['filename'] for artifact in self.artifacts)
+        for path in paths:
+            if os.path.basename(path) in recorded:
+                continue
+            self.artifacts.append({
+                'filename': os.path.basename(path),
+                'size': os.path.getsize(path),
+                'sha256': sha256sum_filename(path)})
+
+    def as_dict(self):
+        def format_time(t):
+            if t is None:
+                return None
+            return t.strftime('%Y-%m-%dT%H:%M:%SZ')
+        return {
+            'package': self.package,
+            'version': str(self.version),
+            'build-type': self.build_type,
+            'distiller': (
+                self.distiller.__class__.__name__
+                if self.distiller is not None else None),
+            'builder': self.builder,
+            'profiles': self.profiles,
+            'build-options': self.build_options,
+            'start-time': format_time(self.start_time),
+            'end-time': format_time(self.end_time),
+            'exit-status': self.exit_status,
+            'build-log': (
+                os.path.basename(self.log_path)
+                if self.log_path is not None else None),
+            'upstream-tarballs': self.tarballs,
+            'artifacts': self.artifacts,
+            }
+
+    def write(self, path):
+        with open(path, 'w') as f:
+            json.dump(self.as_dict(), f, indent=2, sort_keys=True)
+            f.write('\n')
+
+
+def _non_epoch_version(version):
+    non_epoch_version = version.upstream_version
+    if version.debian_version is not None:
+        non_epoch_version += "-%s" % version.debian_version
+    return non_epoch_version
+
+
+def build_log_name(package, version, architecture):
+    """Return the name of the build log for a package, like sbuild does."""
+    return "%s_%s_%s.build" % (
+        package, _non_epoch_version(version), architecture)
+
+
+def build_report_path(changes_path):
+    """Return the path of the build report for a changes file."""
+    return changes_path[:-len('.changes')] + '.build-report.json'
+
+
+def failed_build_report_path(target_dir, package, version):
+    """Return the path of the build report for a build that failed."""
+    return os.path.join(
+        target_dir, "%s_%s.build-report.json" % (
+            package, _non_epoch_version(version)))
 
 
 def do_build(package_name, version, distiller, local_tree, config,
-             build_command, target_dir=None):
-    """Actually run a build."""
+             build_command, target_dir=None, build_type=None, report=False):
+    """Actually run a build.
+
+    :param report: whether to write a BuildReport in JSON next to the
+        changes file in target_dir.
+    """
+    build_report = BuildReport(
+        package_name, version, build_type=build_type, distiller=distiller,
+        builder=build_command)
+    host_arch = getattr(build_command, 'host_architecture', None)
+    if target_dir is not None:
+        log_path = os.path.join(
+            target_dir,
+            build_log_name(
+                package_name, version,
+                host_arch or get_build_architecture()))
+    else:
+        log_path = None
     with tempfile.TemporaryDirectory() as bd:
         build_source_dir = os.path.join(
             bd, package_name + "-" + version.upstream_version)
         builder = DebBuild(
                 distiller, build_source_dir,
                 build_command,
-                use_existing=False)
+                use_existing=False, log_path=log_path)
+        build_report.log_path = log_path
         builder.prepare()
-        run_hook(local_tree, 'pre-export', config)
+        hook_env = hook_environment(
+            package_name, version, build_dir=build_source_dir,
+            result_dir=target_dir)
+        run_hook(local_tree, 'pre-export', config, env=hook_env)
         builder.export()
-        run_hook(local_tree, 'pre-build', config, wd=build_source_dir)
-        builder.build()
-        run_hook(local_tree, 'post-build', config, wd=build_source_dir)
+        run_hook(
+            local_tree, 'post-export', config, wd=build_source_dir,
+            env=hook_env)
+        build_report.add_tarballs(
+            getattr(distiller, 'upstream_provider', None))
+        run_hook(
+            local_tree, 'pre-build', config, wd=build_source_dir,
+            env=hook_env)
+        build_report.start()
+        try:
+            builder.build()
+        except BuildFailedError:
+            build_report.finish(builder.returncode)
+            if report and target_dir is not None:
+                build_report.write(failed_build_report_path(
+                    target_dir, package_name, version))
+            raise
+        build_report.finish(builder.returncode)
+        changes_paths = [
+            entry.path for kind, entry in find_changes_files(
+                builder.result_dir, package_name, version,
+                architecture=host_arch)]
+        run_hook(
+            local_tree, 'post-build', config, wd=build_source_dir,
+            env=hook_environment(
+                package_name, version, build_dir=build_source_dir,
+                result_dir=target_dir,
+                changes_file=(changes_paths[0] if changes_paths else None)))
         if target_dir is not None:
-            for kind, entry in find_changes_files(bd, package_name, version):
-                return dget_changes(entry.path, target_dir)
-            else:
+            if not changes_paths:
                 raise ChangesFileMissing()
+            changes_paths = [
+                dget_changes(changes_path, target_dir)
+                for changes_path in changes_paths]
+            if report:
+                for changes_path in changes_paths:
+                    build_report.add_artifacts(changes_path)
+                build_report.write(build_report_path(changes_paths[0]))
+            return changes_paths[0]
+
+
+def build_architectures(source_dir, build_dir, builder, architectures,
+                        package, version, log_dir=None):
+    """Build an exported source tree once for each of several architectures.
+
+    Every architecture gets a copy of source_dir, and of the upstream
+    tarballs next to it, in its own subdirectory of build_dir, so that the
+    builds don't interfere with each other. The architectures are built
+    concurrently; if there is a build log for each of them, the output of
+    the builders is only written to the logs to keep it apart.
+
+    :param source_dir: the exported source tree to build.
+    :param build_dir: the directory beneath which to build.
+    :param builder: the Builder or build command to use.
+    :param architectures: list of architecture names to build for.
+    :param package: the name of the source package.
+    :param version: the Version of the package.
+    :param log_dir: directory in which to write a build log for each
+        architecture, or None to not keep build logs.
+    :return: tuple with a dictionary mapping the architectures that built
+        successfully to the paths of their changes files, and a list of the
+        architectures that failed to build.
+    """
+    parent_dir = get_parent_dir(source_dir) or '.'
+    tarballs = [
+        entry.path for entry in os.scandir(parent_dir)
+        if '.orig' in entry.name and entry.is_file()]
+    builds = {}
+    for arch in architectures:
+        arch_dir = os.path.join(build_dir, arch)
+        arch_source_dir = os.path.join(
+            arch_dir, os.path.basename(source_dir.rstrip('/')))
+        if os.path.exists(arch_dir):
+            shutil.rmtree(arch_dir)
+        os.makedirs(arch_dir)
+        shutil.copytree(source_dir, arch_source_dir, symlinks=True)
+        for tarball in tarballs:
+            shutil.copy(tarball, arch_dir)
+        if log_dir is not None:
+            log_path = os.path.join(
+                log_dir, build_log_name(package, version, arch))
+        else:
+            log_path = None
+        builds[arch] = DebBuild(
+            None, arch_source_dir, builder, architecture=arch,
+            log_path=log_path, quiet=len(architectures) > 1)
+    with concurrent.futures.ThreadPoolExecutor(
+            max_workers=max(len(architectures), 1)) as executor:
+        futures = {
+            arch: executor.submit(builds[arch].build)
+            for arch in architectures}
+    succeeded = {}
+    failed = []
+    for arch in architectures:
+        try:
+            futures[arch].result()
+        except BuildFailedError as e:
+            if e.failure is not None:
+                note("Build for %s failed: %s", arch, e.failure)
+            else:
+                note("Build for %s failed.", arch)
+            failed.append(arch)
+            continue
+        for kind, entry in find_changes_files(
+                builds[arch].result_dir, package, version):
+            if kind in (arch, 'multi'):
+                succeeded[arch] = entry.path
+                break
+        else:
+            note("Build for %s did not produce a changes file.", arch)
+            failed.append(arch)
+    return succeeded, failed
+
+
+def merge_changes(changes_paths, target_dir, package, version):
+    """Merge several changes files into a single multi-arch changes file.
+
+    The files referenced by each changes file are copied into target_dir
+    along with the merged changes file.
+
+    :param changes_paths: paths of the changes files to merge.
+    :param target_dir: directory in which to write the result.
+    :param package: the name of the source package.
+    :param version: the Version of the package.
+    :return: path of the merged changes file.
+    """
+    local_paths = [
+        dget_changes(changes_path, target_dir)
+        for changes_path in changes_paths]
+    merged_path = os.path.join(
+        target_dir, "%s_%s_multi.changes" % (
+            package, _non_epoch_version(version)))
+    proc = subprocess.Popen(
+        ['mergechanges'] + local_paths, stdout=subprocess.PIPE,
+        stderr=subprocess.PIPE, preexec_fn=subprocess_setup)
+    (stdout, stderr) = proc.communicate()
+    if proc.returncode != 0:
+        raise MergeChangesFailed(stderr.decode(errors='replace').strip())
+    with open(merged_path, 'wb') as f:
+        f.write(stdout)
+    return merged_path
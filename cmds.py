@@ -29,6 +29,7 @@ import tempfile
 from typing import Optional
 
 from ... import (
+    osutils,
     urlutils,
     )
 from ...branch import Branch
@@ -61,6 +62,7 @@ from .config import (
     BUILD_TYPE_SPLIT,
     )
 from .util import (
+    config_list,
     debuild_config,
     get_build_architecture,
     )
@@ -246,30 +248,48 @@ def _get_upstream_sources(local_tree, subpath, packaging_branch,
     yield DirectoryScanSource('..')
 
 
+def _find_build_type(tree, subpath, changelog, build_type, config,
+                     contains_upstream_source=True):
+    """Find the build type to use, from the configuration or the tree.
+
+    :param build_type: the build type that was asked for, or None
+    """
+    from .util import guess_build_type
+    if build_type is None:
+        build_type = config.build_type
+    if build_type is None:
+        build_type = guess_build_type(
+            tree, changelog.version, subpath, contains_upstream_source)
+    return build_type
+
+
 def _get_distiller(
         tree, subpath, packaging_branch, changelog, build_type, config,
         contains_upstream_source=True, top_level=False,
         orig_dir=default_orig_dir, use_existing=False,
         export_upstream=None, export_upstream_revision=None,
         guess_upstream_branch_url=False):
-    from .util import (
-        guess_build_type,
-        )
     from .upstream import (
+        ChecksumsFile,
         UpstreamProvider,
+        UpstreamSigningKeys,
         )
     from .source_distiller import (
+        DgitSourceDistiller,
         FullSourceDistiller,
         MergeModeDistiller,
         NativeSourceDistiller,
         DebcargoDistiller,
+        GoModuleDistiller,
+        PyPIDistiller,
         )
-
-    if build_type is None:
-        build_type = config.build_type
-    if build_type is None:
-        build_type = guess_build_type(
-            tree, changelog.version, subpath, contains_upstream_source)
+    from .registry import (
+        python_project_from_package,
+        registry_from_package,
+        )
+    build_type = _find_build_type(
+        tree, subpath, changelog, build_type, config,
+        contains_upstream_source=contains_upstream_source)
 
     note(gettext("Building package in %s mode") % build_type)
 
@@ -280,9 +300,12 @@ def _get_distiller(
         export_upstream_revision=export_upstream_revision,
         guess_upstream_branch_url=guess_upstream_branch_url))
 
+    checksums_file = ChecksumsFile.from_tree(tree, subpath, top_level)
     upstream_provider = UpstreamProvider(
         changelog.package, changelog.version.upstream_version, orig_dir,
-        upstream_sources)
+        upstream_sources,
+        checksum_sources=[checksums_file] if checksums_file else None,
+        signing_keys=UpstreamSigningKeys.from_tree(tree, subpath, top_level))
 
     # Turn this into a build type?
     if tree.has_filename(os.path.join(subpath, 'debian/debcargo.toml')):
@@ -290,13 +313,35 @@ def _get_distiller(
             tree, subpath, top_level=top_level,
             use_existing=use_existing)
     if build_type == BUILD_TYPE_MERGE:
+        registry = config.upstream_registry
+        if registry is None:
+            registry = registry_from_package(changelog.package)
+        if registry == 'go':
+            return GoModuleDistiller(
+                tree, subpath, upstream_provider, changelog.package,
+                changelog.version.upstream_version, proxy=config.go_proxy,
+                top_level=top_level, use_existing=use_existing)
+        if registry == 'pypi':
+            project = config.pypi_project
+            if project is None:
+                project = python_project_from_package(changelog.package)
+            return PyPIDistiller(
+                tree, subpath, upstream_provider, changelog.package,
+                changelog.version.upstream_version,
+                project=project, mirror=config.pypi_mirror,
+                top_level=top_level, use_existing=use_existing)
         return MergeModeDistiller(
             tree, subpath, upstream_provider, top_level=top_level,
             use_existing=use_existing)
     elif build_type == BUILD_TYPE_NATIVE:
-        return NativeSourceDistiller(tree, subpath)
+        return NativeSourceDistiller(
+            tree, subpath, use_existing=use_existing)
+    elif config.dgit:
+        return DgitSourceDistiller(
+            tree, subpath, upstream_provider, use_existing=use_existing)
     else:
-        return FullSourceDistiller(tree, subpath, upstream_provider)
+        return FullSourceDistiller(
+            tree, subpath, upstream_provider, use_existing=use_existing)
 
 
 class cmd_builddeb(Command):
@@ -315,30 +360,84 @@ class cmd_builddeb(Command):
 
     To leave the build directory when the build is completed use --dont-purge.
 
-    Specify the command to use when building using the --builder option, by
-    default "debuild" is used. It can be overriden by setting the "builder"
-    variable in you configuration. You can specify extra options to build with
-    by adding them to the end of the command, after using "--" to indicate the
-    end of the options to builddeb itself. The builder that you specify must
-    accept the options you provide at the end of its command line.
+    Specify the builder to use with the --builder option, by default
+    "debuild" is used. It can be overriden by setting the "builder" variable
+    in you configuration. The builder can be one of "debuild",
+    "dpkg-buildpackage", "sbuild", "pbuilder", "cowbuilder", "podman" or
+    "docker", or any other command to build with. You can specify extra
+    options to build with by adding them to the end of the command, after
+    using "--" to indicate the end of the options to builddeb itself. The
+    builder that you specify must accept the options you provide at the end
+    of its command line.
 
     You can also specify directories to use for different things. --build-dir
     is the directory to build the packages beneath, which defaults to
     '../build-area'. '--orig-dir' specifies the directory that contains the
     .orig.tar.gz files , which defaults to '..'. '--result-dir' specifies where
     the resulting package files should be placed, which defaults to '..'.
-    --result-dir will have problems if you use a build command that places
-    the results in a different directory.
+    A build command other than one of the named builders must place the
+    results in the parent directory of the source tree it builds, like
+    dpkg-buildpackage does.
 
-    The --reuse option will be useful if you are in merge mode, and the
-    upstream tarball is very large. It attempts to reuse a build directory from
-    an earlier build. It will fail if one doesn't exist, but you can create one
-    by using --export-only.
+    The --reuse option will be useful if the upstream tarball or the branch
+    is very large. It attempts to reuse a build directory from an earlier
+    build. In merge mode it saves unpacking the upstream tarball; in the
+    other modes only the files that changed since the earlier export are
+    updated. It will fail if no build directory exists, but you can create
+    one by using --export-only.
 
     --quick allows you to define a quick-builder in your configuration files,
     which will be used when this option is passed. It defaults to 'fakeroot
     debian/rules binary'. It is overriden if --builder is passed. Using this
     and --reuse allows for fast rebuilds.
+
+    The output of the build command is written to a PACKAGE_VERSION_ARCH.build
+    log in the result directory, which is kept even if the build directory
+    is purged.
+
+    --check-reproducible builds the package twice from the same export,
+    with a different build path, timezone, locale and umask, and with
+    SOURCE_DATE_EPOCH set to the date of the last changelog entry. It then
+    compares the binary packages and reports which packages and which files
+    in them differ. With --diffoscope a diffoscope report is written to the
+    result directory for each package that differs.
+
+    --build-deps checks the build dependencies in debian/control of the
+    exported tree after the pre-build hook has run. With "check" the build is
+    refused if some are not installed, with "print" they are listed and with
+    "install" they are installed using the "build-deps-installer" from your
+    configuration, which defaults to "apt-get satisfy". The default can be
+    set with the "build-deps" variable in your configuration. Builders that
+    build in a chroot or container install the build dependencies themselves,
+    so the check is skipped for them.
+
+    --profiles and --build-options set the build profiles and the
+    DEB_BUILD_OPTIONS to build with, in the way that suits the builder. They
+    can also be set with the "build-profiles" and "build-options" variables
+    in your configuration.
+
+    --host-arch cross-builds the package for another architecture, enabling
+    the "cross" and "nocheck" build profiles. The build log and changes file
+    are named after that architecture. It can also be set with the
+    "host-arch" variable in your configuration.
+
+    --patch-queue builds with the patches in debian/patches generated from
+    the commits on a patch-queue branch, as created by "bzr deb-pq-import",
+    so that they can be tested before exporting them with
+    "bzr deb-pq-export".
+
+    Sections such as [BUILDDEB:bookworm-backports] or [BUILDDEB:ubuntu] in
+    the configuration files override the [BUILDDEB] section when building
+    for that distribution, which is taken from debian/changelog. --target
+    selects another distribution, e.g. to build for a backport without
+    changing debian/changelog first.
+
+    --architectures takes a comma-separated list of architectures. The
+    package is exported once and then built separately for each of the
+    architectures, each in its own build directory. The architecture is
+    substituted for $ARCH in the build command, or passed as -aARCH if the
+    build command doesn't mention $ARCH. The changes files of the successful
+    builds are merged into a single multi-architecture changes file.
     """
     export_only_opt = Option('export-only', help="Export only, don't build.",
                              short_name='e')
@@ -348,9 +447,10 @@ class cmd_builddeb(Command):
                        "quick-builder, which defaults to \"fakeroot "
                        "debian/rules binary\".")
     reuse_opt = Option('reuse', help="Try to avoid exporting too much on each "
-                       "build. Only works in merge mode; it saves unpacking "
-                       "the upstream tarball each time. Implies --dont-purge "
-                       "and --use-existing.")
+                       "build. In merge mode it saves unpacking the upstream "
+                       "tarball each time, in other modes it only updates "
+                       "changed files. Implies --dont-purge and "
+                       "--use-existing.")
     source_opt = Option('source', help="Build a source package.",
                         short_name='S')
     strict_opt = Option(
@@ -365,6 +465,46 @@ class cmd_builddeb(Command):
         'guess-upstream-branch-url', help=(
             'Guess upstream branch URL if unknown '
             '(requires upstream-ontologist)'))
+    architectures_opt = Option(
+        'architectures', help=(
+            'Comma-separated list of architectures to build for.'),
+        type=str, argname="ARCHITECTURES")
+    check_reproducible_opt = Option(
+        'check-reproducible',
+        help="Build the package twice in different environments and check "
+             "that the binary packages are identical.")
+    diffoscope_opt = Option(
+        'diffoscope',
+        help="Run diffoscope on packages that are not reproducible.")
+    profiles_opt = Option(
+        'profiles',
+        help="Comma-separated list of build profiles to build with, "
+             "e.g. nocheck,nodoc.", type=str, argname="PROFILES")
+    build_options_opt = Option(
+        'build-options',
+        help="DEB_BUILD_OPTIONS to build with, e.g. parallel=4,noopt.",
+        type=str, argname="OPTIONS")
+    host_arch_opt = Option(
+        'host-arch',
+        help="Cross-build the package for this architecture.", type=str,
+        argname="ARCH")
+    patch_queue_opt = Option(
+        'patch-queue',
+        help="Take debian/patches from the commits on this patch-queue "
+             "branch.", type=str, argname="LOCATION")
+    build_deps_opt = Option(
+        'build-deps',
+        help="What to do about missing build dependencies: 'check' to "
+             "refuse to build, 'print' to list them or 'install' to "
+             "install them.", type=str, argname="MODE")
+    report_opt = Option(
+        'report',
+        help="Write a machine-readable report of the build next to the "
+             "changes file.")
+    target_opt = Option(
+        'target',
+        help="Use the configuration for this distribution, rather than the "
+             "one in debian/changelog.", type=str, argname="DISTRIBUTION")
     takes_args = ['branch_or_build_options*']
     aliases = ['bd', 'debuild']
     takes_options = [
@@ -372,7 +512,10 @@ class cmd_builddeb(Command):
         builder_opt, merge_opt, build_dir_opt, orig_dir_opt, split_opt,
         export_upstream_opt, export_upstream_revision_opt, quick_opt,
         reuse_opt, native_opt, source_opt, 'revision', strict_opt,
-        package_merge_opt, guess_upstream_branch_url_opt]
+        package_merge_opt, guess_upstream_branch_url_opt, architectures_opt,
+        check_reproducible_opt, diffoscope_opt, build_deps_opt, profiles_opt,
+        build_options_opt, host_arch_opt, patch_queue_opt, target_opt,
+        report_opt]
 
     def _get_tree_and_branch(self, location):
         if location is None:
@@ -414,7 +557,10 @@ class cmd_builddeb(Command):
             return BUILD_TYPE_SPLIT
         return None
 
-    def _get_build_command(self, config, builder, quick, build_options):
+    def _get_build_command(self, config, builder, quick, builder_args,
+                           profiles=None, build_options=None,
+                           host_arch=None):
+        from .builder import get_builder
         if builder is None:
             if quick:
                 builder = config.quick_builder
@@ -424,9 +570,13 @@ class cmd_builddeb(Command):
                 builder = config.builder
                 if builder is None:
                     builder = "debuild"
-        if build_options:
-            builder += " " + " ".join(build_options)
-        return builder
+        if profiles is not None:
+            profiles = profiles.replace(',', ' ').split()
+        if build_options is not None:
+            build_options = build_options.replace(',', ' ').split()
+        return get_builder(
+            builder, config, builder_args, profiles=profiles,
+            build_options=build_options, host_architecture=host_arch)
 
     def _get_dirs(self, config, location, is_local, result_dir, build_dir,
                   orig_dir):
@@ -454,6 +604,15 @@ class cmd_builddeb(Command):
             config.user_orig_dir or 'build-area')
         return result_dir, build_dir, orig_dir
 
+    def _get_patch_queue_distiller(self, distiller, branch, location):
+        from .source_distiller import PatchQueueDistiller
+        queue_branch = Branch.open_containing(location)[0]
+        self.add_cleanup(queue_branch.lock_read().unlock)
+        graph = queue_branch.repository.get_graph(branch.repository)
+        base_revid = graph.find_unique_lca(
+            queue_branch.last_revision(), branch.last_revision())
+        return PatchQueueDistiller(distiller, queue_branch, base_revid)
+
     def _branch_and_build_options(self, branch_or_build_options_list,
                                   source=False):
         branch = None
@@ -480,10 +639,23 @@ class cmd_builddeb(Command):
             orig_dir=None, split=None,
             quick=False, reuse=False, native=None,
             source=False, revision=None, package_merge=None,
-            strict=False, guess_upstream_branch_url=False):
-        from .builder import DebBuild
+            strict=False, guess_upstream_branch_url=False,
+            architectures=None, check_reproducible=False, diffoscope=False,
+            build_deps=None, profiles=None, build_options=None,
+            host_arch=None, patch_queue=None, target=None, report=None):
+        from .builder import (
+            BuildFailedError,
+            BuildReport,
+            DebBuild,
+            build_log_name,
+            build_report_path,
+            failed_build_report_path,
+            )
         from .config import UpstreamMetadataSyntaxError
-        from .hooks import run_hook
+        from .hooks import (
+            hook_environment,
+            run_hook,
+            )
         from .source_distiller import DebcargoError
         from .util import (
             NoPreviousUpload,
@@ -494,7 +666,7 @@ class cmd_builddeb(Command):
             find_changes_files,
             )
 
-        location, build_options, source = self._branch_and_build_options(
+        location, builder_args, source = self._branch_and_build_options(
                 branch_or_build_options_list, source)
         tree, branch, is_local, location, subpath = self._get_tree_and_branch(
             location)
@@ -503,11 +675,18 @@ class cmd_builddeb(Command):
 
         with tree.lock_read():
             try:
-                config = debuild_config(tree, subpath)
+                config = debuild_config(
+                    tree, subpath, target=target, branch=branch)
             except UpstreamMetadataSyntaxError as e:
                 raise BzrCommandError(
                     gettext('Unable to parse upstream metadata file %s: %s')
                     % (e.path, e.error))
+            branch_name = getattr(branch, 'name', None)
+            if (config.debian_branch is not None and branch_name and
+                    branch_name != config.debian_branch):
+                warning(gettext(
+                    "Building from branch %s, rather than the packaging "
+                    "branch %s."), branch_name, config.debian_branch)
             if reuse:
                 note(gettext("Reusing existing build dir"))
                 dont_purge = True
@@ -518,6 +697,11 @@ class cmd_builddeb(Command):
                     tree, subpath)
             (changelog, top_level) = find_changelog(
                 tree, subpath, merge=not contains_upstream_source)
+            build_type = _find_build_type(
+                tree, subpath, changelog, build_type, config,
+                contains_upstream_source=contains_upstream_source)
+            if report is None:
+                report = config.build_report
 
             if package_merge:
                 try:
@@ -526,15 +710,17 @@ class cmd_builddeb(Command):
                 except NoPreviousUpload:
                     prev_version = None
                 if prev_version is None:
-                    build_options.extend(["-sa", "-v0"])
+                    builder_args.extend(["-sa", "-v0"])
                 else:
-                    build_options.append("-v%s" % str(prev_version))
+                    builder_args.append("-v%s" % str(prev_version))
                     if (prev_version.upstream_version !=
                             changelog.version.upstream_version or
                             prev_version.epoch != changelog.version.epoch):
-                        build_options.append("-sa")
+                        builder_args.append("-sa")
             build_cmd = self._get_build_command(
-                config, builder, quick, build_options)
+                config, builder, quick, builder_args, profiles=profiles,
+                build_options=build_options, host_arch=host_arch)
+            host_arch = build_cmd.host_architecture
             result_dir, build_dir, orig_dir = self._get_dirs(
                 config, location or ".", is_local, result_dir, build_dir,
                 orig_dir)
@@ -547,48 +733,260 @@ class cmd_builddeb(Command):
                 top_level=top_level, export_upstream=export_upstream,
                 export_upstream_revision=export_upstream_revision,
                 guess_upstream_branch_url=guess_upstream_branch_url)
+            if patch_queue is not None:
+                distiller = self._get_patch_queue_distiller(
+                    distiller, branch, patch_queue)
 
             build_source_dir = os.path.join(
                 build_dir,
                 "%s-%s" % (changelog.package,
                            changelog.version.upstream_version))
 
+            if export_only or architectures or check_reproducible:
+                log_path = None
+            else:
+                log_path = os.path.join(
+                    self._get_target_dir(result_dir, is_local, location),
+                    build_log_name(
+                        changelog.package, changelog.version,
+                        'source' if source else (
+                            host_arch or get_build_architecture())))
+
             builder = DebBuild(
                 distiller, build_source_dir, build_cmd,
-                use_existing=use_existing)
+                use_existing=use_existing, log_path=log_path)
             builder.prepare()
-            run_hook(tree, 'pre-export', config)
+            run_hook(tree, 'pre-export', config, env=hook_environment(
+                changelog.package, changelog.version,
+                build_dir=build_source_dir))
             try:
                 builder.export()
             except DebcargoError as e:
                 raise BzrCommandError(str(e))
+            run_hook(
+                tree, 'post-export', config, wd=build_source_dir,
+                env=hook_environment(
+                    changelog.package, changelog.version,
+                    build_dir=build_source_dir))
+            if check_reproducible and not export_only:
+                return self._check_reproducible(
+                    tree, config, builder, build_dir, result_dir, is_local,
+                    location, changelog, dont_purge, diffoscope, build_deps,
+                    source)
+            if architectures and not export_only:
+                return self._build_architectures(
+                    tree, config, builder, build_dir, result_dir, is_local,
+                    location, changelog, architectures.split(','),
+                    dont_purge, build_deps, source)
             if not export_only:
-                run_hook(tree, 'pre-build', config, wd=build_source_dir)
-                builder.build()
-                run_hook(tree, 'post-build', config, wd=build_source_dir)
-                if not dont_purge:
-                    builder.clean()
+                target_dir = self._get_target_dir(
+                    result_dir, is_local, location)
+                run_hook(
+                    tree, 'pre-build', config, wd=build_source_dir,
+                    env=hook_environment(
+                        changelog.package, changelog.version,
+                        build_dir=build_source_dir, result_dir=target_dir))
+                self._handle_build_dependencies(
+                    config, builder, build_deps, source)
+                build_report = BuildReport(
+                    changelog.package, changelog.version,
+                    build_type=build_type, distiller=distiller,
+                    builder=build_cmd)
+                build_report.log_path = log_path
+                build_report.add_tarballs(
+                    getattr(distiller, 'upstream_provider', None))
+                build_report.start()
+                try:
+                    builder.build()
+                except BuildFailedError:
+                    build_report.finish(builder.returncode)
+                    if report:
+                        build_report.write(failed_build_report_path(
+                            target_dir, changelog.package,
+                            changelog.version))
+                    raise
+                build_report.finish(builder.returncode)
                 changes_paths = []
                 for kind, entry in find_changes_files(
-                        build_dir, changelog.package, changelog.version):
+                        builder.result_dir, changelog.package,
+                        changelog.version,
+                        architecture=(None if source else host_arch)):
                     changes_paths.append(entry.path)
+                run_hook(
+                    tree, 'post-build', config, wd=build_source_dir,
+                    env=hook_environment(
+                        changelog.package, changelog.version,
+                        build_dir=build_source_dir, result_dir=target_dir,
+                        changes_file=(
+                            changes_paths[0] if changes_paths else None)))
+                if builder.builder.get_profiles():
+                    note(gettext("Built with the build profiles: %s"),
+                         ", ".join(builder.builder.get_profiles()))
+                if not dont_purge:
+                    builder.clean()
                 if not changes_paths:
                     if result_dir is not None:
                         raise BzrCommandError(
                             "Could not find the .changes "
-                            "file from the build: %s" % build_dir)
+                            "file from the build: %s" % builder.result_dir)
                     return
-                if is_local:
-                    target_dir = result_dir or default_result_dir
-                    target_dir = os.path.join(
-                            urlutils.local_path_from_url(location),
-                            target_dir)
-                else:
-                    target_dir = "."
-                if not os.path.exists(target_dir):
-                    os.makedirs(target_dir)
-                for changes_path in changes_paths:
+                changes_paths = [
                     dget_changes(changes_path, target_dir)
+                    for changes_path in changes_paths]
+                if report:
+                    for changes_path in changes_paths:
+                        build_report.add_artifacts(changes_path)
+                    build_report.write(build_report_path(changes_paths[0]))
+
+    def _handle_build_dependencies(self, config, builder, mode, source,
+                                   architecture=None):
+        from .build_deps import (
+            build_depends_fields,
+            handle_build_dependencies,
+            )
+        if mode is None:
+            mode = config.build_deps
+        if mode is None or source:
+            return
+        if builder.builder.installs_build_dependencies:
+            note(gettext("Not checking the build dependencies, the %s "
+                         "builder installs them itself."), builder.builder)
+            return
+        handle_build_dependencies(
+            builder.target_dir, mode,
+            (builder.builder.host_architecture or architecture or
+             builder.architecture or get_build_architecture()),
+            profiles=builder.builder.get_environment().get(
+                'DEB_BUILD_PROFILES',
+                os.environ.get('DEB_BUILD_PROFILES', '')).split(),
+            installer=config.build_deps_installer or 'apt',
+            fields=build_depends_fields(builder.builder.extra_args))
+
+    def _get_target_dir(self, result_dir, is_local, location):
+        if is_local:
+            target_dir = result_dir or default_result_dir
+            target_dir = os.path.join(
+                    urlutils.local_path_from_url(location),
+                    target_dir)
+        else:
+            target_dir = "."
+        if not os.path.exists(target_dir):
+            os.makedirs(target_dir)
+        return target_dir
+
+    def _check_reproducible(self, tree, config, builder, build_dir,
+                            result_dir, is_local, location, changelog,
+                            dont_purge, diffoscope, build_deps, source):
+        from .hooks import (
+            hook_environment,
+            run_hook,
+            )
+        from .reproducible import (
+            changelog_timestamp,
+            check_reproducible,
+            )
+        from .util import dget_changes
+        target_dir = self._get_target_dir(result_dir, is_local, location)
+        run_hook(
+            tree, 'pre-build', config, wd=builder.target_dir,
+            env=hook_environment(
+                changelog.package, changelog.version,
+                build_dir=builder.target_dir, result_dir=target_dir))
+        self._handle_build_dependencies(config, builder, build_deps, source)
+        try:
+            changes_path, differences = check_reproducible(
+                builder.target_dir, build_dir, builder.builder,
+                changelog.package, changelog.version,
+                source_date_epoch=changelog_timestamp(changelog),
+                log_dir=target_dir,
+                diffoscope_dir=(target_dir if diffoscope else None))
+            dget_changes(changes_path, target_dir)
+            run_hook(
+                tree, 'post-build', config, wd=builder.target_dir,
+                env=hook_environment(
+                    changelog.package, changelog.version,
+                    build_dir=builder.target_dir, result_dir=target_dir,
+                    changes_file=changes_path))
+        finally:
+            if not dont_purge:
+                builder.clean()
+                shutil.rmtree(
+                    os.path.join(build_dir, 'reproducible'),
+                    ignore_errors=True)
+        if not differences:
+            note(gettext("The binary packages are reproducible."))
+            return
+        for name, deb_differences in sorted(differences.items()):
+            note(gettext("%s differs between the builds:"), name)
+            for member, paths in deb_differences:
+                if paths:
+                    note("  %s: %s", member, ", ".join(paths))
+                else:
+                    note("  %s", member)
+        raise BzrCommandError(
+            gettext("The binary packages are not reproducible: %s") %
+            ", ".join(sorted(differences)))
+
+    def _build_architectures(self, tree, config, builder, build_dir,
+                             result_dir, is_local, location, changelog,
+                             architectures, dont_purge, build_deps, source):
+        from .builder import (
+            build_architectures,
+            merge_changes,
+            )
+        from .hooks import (
+            hook_environment,
+            run_hook,
+            )
+        from .util import dget_changes
+        architectures = [arch.strip() for arch in architectures
+                         if arch.strip()]
+        target_dir = self._get_target_dir(result_dir, is_local, location)
+        run_hook(
+            tree, 'pre-build', config, wd=builder.target_dir,
+            env=hook_environment(
+                changelog.package, changelog.version,
+                build_dir=builder.target_dir, result_dir=target_dir))
+        for arch in architectures:
+            self._handle_build_dependencies(
+                config, builder, build_deps, source, architecture=arch)
+        try:
+            succeeded, failed = build_architectures(
+                builder.target_dir, build_dir, builder.builder, architectures,
+                changelog.package, changelog.version, log_dir=target_dir)
+            if succeeded:
+                changes_paths = [succeeded[arch] for arch in architectures
+                                 if arch in succeeded]
+                if len(changes_paths) > 1:
+                    changes_path = merge_changes(
+                        changes_paths, build_dir, changelog.package,
+                        changelog.version)
+                else:
+                    changes_path = changes_paths[0]
+                changes_path = dget_changes(changes_path, target_dir)
+                run_hook(
+                    tree, 'post-build', config, wd=builder.target_dir,
+                    env=hook_environment(
+                        changelog.package, changelog.version,
+                        build_dir=builder.target_dir, result_dir=target_dir,
+                        changes_file=changes_path))
+        finally:
+            if not dont_purge:
+                builder.clean()
+                for arch in architectures:
+                    arch_source_dir = os.path.join(
+                        build_dir, arch,
+                        os.path.basename(builder.target_dir.rstrip('/')))
+                    if os.path.exists(arch_source_dir):
+                        shutil.rmtree(arch_source_dir)
+        for arch in architectures:
+            if arch in succeeded:
+                note(gettext("Build for %s succeeded."), arch)
+            else:
+                note(gettext("Build for %s failed."), arch)
+        if failed:
+            raise BzrCommandError(
+                gettext("The build failed for: %s") % ", ".join(failed))
 
 
 class cmd_get_orig_source(Command):
@@ -605,7 +1003,9 @@ class cmd_get_orig_source(Command):
     def run(self, directory='.', version=None):
         from .upstream import (
             AptSource,
+            ChecksumsFile,
             UpstreamProvider,
+            UpstreamSigningKeys,
             )
         from .upstream.uscan import (
             UScanSource,
@@ -638,10 +1038,14 @@ class cmd_get_orig_source(Command):
         else:
             upstream_sources.append(uscan_source)
 
+        checksums_file = ChecksumsFile.from_tree(tree, subpath, larstiq)
         upstream_provider = UpstreamProvider(
             changelog.package,
             str(version), orig_dir,
-            upstream_sources)
+            upstream_sources,
+            checksum_sources=[checksums_file] if checksums_file else None,
+            signing_keys=UpstreamSigningKeys.from_tree(
+                tree, subpath, larstiq))
 
         result = upstream_provider.provide(orig_dir)
         for tar, component in result:
@@ -760,7 +1164,12 @@ class cmd_merge_upstream(Command):
             guess_upstream_branch_url: bool = False):
         from debian.changelog import Version
 
-        from .hooks import run_hook
+        from .hooks import (
+            MergeUpstreamHookParams,
+            hook_environment,
+            run_builddeb_hooks,
+            run_hook,
+            )
         from .merge_upstream import (
             changelog_add_new_version,
             do_merge,
@@ -770,6 +1179,7 @@ class cmd_merge_upstream(Command):
         from .upstream import (
             PackageVersionNotPresent,
             TarfileSource,
+            UpstreamSigningKeys,
             )
         from .upstream.branch import (
             UpstreamBranchSource,
@@ -809,6 +1219,7 @@ class cmd_merge_upstream(Command):
             except NoSuchFile as e:
                 mutter('Copyright file not found: %s', e)
                 files_excluded = []
+            files_excluded.extend(config_list(config.files_excluded))
             contains_upstream_source = tree_contains_upstream_source(
                 tree, subpath)
             if changelog is None:
@@ -950,15 +1361,21 @@ class cmd_merge_upstream(Command):
                         "Specify the revision manually using --revision or "
                         "adjust 'export-upstream-revision' in the "
                         "configuration." % (version, upstream_branch_source))
+            params = MergeUpstreamHookParams(
+                tree, subpath, package, version, changelog,
+                upstream_branch=upstream_branch)
+            params.files_excluded.extend(files_excluded)
             if need_upstream_tarball:
                 target_dir = self.enter_context(tempfile.TemporaryDirectory())
+                components = [None] + config_list(config.components)
                 try:
                     locations = primary_upstream_source.fetch_tarballs(
-                        package, version, target_dir, components=[None])
+                        package, version, target_dir, components=components)
                 except PackageVersionNotPresent:
                     if upstream_revisions is not None:
                         locations = upstream_branch_source.fetch_tarballs(
-                            package, version, target_dir, components=[None],
+                            package, version, target_dir,
+                            components=components,
                             revisions=upstream_revisions)
                     else:
                         raise
@@ -973,15 +1390,26 @@ class cmd_merge_upstream(Command):
                         "are of different formats. Either delete the target "
                         "file, or use it as the argument to import."
                         % e.path)
+                signing_keys = UpstreamSigningKeys.from_tree(
+                    tree, subpath, top_level)
+                if signing_keys is not None:
+                    for tarball_filename in tarball_filenames:
+                        if signing_keys.verify(tarball_filename):
+                            note(gettext("Verified the signature of %s.") %
+                                 os.path.basename(tarball_filename))
+                params.tarballs.extend(tarball_filenames)
+            run_builddeb_hooks('pre_merge_upstream', params)
+            if need_upstream_tarball:
                 try:
                     conflicts, imported_revids = do_merge(
-                        tree, subpath, tarball_filenames, package, version,
+                        tree, subpath, params.tarballs, package, version,
                         current_version, upstream_branch, upstream_revisions,
                         merge_type, force=force,
                         force_pristine_tar=force_pristine_tar,
-                        files_excluded=files_excluded)
+                        files_excluded=params.files_excluded)
                 except PreviousVersionTagMissing as e:
                     raise BzrCommandError(str(e))
+                params.conflicts = conflicts
             if (current_version is not None and
                     Version(current_version) >= Version(version)):
                 raise BzrCommandError(
@@ -989,7 +1417,10 @@ class cmd_merge_upstream(Command):
                     version)
             changelog_add_new_version(
                 tree, subpath, version, distribution_name, changelog, package)
-            run_hook(tree, 'merge-upstream', config)
+            run_builddeb_hooks('post_merge_upstream', params)
+            run_hook(
+                tree, 'post-merge-upstream', config,
+                env=hook_environment(package, upstream_version=version))
         if not need_upstream_tarball:
             note(gettext("An entry for the new upstream version has been "
                  "added to the changelog."))
@@ -1060,8 +1491,14 @@ class cmd_import_dsc(Command):
                 name = file_details['name']
                 get_dsc_part(from_transport, name)
             db.import_package(os.path.join(orig_target, filename))
+        return dsc
 
     def run(self, files_list, file=None):
+        from debian.changelog import Version
+        from .hooks import (
+            hook_environment,
+            run_hook,
+            )
         from .import_dsc import (
             DistributionBranch,
             DistributionBranchSet,
@@ -1128,7 +1565,9 @@ class cmd_import_dsc(Command):
                     db.extract_upstream_tree(upstream_tips, tempdir)
                 else:
                     db.create_empty_upstream_tree(tempdir)
-                self.import_many(db, files_list, orig_target)
+                dsc = self.import_many(db, files_list, orig_target)
+            run_hook(tree, 'post-import', config, env=hook_environment(
+                dsc['Source'], Version(dsc['Version'])))
 
 
 class cmd_import_upstream(Command):
@@ -1165,6 +1604,11 @@ class cmd_import_upstream(Command):
     upstream branch, or previous tarball imports as necessary. In addition
     the parents of the new revision will be the previous upstream tarball
     import and the tip of the upstream branch if you supply one.
+
+    If the tarball has a detached signature next to it (the name of the
+    tarball with ".asc" appended) and the branch has the upstream signing
+    key in debian/upstream/signing-key.asc, the signature is verified before
+    the tarball is imported.
     """
 
     takes_options = [
@@ -1183,16 +1627,43 @@ class cmd_import_upstream(Command):
             DistributionBranch,
             DistributionBranchSet,
             )
+        from .repack_tarball import repack_tarball
+        from .upstream import (
+            UpstreamSigningKeys,
+            )
         from .util import (
+            MissingChangelogError,
+            find_changelog,
             md5sum_filename,
             )
         # TODO: search for similarity etc.
-        branch, _ = Branch.open_containing('.')
+        branch, subpath = Branch.open_containing('.')
         if upstream_branch is None:
             upstream = None
         else:
             upstream = Branch.open(upstream_branch)
         self.add_cleanup(branch.lock_write().unlock)
+        tarball_path = location
+        if not os.path.exists(tarball_path):
+            # Fetch the tarball along with its signature, so that they can
+            # be verified.
+            download_dir = self.enter_context(tempfile.TemporaryDirectory())
+            tarball_path = os.path.join(
+                download_dir, urlutils.basename(location))
+            repack_tarball(
+                location, os.path.basename(tarball_path),
+                target_dir=download_dir)
+        basis_tree = branch.basis_tree()
+        try:
+            (changelog, top_level) = find_changelog(
+                basis_tree, subpath, merge=True)
+        except MissingChangelogError:
+            top_level = False
+        signing_keys = UpstreamSigningKeys.from_tree(
+            basis_tree, subpath, top_level)
+        if signing_keys is not None and signing_keys.verify(tarball_path):
+            note(gettext("Verified the signature of %s.") %
+                 os.path.basename(tarball_path))
         tempdir = self.enter_context(tempfile.TemporaryDirectory(
             dir=branch.controldir.root_transport.clone('..')
             .local_abspath('.')))
@@ -1232,7 +1703,7 @@ class cmd_import_upstream(Command):
             raise BzrCommandError(gettext(
                 'bzr import-upstream --revision takes exactly'
                 ' one revision specifier.'))
-        tarballs = [(location, None, md5sum_filename(location))]
+        tarballs = [(tarball_path, None, md5sum_filename(tarball_path))]
         for (component, tag_name, revid,
              pristine_tar_imported) in db.import_upstream_tarballs(
                 tarballs, None, version, parents, upstream_branch=upstream,
@@ -1292,7 +1763,9 @@ class cmd_builddeb_do(Command):
             )
         from .upstream import (
             AptSource,
+            ChecksumsFile,
             UpstreamProvider,
+            UpstreamSigningKeys,
             )
         from .upstream.uscan import (
             UScanSource,
@@ -1301,7 +1774,10 @@ class cmd_builddeb_do(Command):
         from .upstream.pristinetar import (
             get_pristine_tar_source,
             )
-        from .hooks import run_hook
+        from .hooks import (
+            hook_environment,
+            run_hook,
+            )
         from .util import (
             find_changelog,
             guess_build_type,
@@ -1343,11 +1819,14 @@ class cmd_builddeb_do(Command):
         if orig_dir is None:
             orig_dir = default_orig_dir
 
+        checksums_file = ChecksumsFile.from_tree(t, subpath, top_level)
         upstream_provider = UpstreamProvider(
             changelog.package, changelog.version.upstream_version, orig_dir,
             [get_pristine_tar_source(t, t.branch),
              AptSource(),
-             UScanSource(t, subpath, top_level)])
+             UScanSource(t, subpath, top_level)],
+            checksum_sources=[checksums_file] if checksums_file else None,
+            signing_keys=UpstreamSigningKeys.from_tree(t, subpath, top_level))
 
         distiller = MergeModeDistiller(
                 t, subpath, upstream_provider, top_level=top_level)
@@ -1360,8 +1839,14 @@ class cmd_builddeb_do(Command):
 
         builder = DebBuild(distiller, build_source_dir, command)
         builder.prepare()
-        run_hook(t, 'pre-export', config)
+        run_hook(t, 'pre-export', config, env=hook_environment(
+            changelog.package, changelog.version,
+            build_dir=build_source_dir))
         builder.export()
+        run_hook(t, 'post-export', config, wd=build_source_dir,
+                 env=hook_environment(
+                     changelog.package, changelog.version,
+                     build_dir=build_source_dir))
         note(gettext('Running "%s" in the exported directory.') % (command))
         if give_instruction:
             note(gettext('If you want to cancel your changes then exit '
@@ -1399,6 +1884,112 @@ class cmd_builddeb_do(Command):
                      '"bzr add" or "bzr rm" as appropriate.'))
 
 
+class cmd_deb_patch(Command):
+    """Manage the quilt patches in debian/patches.
+
+    ACTION is one of:
+
+      new NAME      start a new patch on top of the applied patches
+      refresh       update the topmost applied patch with your changes
+      push          apply the next patch, or all with "push -a"
+      pop           unapply the topmost patch, or all with "pop -a"
+      list          list the patches, marking the ones that are applied
+      drop [NAME]   remove a patch from the series and delete it
+
+    Any further arguments are passed on to quilt.
+
+    In full source and native mode quilt is run in the working tree. In
+    merge mode the full source is exported to the build directory, which
+    is kept between runs so that the patches stay applied. Edit the files
+    there, adding them to the topmost patch with "quilt add" first, and
+    run "bzr deb-patch refresh" to update the patch. The changes to
+    debian/patches are then copied back to the branch.
+
+    If quilt fails, for instance because a refresh failed, the changes are
+    not copied back. New patches are added to the branch and dropped
+    patches removed from it; the changes are left uncommitted.
+
+    examples::
+
+        bzr deb-patch new fix-build.patch
+        bzr deb-patch refresh
+        bzr deb-patch pop -a
+    """
+
+    takes_args = ['action', 'arguments*']
+
+    def run(self, action, arguments_list=None):
+        from .quilt import (
+            QUILT_ACTIONS,
+            READ_ONLY_ACTIONS,
+            QuiltError,
+            UnknownQuiltAction,
+            copy_patches,
+            quilt_action,
+            update_patches_versioning,
+            )
+        from .util import (
+            find_changelog,
+            guess_build_type,
+            tree_contains_upstream_source,
+            )
+        if action not in QUILT_ACTIONS:
+            raise BzrCommandError(str(UnknownQuiltAction(action)))
+        if arguments_list is None:
+            arguments_list = []
+        t, subpath = WorkingTree.open_containing('.')
+        self.add_cleanup(t.lock_tree_write().unlock)
+        config = debuild_config(t, subpath)
+        contains_upstream_source = tree_contains_upstream_source(t, subpath)
+        (changelog, top_level) = find_changelog(
+            t, subpath, merge=not contains_upstream_source)
+        build_type = config.build_type
+        if build_type is None:
+            build_type = guess_build_type(
+                t, changelog.version, subpath, contains_upstream_source)
+        if top_level:
+            patches_path = os.path.join(subpath, 'patches')
+        else:
+            patches_path = os.path.join(subpath, 'debian', 'patches')
+
+        if build_type == BUILD_TYPE_MERGE:
+            build_dir = config.build_dir
+            if build_dir is None:
+                build_dir = default_build_dir
+            orig_dir = config.orig_dir
+            if orig_dir is None:
+                orig_dir = default_orig_dir
+            source_dir = os.path.join(
+                build_dir,
+                changelog.package + "-" + changelog.version.upstream_version)
+            use_existing = os.path.isdir(source_dir)
+            distiller = _get_distiller(
+                t, subpath, t.branch, changelog, build_type, config,
+                contains_upstream_source=contains_upstream_source,
+                top_level=top_level, orig_dir=orig_dir,
+                use_existing=use_existing)
+            distiller.distill(source_dir)
+        else:
+            source_dir = t.abspath(subpath)
+
+        try:
+            output = quilt_action(action, arguments_list, source_dir)
+        except QuiltError as e:
+            if e.output:
+                self.outf.write(e.output)
+            raise BzrCommandError(gettext(
+                'quilt %s failed; not updating debian/patches.') % e.command)
+        self.outf.write(output)
+        if action in READ_ONLY_ACTIONS:
+            return
+        if build_type == BUILD_TYPE_MERGE:
+            copy_patches(
+                os.path.join(source_dir, 'debian', 'patches'),
+                t.abspath(patches_path))
+            note(gettext('The patched source is in %s.'), source_dir)
+        update_patches_versioning(t, patches_path)
+
+
 class cmd_mark_uploaded(Command):
     """Mark that this branch has been uploaded, prior to pushing it.
 
@@ -1414,6 +2005,10 @@ class cmd_mark_uploaded(Command):
     hidden = True
 
     def run(self, merge=None, force=None):
+        from .hooks import (
+            hook_environment,
+            run_hook,
+            )
         from .import_dsc import (
             DistributionBranch,
             DistributionBranchSet,
@@ -1433,7 +2028,9 @@ class cmd_mark_uploaded(Command):
                     raise BzrCommandError(gettext(
                         "The changelog still targets "
                         "'UNRELEASED', so apparently hasn't been uploaded."))
-            db = DistributionBranch(t.branch, None)
+            db = DistributionBranch(
+                t.branch, None, tag_format=config.debian_tag,
+                sign_tags=config.sign_tags)
             dbs = DistributionBranchSet()
             dbs.add_branch(db)
             if db.has_version(changelog.version):
@@ -1444,6 +2041,8 @@ class cmd_mark_uploaded(Command):
                         "this new version."))
             tag_name = db.tag_version(changelog.version)
             self.outf.write(gettext("Tag '%s' created.\n") % tag_name)
+            run_hook(t, 'post-tag', config, env=hook_environment(
+                changelog.package, changelog.version, tag=tag_name))
 
 
 class cmd_dep3_patch(Command):
@@ -1545,6 +2144,351 @@ class cmd_dep3_patch(Command):
             last_update=last_update)
 
 
+class cmd_deb_pq_import(Command):
+    """Create a patch-queue branch from the patches in debian/patches.
+
+    The packaging branch (either that in the current working directory or
+    specified by --directory) is branched to LOCATION, after which each of
+    the patches in debian/patches/series is applied and committed
+    separately, using the description, authors and bugs from its DEP-3
+    header.
+
+    The patches can then be changed, reordered or added to by changing the
+    commits on the patch-queue branch, after which debian/patches is
+    regenerated with "bzr deb-pq-export". Changes to the debian directory on
+    the patch-queue branch are not included in the patches.
+
+    examples::
+
+        bzr deb-pq-import ../package-patch-queue
+    """
+
+    takes_args = ["location"]
+
+    directory_opt = Option('directory',
+                           help='Packaging tree to read the patches from.',
+                           short_name='d', type=str)
+
+    takes_options = [directory_opt]
+
+    def run(self, location, directory="."):
+        from .patch_queue import import_patch_queue
+        packaging_tree, subpath = WorkingTree.open_containing(directory)
+        self.add_cleanup(packaging_tree.lock_read().unlock)
+        _check_uncommitted(packaging_tree, subpath)
+        to_dir = packaging_tree.branch.controldir.sprout(
+            location, packaging_tree.last_revision(),
+            create_tree_if_local=True,
+            source_branch=packaging_tree.branch)
+        queue_tree = to_dir.open_workingtree()
+        self.add_cleanup(queue_tree.lock_write().unlock)
+        revids = import_patch_queue(queue_tree, subpath)
+        note(gettext("Created patch-queue branch %s with %d patches."),
+             location, len(revids))
+
+
+class cmd_deb_pq_export(Command):
+    """Regenerate debian/patches from a patch-queue branch.
+
+    Every commit on the patch-queue branch at LOCATION that is not on the
+    packaging branch (either that in the current working directory or
+    specified by --directory) becomes a patch in debian/patches, with a
+    DEP-3 header describing it. The patches that were previously listed in
+    debian/patches/series are replaced. The changes are left uncommitted in
+    the packaging tree.
+
+    The patch queue should not contain merges of the packaging branch; if
+    the packaging branch has moved on, create a new patch-queue branch or
+    rebase the old one.
+
+    examples::
+
+        bzr deb-pq-export ../package-patch-queue
+    """
+
+    takes_args = ["location"]
+
+    directory_opt = Option('directory',
+                           help='Packaging tree to write the patches to.',
+                           short_name='d', type=str)
+
+    takes_options = [directory_opt]
+
+    def run(self, location, directory="."):
+        from .patch_queue import export_patch_queue
+        packaging_tree, subpath = WorkingTree.open_containing(directory)
+        self.add_cleanup(packaging_tree.lock_tree_write().unlock)
+        queue_branch = Branch.open_containing(location)[0]
+        self.add_cleanup(queue_branch.lock_read().unlock)
+        graph = queue_branch.repository.get_graph(
+            packaging_tree.branch.repository)
+        base_revid = graph.find_unique_lca(
+            queue_branch.last_revision(), packaging_tree.last_revision())
+        names = export_patch_queue(
+            packaging_tree, queue_branch, base_revid, subpath)
+        if names:
+            note(gettext("Wrote %d patches to debian/patches: %s"),
+                 len(names), ", ".join(names))
+        else:
+            note(gettext("The patch queue is empty; removed all patches."))
+
+
+class cmd_deb_verify_source(Command):
+    """Check that the source package faithfully represents the branch.
+
+    The source package is built from the branch with dpkg-source, unpacked
+    again and compared with the branch, the way dgit requires them to be
+    identical. Every file that differs is listed, and whether the branch
+    matches the source package with the quilt patches applied or
+    unapplied is reported.
+
+    In merge mode only the files in the branch are compared, as the
+    upstream source comes from the upstream tarball.
+
+    The command fails if there are any differences.
+    """
+
+    takes_args = ["location?"]
+    takes_options = ['revision', orig_dir_opt]
+
+    def run(self, location=".", revision=None, orig_dir=None):
+        from .dgit import (
+            PATCHES_APPLIED,
+            PATCHES_UNAPPLIED,
+            DpkgSourceFailed,
+            verify_source,
+            )
+        from .util import (
+            find_changelog,
+            guess_build_type,
+            tree_contains_upstream_source,
+            )
+        tree, branch, subpath = ControlDir.open_containing_tree_or_branch(
+            location)
+        if revision is not None or tree is None:
+            if revision is not None and len(revision) == 1:
+                revid = revision[0].as_revision_id(branch)
+            else:
+                revid = branch.last_revision()
+            tree = branch.repository.revision_tree(revid)
+        self.add_cleanup(tree.lock_read().unlock)
+        config = debuild_config(tree, subpath)
+        contains_upstream_source = tree_contains_upstream_source(
+            tree, subpath)
+        (changelog, top_level) = find_changelog(
+            tree, subpath, merge=not contains_upstream_source)
+        build_type = config.build_type
+        if build_type is None:
+            build_type = guess_build_type(
+                tree, changelog.version, subpath, contains_upstream_source)
+        if orig_dir is None:
+            orig_dir = config.orig_dir
+        if orig_dir is None:
+            orig_dir = default_orig_dir
+        distiller = _get_distiller(
+            tree, subpath, branch, changelog, build_type, config,
+            contains_upstream_source=contains_upstream_source,
+            top_level=top_level, orig_dir=orig_dir)
+        with tempfile.TemporaryDirectory(
+                prefix='builddeb-verify-source-') as work_dir:
+            try:
+                state, differences = verify_source(
+                    tree, subpath, distiller, work_dir, changelog.package,
+                    changelog.version.upstream_version,
+                    merge=(build_type == BUILD_TYPE_MERGE),
+                    top_level=top_level)
+            except DpkgSourceFailed as e:
+                raise BzrCommandError(str(e))
+        if state == PATCHES_APPLIED:
+            note(gettext("The quilt patches are applied in the branch."))
+        elif state == PATCHES_UNAPPLIED:
+            note(gettext("The quilt patches are unapplied in the branch."))
+        else:
+            note(gettext("There are no quilt patches."))
+        for path, kind in differences:
+            self.outf.write("%s: %s\n" % (kind, path))
+        if differences:
+            raise BzrCommandError(gettext(
+                "The source package does not match the branch: "
+                "%d files differ.") % len(differences))
+        note(gettext("The source package matches the branch."))
+
+
+CONFIG_LAYERS = ['local', 'user', 'package']
+
+
+class cmd_deb_config(Command):
+    """Show or change the builddeb configuration.
+
+    Without arguments every option is listed with its effective value, the
+    file it was taken from and whether that file is trusted. Options that
+    run commands, such as builder and quick-builder, are only read from
+    trusted files: the configuration in your home directory and the local
+    configuration, which is not versioned.
+
+    Pass NAME to only show that option, NAME=VALUE to set it or --remove
+    to unset it. The --layer option selects the file to change:
+
+      local     debian/local.conf.local, or .bzr-builddeb/local.conf if
+                that exists (the default)
+      user      builddeb.conf in your Breezy configuration directory
+      package   debian/bzr-builddeb.conf, which is shared with everybody
+                working on the package
+
+    With --target the [BUILDDEB:DISTRIBUTION] section is changed instead
+    of [BUILDDEB], and listed options are looked up for that distribution.
+
+    Hooks that are set are listed too, with whether they will be run.
+    Hooks from files that are not trusted are refused, unless the branch
+    matches trusted-hook-branches or untrusted-hooks is set to prompt or
+    allow in your builddeb.conf. Pass the name of a hook as NAME to only
+    show that hook.
+
+    Keys that are outside of a section and keys that are not a known option
+    or hook are reported, as they are ignored. See "bzr help
+    builddeb-options" for the known options.
+
+    examples::
+
+        bzr deb-config
+        bzr deb-config result-dir=../results --layer=user
+        bzr deb-config --remove result-dir --layer=user
+    """
+
+    takes_args = ['name?']
+
+    directory_opt = Option('directory',
+                           help='Packaging tree to use the configuration of.',
+                           short_name='d', type=str)
+    remove_opt = Option('remove', help='Remove the option.')
+    layer_opt = Option(
+        'layer', type=str, argname="LAYER",
+        help="The configuration file to change: %s." % (
+            ", ".join(CONFIG_LAYERS)))
+
+    takes_options = [
+        directory_opt, remove_opt, layer_opt, cmd_builddeb.target_opt]
+
+    def _layer_path(self, tree, subpath, layer):
+        from . import global_conf
+        from .util import (
+            LOCAL_CONF,
+            NEW_CONF,
+            NEW_LOCAL_CONF,
+            )
+        if layer == 'user':
+            return global_conf(), None
+        if layer == 'package':
+            relpath = osutils.pathjoin(subpath, NEW_CONF)
+        elif layer == 'local':
+            relpath = osutils.pathjoin(subpath, NEW_LOCAL_CONF)
+            old_relpath = osutils.pathjoin(subpath, LOCAL_CONF)
+            if (not tree.has_filename(relpath) and
+                    tree.has_filename(old_relpath)):
+                relpath = old_relpath
+            if tree.is_versioned(relpath):
+                warning(gettext(
+                    "%s is versioned, so it is not used."), relpath)
+        else:
+            raise BzrCommandError(gettext(
+                "Unknown layer %s, should be one of: %s") % (
+                    layer, ", ".join(CONFIG_LAYERS)))
+        return tree.abspath(relpath), relpath
+
+    def _show(self, config, name):
+        from .config import KNOWN_OPTIONS
+        option = KNOWN_OPTIONS[name]
+        found = config.lookup(name)
+        if found is not None:
+            self.outf.write("%s = %s (%s [%s], %s)\n" % (
+                name, found.value, found.source, found.section,
+                "trusted" if found.trusted else "untrusted"))
+        elif option.default is not None:
+            self.outf.write("%s = %s (default)\n" % (name, option.default))
+        else:
+            self.outf.write("%s is not set\n" % name)
+        ignored = config.ignored_untrusted(name)
+        if ignored is not None:
+            warning(gettext(
+                "Ignoring %s = %s from [%s] in %s, as it is not trusted."),
+                name, ignored.value, ignored.section, ignored.source)
+
+    def _show_hook(self, config, hook_name):
+        from .hooks import untrusted_hook_action
+        found = config.find_hook(hook_name)
+        if found is None:
+            return False
+        if found.trusted:
+            status = "trusted"
+        else:
+            action = untrusted_hook_action(config)
+            status = "untrusted, %s" % {
+                'allow': 'allowed',
+                'prompt': 'prompted for',
+                'refuse': 'refused'}[action]
+            if action != 'refuse' and config.hook_sandbox is not None:
+                status += ", in the %s sandbox" % config.hook_sandbox
+        self.outf.write("%s hook = %s (%s [%s], %s)\n" % (
+            hook_name, found.value, found.source, found.section, status))
+        return True
+
+    def run(self, name=None, directory=".", remove=False, layer="local",
+            target=None):
+        from .config import (
+            KNOWN_OPTIONS,
+            DebBuildConfig,
+            remove_config_option,
+            set_config_option,
+            )
+        from .hooks import KNOWN_HOOKS
+        tree, subpath = WorkingTree.open_containing(directory)
+        value = None
+        if name is not None and '=' in name:
+            name, value = name.split('=', 1)
+        if value is None and not remove and name in KNOWN_HOOKS:
+            self.add_cleanup(tree.lock_read().unlock)
+            config = debuild_config(tree, subpath, target=target)
+            if not self._show_hook(config, name):
+                self.outf.write("%s hook is not set\n" % name)
+            return
+        if name is not None and name not in KNOWN_OPTIONS:
+            raise BzrCommandError(gettext("Unknown option: %s") % name)
+        if value is None and not remove:
+            self.add_cleanup(tree.lock_read().unlock)
+            config = debuild_config(tree, subpath, target=target)
+            if name is not None:
+                self._show(config, name)
+            else:
+                for option_name in sorted(KNOWN_OPTIONS):
+                    self._show(config, option_name)
+                for hook_name in KNOWN_HOOKS:
+                    self._show_hook(config, hook_name)
+            return
+        if name is None:
+            raise BzrCommandError(gettext("No option specified."))
+        if remove and value is not None:
+            raise BzrCommandError(gettext(
+                "--remove can not be used when setting a value."))
+        section = DebBuildConfig.section
+        if target is not None:
+            section = "%s:%s" % (section, target)
+        self.add_cleanup(tree.lock_write().unlock)
+        path, relpath = self._layer_path(tree, subpath, layer)
+        if remove:
+            remove_config_option(path, name, section)
+            return
+        if KNOWN_OPTIONS[name].trusted and layer == 'package':
+            warning(gettext(
+                "%s is only read from trusted files, so it will be ignored "
+                "in %s."), name, relpath)
+        parent = os.path.dirname(path)
+        if not os.path.isdir(parent):
+            os.makedirs(parent)
+        set_config_option(path, name, value, section)
+        if layer == 'package' and not tree.is_versioned(relpath):
+            tree.smart_add([path])
+
+
 class LocalTree(object):
 
     def __init__(self, branch):
@@ -1579,10 +2523,11 @@ class LocalTree(object):
 
 def _build_helper(
         local_tree, subpath, packaging_branch, target_dir, builder,
-        guess_upstream_branch_url=False):
+        guess_upstream_branch_url=False, report=None, builder_args=None):
     # TODO(jelmer): Integrate this with cmd_builddeb
     from .builder import (
         do_build,
+        get_builder,
         )
     from .util import (
         find_changelog,
@@ -1592,19 +2537,27 @@ def _build_helper(
     (changelog, top_level) = find_changelog(
         local_tree, subpath, merge=False, max_blocks=2)
 
-    config = debuild_config(local_tree, subpath)
+    config = debuild_config(local_tree, subpath, branch=packaging_branch)
     contains_upstream_source = tree_contains_upstream_source(
         local_tree, subpath)
+    builder = get_builder(builder, config, builder_args)
+
+    build_type = _find_build_type(
+        local_tree, subpath, changelog, None, config,
+        contains_upstream_source=contains_upstream_source)
+    if report is None:
+        report = config.build_report
 
     distiller = _get_distiller(
-            local_tree, subpath, packaging_branch, build_type=None,
+            local_tree, subpath, packaging_branch, build_type=build_type,
             config=config, changelog=changelog,
             contains_upstream_source=contains_upstream_source,
             top_level=top_level,
             guess_upstream_branch_url=guess_upstream_branch_url)
 
     return do_build(changelog.package, changelog.version, distiller,
-                    local_tree, config, builder, target_dir)
+                    local_tree, config, builder, target_dir,
+                    build_type=build_type, report=report)
 
 
 class cmd_debrelease(Command):
@@ -1627,10 +2580,21 @@ class cmd_debrelease(Command):
         Option('skip-upload', help='Skip upload.'), builder_opt]
 
     def run(self, location='.', strict=True, skip_upload=False,
-            builder="sbuild --source --source-only-changes"):
+            builder=None):
+        from .builder import (
+            SbuildBuilder,
+            builder_registry,
+            )
+        from .hooks import (
+            ReleaseHookParams,
+            hook_environment,
+            run_builddeb_hooks,
+            run_hook,
+            )
         from .release import release
         from .util import (
             dput_changes,
+            find_changelog,
             )
 
         branch, subpath = Branch.open_containing(location)
@@ -1639,13 +2603,34 @@ class cmd_debrelease(Command):
         # clean.
         with LocalTree(branch) as local_tree:
             _check_tree(local_tree, subpath, strict)
+            config = debuild_config(local_tree, subpath, branch=branch)
+            (changelog, top_level) = find_changelog(
+                local_tree, subpath, merge=False, max_blocks=2)
+            params = ReleaseHookParams(local_tree, subpath, changelog)
+            run_builddeb_hooks('pre_release', params)
+            run_hook(local_tree, 'pre-release', config, env=hook_environment(
+                changelog.package, changelog.version))
             release(local_tree, subpath)
 
+            if builder is None:
+                builder = config.builder or SbuildBuilder.name
+            try:
+                builder_args = builder_registry.get(
+                    builder).source_only_changes_args
+            except KeyError:
+                builder_args = None
             with tempfile.TemporaryDirectory() as td:
                 changes_file = _build_helper(
                         local_tree, subpath, local_tree.branch,
                         target_dir=(td if not skip_upload else None),
-                        builder=builder)
+                        builder=builder, builder_args=builder_args)
                 if not skip_upload:
                     dput_changes(changes_file)
+                params.changes_file = changes_file
+                run_builddeb_hooks('post_release', params)
+                run_hook(
+                    local_tree, 'post-release', config,
+                    env=hook_environment(
+                        changelog.package, changelog.version,
+                        changes_file=changes_file))
             local_tree.branch.push(branch)
@@ -20,11 +20,16 @@
 
 from __future__ import absolute_import
 
+from collections import namedtuple
+from io import BytesIO, StringIO
+import re
+
 import yaml
 
 from ...config import (
   configobj,
   ConfigObj,
+  NoSuchConfigOption,
   TreeConfig,
   )
 from ...errors import BzrError, NoSuchFile
@@ -36,6 +41,212 @@ BUILD_TYPE_NATIVE = "native"
 BUILD_TYPE_MERGE = "merge"
 BUILD_TYPE_SPLIT = "split"
 
+BRANCH_SOURCE = "branch configuration"
+TREE_SOURCE = "svn-buildpackage properties"
+
+# Options read by DebBuildConfig, by name.
+KNOWN_OPTIONS = {}
+
+# Options that are no longer read, and the options that replace them.
+DEPRECATED_OPTIONS = {
+    'export-upstream': 'upstream-branch',
+    'larstiq': None,
+    }
+
+# Where the value of an option was found; trusted is whether the source
+# is trusted for sensitive options.
+ConfigValue = namedtuple(
+    'ConfigValue', ['value', 'source', 'section', 'trusted'])
+
+
+class ConfigOption(object):
+    """An option in the [BUILDDEB] section of the configuration files.
+
+    :ivar name: name of the option, e.g. "build-dir"
+    :ivar help: one line description
+    :ivar type: str, bool or list; list options are a comma separated
+        list of values
+    :ivar default: the value used if the option is not set
+    :ivar trusted: whether the option is only read from trusted files
+    :ivar choices: the allowed values, or None to allow any value
+    """
+
+    def __init__(self, name, help, type=str, default=None, trusted=False,
+                 choices=None):
+        self.name = name
+        self.help = help
+        self.type = type
+        self.default = default
+        self.trusted = trusted
+        self.choices = choices
+
+    def __repr__(self):
+        return "<%s(%r)>" % (self.__class__.__name__, self.name)
+
+    def validate(self, value):
+        """Check a value of the option from a configuration file.
+
+        :return: None if the value is valid, or the reason it is not
+        """
+        if self.type is bool:
+            if not isinstance(value, str) or (
+                    value.lower() not in configobj.ConfigObj._bools):
+                return "expected a boolean"
+        if self.choices is not None:
+            if value not in self.choices:
+                return "expected one of %s" % ", ".join(self.choices)
+        return None
+
+
+class ConfigValueError(BzrError):
+
+    _fmt = "%(location)s: invalid value %(value)r for %(name)s: %(reason)s"
+
+    def __init__(self, location, name, value, reason):
+        BzrError.__init__(
+            self, location=location, name=name, value=value, reason=reason)
+
+
+_SECTION_RE = re.compile(r'^\s*\[+\s*(.*?)\s*\]+')
+_KEY_RE = re.compile(r'^\s*([^#=\[\s][^=]*?)\s*=')
+
+
+def _find_line(lines, section, key):
+    """Find the line a key is set on in a configuration file.
+
+    :param section: name of the section, or None for keys outside of
+        any section
+    :return: the line number, counting from 1, or None
+    """
+    current = None
+    for i, line in enumerate(lines):
+        m = _SECTION_RE.match(line)
+        if m:
+            current = m.group(1)
+            continue
+        m = _KEY_RE.match(line)
+        if m and current == section and m.group(1).strip('"\'') == key:
+            return i + 1
+    return None
+
+
+def _location(filename, lines, section, key):
+    line = _find_line(lines, section, key)
+    if line is None:
+        return filename
+    return "%s:%d" % (filename, line)
+
+
+def validate_config(config, lines, section='BUILDDEB'):
+    """Validate the keys in a configuration file.
+
+    :param config: the ConfigObj
+    :param lines: the lines of the file, to report line numbers
+    :param section: the main section, e.g. BUILDDEB
+    :raises ConfigValueError: if a known option has an invalid value
+    :return: list of warnings about keys that are ignored
+    """
+    from .hooks import KNOWN_HOOKS
+    filename = config.filename or '<unknown>'
+    problems = []
+    for key in config.scalars:
+        problems.append(
+            "%s: '%s' is not in a section, so it is ignored" % (
+                _location(filename, lines, None, key), key))
+    for name in config.sections:
+        base = name.split(':', 1)[0]
+        for key in config[name].scalars:
+            location = _location(filename, lines, name, key)
+            if base == 'HOOKS':
+                if key not in KNOWN_HOOKS:
+                    problems.append(
+                        "%s: '%s' in [%s] is not a known hook" % (
+                            location, key, name))
+                continue
+            if base != section:
+                continue
+            if key in DEPRECATED_OPTIONS:
+                replacement = DEPRECATED_OPTIONS[key]
+                if replacement is None:
+                    problems.append(
+                        "%s: '%s' is deprecated and ignored" % (
+                            location, key))
+                else:
+                    problems.append(
+                        "%s: '%s' is deprecated and ignored, use '%s' "
+                        "instead" % (location, key, replacement))
+                continue
+            try:
+                option = KNOWN_OPTIONS[key]
+            except KeyError:
+                problems.append(
+                    "%s: '%s' in [%s] is not a known option" % (
+                        location, key, name))
+                continue
+            reason = option.validate(config[name][key])
+            if reason is not None:
+                raise ConfigValueError(
+                    location, key, config[name][key], reason)
+    return problems
+
+
+def _read_config(source):
+    """Read a configuration file.
+
+    :param source: path to the file, or a file object
+    :return: tuple with a ConfigObj and the lines of the file
+    """
+    if isinstance(source, str):
+        try:
+            with open(source, 'rb') as f:
+                content = f.read()
+        except FileNotFoundError:
+            return ConfigObj(source), []
+        config = ConfigObj(source)
+    else:
+        content = source.read()
+        if isinstance(content, bytes):
+            config = ConfigObj(BytesIO(content))
+        else:
+            config = ConfigObj(StringIO(content))
+    if isinstance(content, bytes):
+        content = content.decode('utf-8', 'replace')
+    return config, content.splitlines()
+
+
+def describe_options(topic=None):
+    """Describe the known options, as help text.
+
+    :param topic: name of the help topic, unused
+    """
+    ret = ["Options in the [BUILDDEB] section of the builddeb "
+           "configuration files",
+           "",
+           "See ``bzr deb-config`` for the effective values.",
+           ""]
+    for name in sorted(KNOWN_OPTIONS):
+        option = KNOWN_OPTIONS[name]
+        ret.append(":%s:" % name)
+        ret.append("    %s." % option.help.rstrip('.'))
+        if option.type is bool:
+            ret.append("    A boolean, defaults to %s." % option.default)
+        elif option.type is list:
+            ret.append("    A comma separated list.")
+        if option.choices is not None:
+            ret.append("    One of: %s." % ", ".join(option.choices))
+        if option.trusted:
+            ret.append("    Only read from trusted files.")
+        ret.append("")
+    ret.append("Deprecated options, which are ignored:")
+    ret.append("")
+    for name in sorted(DEPRECATED_OPTIONS):
+        replacement = DEPRECATED_OPTIONS[name]
+        if replacement is None:
+            ret.append(":%s: no longer used" % name)
+        else:
+            ret.append(":%s: use %s instead" % (name, replacement))
+    return "\n".join(ret) + "\n"
+
 
 class SvnBuildPackageMappedConfig(object):
     """Config object that provides a bzr-builddeb configuration
@@ -77,15 +288,28 @@ class UpstreamMetadataConfig(object):
     def __init__(self, text):
         try:
             self.metadata = yaml.safe_load(text)
-        except (yaml.scanner.ScannerError, yaml.composer.ComposerError,
-                yaml.parser.ParserError) as e:
-            raise UpstreamMetadataSyntaxError('debian/upstream/metadata', e)
+        except yaml.YAMLError as e:
+            mark = getattr(e, 'problem_mark', None)
+            if mark is not None:
+                raise UpstreamMetadataSyntaxError(
+                    '%s:%d' % (self.filename, mark.line + 1),
+                    getattr(e, 'problem', None) or e)
+            raise UpstreamMetadataSyntaxError(self.filename, e)
+        if self.metadata is None:
+            self.metadata = {}
         if isinstance(self.metadata, str):
             raise UpstreamMetadataSyntaxError(
               'debian/upstream/metadata', TypeError(self.metadata))
         if isinstance(self.metadata, list):
             raise UpstreamMetadataSyntaxError(
               'debian/upstream/metadata', TypeError(self.metadata))
+        for field in ('Repository', 'Repository-Tag-Prefix'):
+            value = self.metadata.get(field)
+            if value is not None and not isinstance(value, str):
+                raise UpstreamMetadataSyntaxError(
+                    self.filename,
+                    TypeError("%s should be a string, not %r" % (
+                        field, value)))
 
     def get_value(self, section, option):
         if section == "BUILDDEB":
@@ -95,6 +319,11 @@ class UpstreamMetadataConfig(object):
                 tag_prefix = self.metadata.get("Repository-Tag-Prefix")
                 if tag_prefix is not None:
                     return "tag:" + tag_prefix + "$UPSTREAM_VERSION"
+            if option == "pypi-project":
+                for entry in self.metadata.get('Registry') or []:
+                    if (isinstance(entry, dict) and
+                            entry.get('Name') == 'PyPI'):
+                        return entry.get('Entry')
         raise KeyError
 
     def __getitem__(self, key):
@@ -113,7 +342,11 @@ class DebBuildConfig(object):
     debian/bzr-builddeb.conf.local,
     ~/.bazaar/builddeb.conf, debian/bzr-builddeb.conf,
     finally .bzr-builddeb/default.conf. The value is
-    taken from the first file in which it is specified."""
+    taken from the first file in which it is specified.
+
+    Within a file, sections for the targets, such as
+    [BUILDDEB:bookworm-backports], take precedence over the generic
+    section."""
 
     section = 'BUILDDEB'
 
@@ -154,9 +387,10 @@ class DebBuildConfig(object):
         userbuild
         """
         self._config_files = []
+        self._problems = []
         for input in files:
             try:
-                config = ConfigObj(input[0])
+                config, lines = _read_config(input[0])
             except configobj.ParseError as e:
                 if len(input) > 2:
                     content = input[2]
@@ -166,6 +400,10 @@ class DebBuildConfig(object):
                 continue
             if len(input) > 2:
                 config.filename = input[2]
+            problems = validate_config(config, lines, self.section)
+            for problem in problems:
+                warning(problem)
+            self._problems.extend(problems)
             self._config_files.append((config, input[1]))
         if branch is not None:
             self._branch_config = TreeConfig(branch)
@@ -199,6 +437,43 @@ class DebBuildConfig(object):
                 self._config_files.append(
                     (UpstreamMetadataConfig(upstream_metadata_text), False))
         self.user_config = None
+        self.targets = []
+        self.branch_url = None
+
+    def set_targets(self, targets):
+        """Set the targets to use the sections of.
+
+        :param targets: list of target names, most specific first, e.g.
+            ["bookworm-backports", "debian"]
+        """
+        self.targets = list(targets)
+        mutter("Using configuration for targets: %r", self.targets)
+
+    def _sections(self, section):
+        return ["%s:%s" % (section, target) for target in self.targets] + [
+            section]
+
+    def add_config(self, config, trusted):
+        """Add a layer with a lower precedence than the existing ones.
+
+        :param config: an object that maps the values, such as GbpConfig,
+            with get_value and get_bool methods like ConfigObj
+        :param trusted: whether values of sensitive options can be taken
+            from it
+        """
+        self._config_files.append((config, trusted))
+
+    def get_layer(self, source):
+        """Get the config object that a ConfigValue was taken from.
+
+        :param source: the source of the ConfigValue
+        :return: the config object, or None if the value wasn't taken from
+            one of the configuration files
+        """
+        for config, trusted in self._config_files:
+            if getattr(config, 'filename', None) == source:
+                return config
+        return None
 
     def set_user_config(self, user_conf):
         if user_conf is not None:
@@ -206,10 +481,11 @@ class DebBuildConfig(object):
 
     def _user_config_value(self, key):
         if self.user_config is not None:
-            try:
-                return self.user_config.get_value(self.section, key)
-            except KeyError:
-                pass
+            for section in self._sections(self.section):
+                try:
+                    return self.user_config.get_value(section, key)
+                except KeyError:
+                    pass
         return None
 
     def _get_opt(self, config, key, section=None):
@@ -220,16 +496,41 @@ class DebBuildConfig(object):
         try:
             return config.get_value(section, key)
         except KeyError:
-            pass
-        if config.filename is not None:
-            try:
-                config[key]
-                warning(
-                    "'%s' defines a value for '%s', but it is not in a '%s' "
-                    "section, so it is ignored" % (
-                      config.filename, key, section))
-            except KeyError:
-                pass
+            return None
+
+    def _find_best_opt(self, key, trusted=False, section=None):
+        """Find the value for key, obeying precedence.
+
+        :return: a ConfigValue, or None if none of the files define it
+        """
+        if section is None:
+            section = self.section
+        sections = self._sections(section)
+        if not trusted:
+            for s in sections:
+                if self._branch_config is not None:
+                    value = self._branch_config.get_option(key, section=s)
+                    if value is not None:
+                        mutter("Using %s for %s, taken from [%s] in the "
+                               "branch", value, key, s)
+                        return ConfigValue(value, BRANCH_SOURCE, s, False)
+            if self._tree_config is not None:
+                for s in sections:
+                    value = self._tree_config.get_option(key, section=s)
+                    if value is not None:
+                        mutter("Using %s for %s, taken from [%s] in the "
+                               "tree", value, key, s)
+                        return ConfigValue(value, TREE_SOURCE, s, False)
+        for config_file in self._config_files:
+            if not trusted or config_file[1]:
+                for s in sections:
+                    value = self._get_opt(config_file[0], key, section=s)
+                    if value is not None:
+                        mutter("Using %s for %s, taken from [%s] in %s",
+                               value, key, s, config_file[0].filename)
+                        return ConfigValue(
+                            value, config_file[0].filename, s,
+                            config_file[1])
         return None
 
     def _get_best_opt(self, key, trusted=False, section=None):
@@ -242,48 +543,63 @@ class DebBuildConfig(object):
         marked as trusted.
 
         """
-        if section is None:
-            section = self.section
-        if not trusted:
-            if self._branch_config is not None:
-                value = self._branch_config.get_option(
-                    key, section=self.section)
-                if value is not None:
-                    mutter("Using %s for %s, taken from the branch",
-                           value, key)
-                    return value
-            if self._tree_config is not None:
-                value = self._tree_config.get_option(key, section=self.section)
-                if value is not None:
-                    mutter("Using %s for %s, taken from the tree", value, key)
-                    return value
-        for config_file in self._config_files:
-            if not trusted or config_file[1]:
-                value = self._get_opt(config_file[0], key, section=section)
-                if value is not None:
-                    mutter("Using %s for %s, taken from %s", value, key,
-                           config_file[0].filename)
-                    return value
-        return None
+        found = self._find_best_opt(key, trusted, section)
+        if found is None:
+            return None
+        return found.value
+
+    def find_hook(self, hook_name):
+        """Find the command for a hook and where it is set.
+
+        :return: a ConfigValue, or None if the hook is not set
+        """
+        return self._find_best_opt(hook_name, section='HOOKS')
 
     def get_hook(self, hook_name):
-        return self._get_best_opt(hook_name, section='HOOKS')
+        found = self.find_hook(hook_name)
+        if found is None:
+            return None
+        return found.value
 
-    def _get_bool(self, config, key):
+    def _get_bool(self, config, key, section='BUILDDEB'):
         try:
-            return True, config.get_bool('BUILDDEB', key)
+            return True, config.get_bool(section, key)
         except KeyError:
-            pass
-        if config.filename is not None:
-            try:
-                config.as_bool(key)
-                warning(
-                    "'%s' defines a value for '%s', but it is not in a "
-                    "'BUILDDEB' section, so it is ignored" %
-                    (config.filename, key))
-            except KeyError:
-                pass
-        return False, False
+            return False, False
+
+    def _find_best_bool(self, key, trusted=False):
+        """Find the boolean value of key, obeying precedence.
+
+        :return: a ConfigValue, or None if none of the files define it
+        """
+        sections = self._sections(self.section)
+        if not trusted:
+            for s in sections:
+                if self._branch_config is not None:
+                    value = self._branch_config.get_option(key, section=s)
+                    if value is not None:
+                        mutter("Using %s for %s, taken from [%s] in the "
+                               "branch", value, key, s)
+                        return ConfigValue(value, BRANCH_SOURCE, s, False)
+            if self._tree_config is not None:
+                for s in sections:
+                    value = self._tree_config.get_option(key, section=s)
+                    if value is not None:
+                        mutter("Using %s for %s, taken from [%s] in the "
+                               "tree", value, key, s)
+                        return ConfigValue(value, TREE_SOURCE, s, False)
+        for config_file in self._config_files:
+            if not trusted or config_file[1]:
+                for s in sections:
+                    (found, value) = self._get_bool(
+                        config_file[0], key, section=s)
+                    if found:
+                        mutter("Using %s for %s, taken from [%s] in %s",
+                               str(value), key, s, config_file[0].filename)
+                        return ConfigValue(
+                            value, config_file[0].filename, s,
+                            config_file[1])
+        return None
 
     def _get_best_bool(self, key, trusted=False, default=False):
         """Returns the value of key, obeying precedence.
@@ -295,33 +611,60 @@ class DebBuildConfig(object):
         marked as trusted.
 
         """
-        if not trusted:
-            if self._branch_config is not None:
-                value = self._branch_config.get_option(
-                    key, section=self.section)
-                if value is not None:
-                    mutter("Using %s for %s, taken from the branch",
-                           value, key)
-                    return value
-            if self._tree_config is not None:
-                value = self._tree_config.get_option(key, section=self.section)
-                if value is not None:
-                    mutter("Using %s for %s, taken from the tree", value, key)
-                    return value
-        for config_file in self._config_files:
-            if not trusted or config_file[1]:
-                (found, value) = self._get_bool(config_file[0], key)
-                if found:
-                    mutter("Using %s for %s, taken from %s", str(value), key,
-                           config_file[0].filename)
-                    return value
-        return default
-
-    def _opt_property(name, help=None, trusted=False):
+        found = self._find_best_bool(key, trusted)
+        if found is None:
+            return default
+        return found.value
+
+    def lookup(self, name):
+        """Find the effective value of a known option and where it is set.
+
+        :param name: name of the option, e.g. "build-dir"
+        :return: a ConfigValue, or None if the option is not set
+        :raises KeyError: if name is not a known option
+        """
+        option = KNOWN_OPTIONS[name]
+        if option.type is bool:
+            return self._find_best_bool(name, option.trusted)
+        return self._find_best_opt(name, option.trusted)
+
+    def ignored_untrusted(self, name):
+        """Find a value of a trusted option that is ignored.
+
+        :param name: name of an option that is only read from trusted files
+        :return: a ConfigValue for the value from an untrusted source that
+            would have been used if the option wasn't trusted, or None
+        """
+        option = KNOWN_OPTIONS[name]
+        if not option.trusted:
+            return None
+        if option.type is bool:
+            found = self._find_best_bool(name, False)
+        else:
+            found = self._find_best_opt(name, False)
+        if found is None or found.trusted:
+            return None
+        return found
+
+    def check(self):
+        """Return the warnings about the configuration files.
+
+        :return: list of messages about keys that are ignored, because they
+            are not in a section, are deprecated or are not known options
+            or hooks
+        """
+        return list(self._problems)
+
+    def _opt_property(name, help=None, trusted=False, type=str,
+                      choices=None):
+        KNOWN_OPTIONS[name] = ConfigOption(
+            name, help, type=type, trusted=trusted, choices=choices)
         return property(lambda self: self._get_best_opt(name, trusted), None,
                         None, help)
 
     def _bool_property(name, help=None, trusted=False, default=False):
+        KNOWN_OPTIONS[name] = ConfigOption(
+            name, help, type=bool, default=default, trusted=trusted)
         return property(
             lambda self: self._get_best_bool(name, trusted, default),
             None, None, help)
@@ -336,7 +679,52 @@ class DebBuildConfig(object):
 
     user_orig_dir = property(lambda self: self._user_config_value('orig-dir'))
 
-    builder = _opt_property('builder', "The command to build with", True)
+    builder = _opt_property(
+        'builder', "The builder or command to build with", True)
+
+    builder_distribution = _opt_property(
+        'builder-distribution', "The distribution the builder builds for")
+
+    builder_architecture = _opt_property(
+        'builder-architecture', "The architecture the builder builds for")
+
+    builder_extra_repositories = _opt_property(
+        'builder-extra-repositories',
+        "Extra apt repositories the builder uses", True, type=list)
+
+    builder_run_tests = _bool_property(
+        'builder-run-tests', "Run the test suite when building",
+        default=True)
+
+    host_arch = _opt_property(
+        'host-arch', "The architecture to cross-build for")
+
+    build_profiles = _opt_property(
+        'build-profiles', "The build profiles to build with", type=list)
+
+    build_options = _opt_property(
+        'build-options', "The DEB_BUILD_OPTIONS to build with", type=list)
+
+    build_deps = _opt_property(
+        'build-deps', "What to do about missing build dependencies",
+        choices=['check', 'print', 'install'])
+
+    build_report = _bool_property(
+        'build-report',
+        "Write a machine-readable report of the build next to the changes "
+        "file")
+
+    build_deps_installer = _opt_property(
+        'build-deps-installer',
+        "The command to install missing build dependencies with", True)
+
+    container_image = _opt_property(
+        'container-image', "The image the container builders build in")
+
+    container_setup = _opt_property(
+        'container-setup',
+        "Commands to run in the container before building", True,
+        type=list)
 
     result_dir = _opt_property('result-dir', "The dir to put the results in")
 
@@ -363,6 +751,25 @@ class DebBuildConfig(object):
 
     split = _bool_property('split', "Split a full source package")
 
+    dgit = _bool_property(
+        'dgit', "Export the source with the quilt patches applied, as dgit "
+        "does")
+
+    go_proxy = _opt_property(
+        'go-proxy', "The Go module proxy to fetch Go modules from", True)
+
+    pypi_mirror = _opt_property(
+        'pypi-mirror', "The PyPI file host to fetch sdists from", True)
+
+    upstream_registry = _opt_property(
+        'upstream-registry',
+        "The language registry to fetch the upstream source from in merge "
+        "mode, overriding the one the package name suggests",
+        choices=['go', 'pypi', 'none'])
+
+    pypi_project = _opt_property(
+        'pypi-project', "The name of the project on PyPI")
+
     upstream_branch = _opt_property(
         'upstream-branch', "The upstream branch to merge from")
 
@@ -370,6 +777,76 @@ class DebBuildConfig(object):
         'export-upstream-revision',
         "The revision of the upstream source to use.")
 
+    compression = _opt_property(
+        'compression',
+        "The compression of tarballs exported from the upstream branch",
+        choices=['auto', 'gzip', 'bzip2', 'xz', 'lzma'])
+
+    components = _opt_property(
+        'components', "The additional upstream tarballs", type=list)
+
+    files_excluded = _opt_property(
+        'files-excluded',
+        "Files to leave out when importing upstream tarballs, in addition "
+        "to Files-Excluded in debian/copyright", type=list)
+
+    debian_branch = _opt_property(
+        'debian-branch', "The branch the packaging is done on")
+
+    debian_tag = _opt_property(
+        'debian-tag',
+        "The format of the tags for uploads, e.g. debian/%(version)s")
+
+    sign_tags = _bool_property(
+        'sign-tags', "Sign the revisions that are tagged for uploads")
+
+    untrusted_hooks = _opt_property(
+        'untrusted-hooks',
+        "What to do with hooks from files that are not trusted, such as "
+        "debian/bzr-builddeb.conf", True,
+        choices=['refuse', 'prompt', 'allow'])
+
+    trusted_hook_branches = _opt_property(
+        'trusted-hook-branches',
+        "URLs of branches to run the hooks of, which may contain wildcards",
+        True, type=list)
+
+    hook_sandbox = _opt_property(
+        'hook-sandbox',
+        "The sandbox to run hooks from files that are not trusted in",
+        True, choices=['bwrap', 'unshare'])
+
+
+def set_config_option(path, name, value, section=DebBuildConfig.section):
+    """Set an option in a configuration file.
+
+    :param path: path to the configuration file, which is created if it
+        does not exist
+    :param name: name of the option
+    :param value: the new value
+    :param section: the section to set the option in
+    """
+    config = ConfigObj(path)
+    config.setdefault(section, {})[name] = value
+    with open(path, 'wb') as f:
+        config.write(f)
+
+
+def remove_config_option(path, name, section=DebBuildConfig.section):
+    """Remove an option from a configuration file.
+
+    :raises NoSuchConfigOption: if the option is not set in the file
+    """
+    config = ConfigObj(path)
+    try:
+        del config[section][name]
+    except KeyError:
+        raise NoSuchConfigOption(name)
+    if not config[section]:
+        del config[section]
+    with open(path, 'wb') as f:
+        config.write(f)
+
 
 def _test():
     import doctest
@@ -0,0 +1,282 @@
+#    dgit.py -- Check that a source package represents a branch
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Checking that the source package built from a branch matches it.
+
+dgit requires the tree that is pushed to be identical to the result of
+unpacking the source package, so this is worth checking before an upload.
+"""
+
+from __future__ import absolute_import
+
+import os
+import stat
+import subprocess
+
+from ...errors import BzrError
+from ...export import export
+
+from .util import subprocess_setup
+
+
+PATCHES_APPLIED = 'applied'
+PATCHES_UNAPPLIED = 'unapplied'
+
+DIFF_MODIFIED = 'modified'
+DIFF_KIND = 'kind changed'
+DIFF_MODE = 'mode changed'
+DIFF_ONLY_IN_BRANCH = 'only in branch'
+DIFF_ONLY_IN_SOURCE = 'only in source package'
+
+# Files that dpkg-source creates when unpacking, which are never in a branch.
+SOURCE_PACKAGE_IGNORES = ['.pc']
+
+
+class DpkgSourceFailed(BzrError):
+
+    _fmt = "%(command)s failed: %(output)s"
+
+    def __init__(self, command, output):
+        BzrError.__init__(self, command=command, output=output)
+
+
+def _run_dpkg_source(args, cwd):
+    proc = subprocess.Popen(
+        ['dpkg-source'] + args, cwd=cwd, stdout=subprocess.PIPE,
+        stderr=subprocess.STDOUT, preexec_fn=subprocess_setup)
+    output = proc.communicate()[0].decode('utf-8', 'replace')
+    if proc.returncode != 0:
+        raise DpkgSourceFailed(
+            " ".join(['dpkg-source'] + args), output)
+    return output
+
+
+def _series(source_dir):
+    try:
+        with open(os.path.join(
+                source_dir, 'debian', 'patches', 'series'), 'r') as f:
+            lines = f.readlines()
+    except FileNotFoundError:
+        return []
+    return [line.split()[0] for line in lines
+            if line.strip() and not line.startswith('#')]
+
+
+def _patch_applies(source_dir, patch, reverse=False):
+    args = ['patch', '-p1', '--dry-run', '--force', '--silent', '-i',
+            os.path.join('debian', 'patches', patch)]
+    if reverse:
+        args.append('--reverse')
+    with open(os.devnull, 'wb') as devnull:
+        return subprocess.call(
+            args, cwd=source_dir, stdout=devnull, stderr=devnull,
+            preexec_fn=subprocess_setup) == 0
+
+
+def patches_applied(source_dir):
+    """Check whether the quilt patches in a source tree are applied.
+
+    :param source_dir: the source tree
+    :return: True if they are applied, False if they are not, or None if
+        there are no patches or it could not be determined
+    """
+    series = _series(source_dir)
+    if not series:
+        return None
+    if os.path.exists(os.path.join(source_dir, '.pc', 'applied-patches')):
+        return True
+    if _patch_applies(source_dir, series[-1], reverse=True):
+        return True
+    if _patch_applies(source_dir, series[0]):
+        return False
+    return None
+
+
+def apply_patches(source_dir):
+    """Apply the quilt patches in a source tree, the way dpkg-source does."""
+    _run_dpkg_source(['--before-build', os.path.basename(source_dir)],
+                     os.path.dirname(os.path.abspath(source_dir)))
+
+
+def build_source_package(source_dir):
+    """Build a source package from an unpacked source tree.
+
+    The upstream tarballs should be next to the source tree.
+
+    :param source_dir: the source tree
+    :return: path to the .dsc file
+    """
+    parent_dir = os.path.dirname(os.path.abspath(source_dir))
+    before = set(os.listdir(parent_dir))
+    _run_dpkg_source(['-b', os.path.basename(source_dir)], parent_dir)
+    for name in sorted(set(os.listdir(parent_dir)) - before):
+        if name.endswith('.dsc'):
+            return os.path.join(parent_dir, name)
+    raise DpkgSourceFailed(
+        "dpkg-source -b %s" % source_dir, "no .dsc file was created")
+
+
+def extract_source_package(dsc_path, target, skip_patches=False):
+    """Unpack a source package.
+
+    :param dsc_path: path to the .dsc file
+    :param target: the directory to unpack to, which must not exist
+    :param skip_patches: whether to leave the quilt patches unapplied
+    """
+    args = ['-x']
+    if skip_patches:
+        args.append('--skip-patches')
+    target = os.path.abspath(target)
+    _run_dpkg_source(
+        args + [os.path.basename(dsc_path), target],
+        os.path.dirname(os.path.abspath(dsc_path)))
+
+
+def _list_tree(path, ignores):
+    entries = {}
+    for dirpath, dirnames, filenames in os.walk(path):
+        relpath = os.path.relpath(dirpath, path)
+        if relpath == '.':
+            relpath = ''
+        for name in list(dirnames):
+            child = os.path.join(relpath, name)
+            if child in ignores:
+                dirnames.remove(name)
+            elif os.path.islink(os.path.join(dirpath, name)):
+                entries[child] = 'symlink'
+            else:
+                entries[child] = 'directory'
+        for name in filenames:
+            child = os.path.join(relpath, name)
+            if child in ignores:
+                continue
+            if os.path.islink(os.path.join(dirpath, name)):
+                entries[child] = 'symlink'
+            else:
+                entries[child] = 'file'
+    return entries
+
+
+def _executable(path):
+    return bool(os.lstat(path).st_mode & stat.S_IXUSR)
+
+
+def _same_contents(path1, path2):
+    with open(path1, 'rb') as f1, open(path2, 'rb') as f2:
+        while True:
+            chunk1 = f1.read(65536)
+            if chunk1 != f2.read(65536):
+                return False
+            if not chunk1:
+                return True
+
+
+def compare_directories(branch_dir, source_dir, source_ignores=None,
+                        only_in_branch=False):
+    """Compare an export of a branch with an unpacked source package.
+
+    :param branch_dir: the export of the branch
+    :param source_dir: the unpacked source package
+    :param source_ignores: paths in the source package to ignore,
+        defaults to SOURCE_PACKAGE_IGNORES
+    :param only_in_branch: only compare the files that are in the branch,
+        e.g. for merge mode where the upstream source is not in the branch
+    :return: sorted list of (path, kind) tuples, where kind is one of the
+        DIFF_* constants
+    """
+    if source_ignores is None:
+        source_ignores = SOURCE_PACKAGE_IGNORES
+    branch_entries = _list_tree(branch_dir, [])
+    source_entries = _list_tree(source_dir, source_ignores)
+    differences = []
+    for path, kind in branch_entries.items():
+        source_kind = source_entries.get(path)
+        if source_kind is None:
+            differences.append((path, DIFF_ONLY_IN_BRANCH))
+            continue
+        if kind != source_kind:
+            differences.append((path, DIFF_KIND))
+            continue
+        branch_path = os.path.join(branch_dir, path)
+        source_path = os.path.join(source_dir, path)
+        if kind == 'symlink':
+            if os.readlink(branch_path) != os.readlink(source_path):
+                differences.append((path, DIFF_MODIFIED))
+        elif kind == 'file':
+            if not _same_contents(branch_path, source_path):
+                differences.append((path, DIFF_MODIFIED))
+            elif _executable(branch_path) != _executable(source_path):
+                differences.append((path, DIFF_MODE))
+    if not only_in_branch:
+        for path in source_entries:
+            if path not in branch_entries:
+                differences.append((path, DIFF_ONLY_IN_SOURCE))
+    return sorted(differences)
+
+
+def verify_source(tree, subpath, distiller, work_dir, package,
+                  upstream_version, merge=False, top_level=False):
+    """Check that the source package built from a tree matches the tree.
+
+    The source package is built with dpkg-source from the tree as exported
+    by the distiller, and then unpacked both with the quilt patches applied
+    and with them unapplied. The tree is compared with both.
+
+    :param tree: the tree to check
+    :param subpath: subpath in the tree where the package lives
+    :param distiller: the SourceDistiller to export the tree with
+    :param work_dir: empty directory to build the source package in
+    :param package: name of the source package
+    :param upstream_version: the upstream version of the package
+    :param merge: whether the tree only contains the packaging
+    :param top_level: whether the packaging is at the top level of the tree
+        rather than in debian/, in merge mode
+    :return: tuple with the state of the quilt patches in the tree, one of
+        PATCHES_APPLIED, PATCHES_UNAPPLIED or None if there are no patches,
+        and the differences, as returned by compare_directories
+    """
+    source_dir = os.path.join(
+        work_dir, "%s-%s" % (package, upstream_version))
+    distiller.distill(source_dir)
+    dsc_path = build_source_package(source_dir)
+    branch_dir = os.path.join(work_dir, 'branch')
+    export(tree, branch_dir, subdir=subpath)
+    if merge and top_level:
+        compare_root = 'debian'
+    else:
+        compare_root = ''
+    results = []
+    for state, skip_patches in [(PATCHES_APPLIED, False),
+                                (PATCHES_UNAPPLIED, True)]:
+        extracted_dir = os.path.join(work_dir, 'extracted-' + state)
+        extract_source_package(
+            dsc_path, extracted_dir, skip_patches=skip_patches)
+        if state == PATCHES_APPLIED and not _series(extracted_dir):
+            # Without patches there is only one way to unpack the source.
+            state = None
+        differences = compare_directories(
+            branch_dir, os.path.join(extracted_dir, compare_root),
+            only_in_branch=merge)
+        results.append((state, differences))
+        if state is None:
+            break
+    # Prefer the state that matches best; on a tie the patches are applied,
+    # which is what dgit expects.
+    return min(results, key=lambda result: len(result[1]))
@@ -0,0 +1,200 @@
+#    gbp.py -- Reading the git-buildpackage configuration
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+
+"""Reading the git-buildpackage configuration.
+
+Packages are often maintained by people using both gbp and bzr-builddeb,
+so the gbp.conf settings that have an equivalent are used as a layer of
+the builddeb configuration, with a lower precedence than the builddeb
+configuration files.
+
+gbp options can be set in the [DEFAULT] section or in the section of the
+gbp command, e.g. [buildpackage], which takes precedence.
+"""
+
+from __future__ import absolute_import
+
+import ast
+import configparser
+import re
+
+from ...trace import warning
+
+
+GBP_CONF = 'debian/gbp.conf'
+USER_GBP_CONF = '~/.gbp.conf'
+
+# builddeb options, and the gbp options and commands they are taken from.
+GBP_OPTIONS = {
+    'build-dir': ('export-dir', ['buildpackage']),
+    'builder': ('builder', ['buildpackage']),
+    'components': ('component', ['buildpackage', 'import-orig']),
+    'compression': ('compression', ['buildpackage', 'export-orig']),
+    'debian-branch': ('debian-branch', ['buildpackage']),
+    'debian-tag': ('debian-tag', ['buildpackage', 'tag']),
+    'export-upstream-revision': (
+        'upstream-vcs-tag', ['import-orig', 'buildpackage']),
+    'files-excluded': ('filter', ['import-orig']),
+    'sign-tags': ('sign-tags', ['buildpackage', 'tag']),
+    }
+
+# builddeb hooks, and the gbp hooks they are taken from.
+GBP_HOOKS = {
+    'pre-build': ('prebuild', ['buildpackage']),
+    'post-build': ('postbuild', ['buildpackage']),
+    'post-export': ('postexport', ['buildpackage']),
+    'post-merge-upstream': ('postimport', ['import-orig']),
+    'post-tag': ('posttag', ['buildpackage', 'tag']),
+    }
+
+# Environment variables set for hooks, and the variables gbp sets for them.
+GBP_ENVIRONMENT = {
+    'BUILDDEB_BUILD_DIR': 'GBP_BUILD_DIR',
+    'BUILDDEB_CHANGES_FILE': 'GBP_CHANGES_FILE',
+    'BUILDDEB_TAG': 'GBP_TAG',
+    'BUILDDEB_UPSTREAM_VERSION': 'GBP_UPSTREAM_VERSION',
+    'BUILDDEB_VERSION': 'GBP_DEBIAN_VERSION',
+    }
+
+# gbp options that are lists
+GBP_LIST_OPTIONS = ['component', 'filter']
+
+_VERSION_RE = re.compile(r'%\(version(%[^)]*)?\)s')
+
+
+def gbp_tag_to_revspec(tag_format):
+    """Convert a gbp tag format to an export-upstream-revision.
+
+    :param tag_format: the tag format, e.g. "v%(version)s"
+    :return: a revision spec, e.g. "tag:v$UPSTREAM_VERSION"
+    """
+    # Version mangling isn't supported, so the plain version is used.
+    return "tag:" + _VERSION_RE.sub('$UPSTREAM_VERSION', tag_format)
+
+
+def gbp_hook_environment(env, branch=None):
+    """Describe the package to a hook taken from gbp.conf.
+
+    gbp hooks expect the variables gbp sets, e.g. $GBP_CHANGES_FILE, rather
+    than the BUILDDEB_* ones.
+
+    :param env: dictionary with the BUILDDEB_* environment variables, as
+        created by hook_environment()
+    :param branch: the branch that is being built, if any
+    :return: dictionary with the GBP_* environment variables
+    """
+    gbp_env = {}
+    for name, gbp_name in GBP_ENVIRONMENT.items():
+        if name in env:
+            gbp_env[gbp_name] = env[name]
+    if branch is not None:
+        gbp_env['GBP_BRANCH'] = branch.name or branch.nick
+    return gbp_env
+
+
+def _listify(value):
+    # Lists are written as Python lists, e.g. ['.svn', '*.orig'].
+    if value.startswith('['):
+        try:
+            return [str(v) for v in ast.literal_eval(value)]
+        except (SyntaxError, ValueError):
+            pass
+    return [value]
+
+
+class GbpConfig(object):
+    """Config object that maps a gbp.conf onto the builddeb options.
+    """
+
+    def __init__(self, text, filename=GBP_CONF):
+        self.filename = filename
+        # Read [DEFAULT] as an ordinary section, so that it is possible to
+        # tell whether an option is set in the section of a command.
+        self._parser = configparser.ConfigParser(
+            strict=False, interpolation=None, default_section='')
+        try:
+            self._parser.read_string(text, filename)
+        except configparser.Error as e:
+            warning("There was an error parsing '%s': %s", filename, e)
+
+    def get_gbp_option(self, name, commands=(), default=None):
+        """Get the value of a gbp option.
+
+        :param name: name of the gbp option, e.g. "upstream-tag"
+        :param commands: the gbp commands whose sections to look in before
+            [DEFAULT], e.g. ["import-orig"]
+        :param default: value to return if the option is not set
+        """
+        for command in commands:
+            # Older versions of gbp used the name of the command.
+            for section in (command, 'git-' + command):
+                if self._parser.has_option(section, name):
+                    return self._parser.get(section, name)
+        if self._parser.has_option('DEFAULT', name):
+            return self._parser.get('DEFAULT', name)
+        return default
+
+    def get_gbp_bool(self, name, commands=(), default=None):
+        """Get the value of a boolean gbp option.
+
+        Invalid values are warned about and ignored.
+        """
+        value = self.get_gbp_option(name, commands)
+        if value is None:
+            return default
+        try:
+            return self._parser.BOOLEAN_STATES[value.lower()]
+        except KeyError:
+            warning("'%s' sets %s to %r, which is not a boolean, so it is "
+                    "ignored", self.filename, name, value)
+            return default
+
+    def get_value(self, section, option):
+        if section == "BUILDDEB":
+            mapping = GBP_OPTIONS
+        elif section == "HOOKS":
+            mapping = GBP_HOOKS
+        else:
+            raise KeyError(option)
+        try:
+            (name, commands) = mapping[option]
+        except KeyError:
+            raise KeyError(option)
+        value = self.get_gbp_option(name, commands)
+        if value is None:
+            raise KeyError(option)
+        if name in GBP_LIST_OPTIONS:
+            return _listify(value)
+        if name == 'upstream-vcs-tag':
+            return gbp_tag_to_revspec(value)
+        return value
+
+    def __getitem__(self, key):
+        return self.get_value("BUILDDEB", key)
+
+    def get_bool(self, section, option):
+        if section != "BUILDDEB" or option not in GBP_OPTIONS:
+            raise KeyError(option)
+        (name, commands) = GBP_OPTIONS[option]
+        value = self.get_gbp_bool(name, commands)
+        if value is None:
+            raise KeyError(option)
+        return value
@@ -20,25 +20,447 @@
 
 from __future__ import absolute_import
 
+from collections import deque
+import fnmatch
+import os
+import shutil
 import subprocess
 
+from ... import (
+    ui,
+    urlutils,
+    )
 from ...errors import BzrError
+from ...hooks import Hooks
 from ...trace import note
 
+from .gbp import GbpConfig, gbp_hook_environment
+
+
+# The hooks that are run, which can be set in the [HOOKS] section.
+KNOWN_HOOKS = [
+    'pre-export', 'post-export', 'pre-build', 'post-build',
+    'post-merge-upstream',
+    'post-import', 'pre-release', 'post-release', 'post-tag',
+    'merge-upstream',
+    ]
+
+# Old names of hooks, which are still run if the new name isn't set.
+HOOK_ALIASES = {'post-merge-upstream': 'merge-upstream'}
+
+# The number of lines of output of a failed hook to include in the error.
+OUTPUT_TAIL_LINES = 20
+
 
 class HookFailedError(BzrError):
-    _fmt = 'The "%(hook_name)s" hook failed.'
+    _fmt = ('The "%(hook_name)s" hook failed%(exit_status)s.'
+            '%(output_tail)s')
+
+    def __init__(self, hook_name, returncode=None, output=None):
+        if returncode is None:
+            exit_status = ''
+        elif returncode < 0:
+            exit_status = ' with signal %d' % -returncode
+        else:
+            exit_status = ' with exit code %d' % returncode
+        if output:
+            output_tail = '\nLast output of the hook:\n' + output.rstrip('\n')
+        else:
+            output_tail = ''
+        BzrError.__init__(
+            self, hook_name=hook_name, returncode=returncode, output=output,
+            exit_status=exit_status, output_tail=output_tail)
+
+
+class UntrustedHookError(BzrError):
+    _fmt = ('Not running the "%(hook_name)s" hook from %(source)s, as it is '
+            'not trusted. Add %(branch)s to trusted-hook-branches in your '
+            'builddeb.conf to run the hooks of this branch.')
+
+    def __init__(self, hook_name, source, branch_url=None):
+        BzrError.__init__(
+            self, hook_name=hook_name, source=source, branch_url=branch_url,
+            branch=(branch_url or 'the branch'))
+
+
+class HookSandboxUnavailable(BzrError):
+    _fmt = ('Unable to run the "%(hook_name)s" hook in the %(sandbox)s '
+            'sandbox, as %(program)s is not installed.')
+
+    def __init__(self, hook_name, sandbox, program):
+        BzrError.__init__(
+            self, hook_name=hook_name, sandbox=sandbox, program=program)
+
+
+def hook_environment(package=None, version=None, upstream_version=None,
+                     build_dir=None, result_dir=None, changes_file=None,
+                     tag=None):
+    """Describe the package to a hook in environment variables.
+
+    Only the variables for the arguments that are not None are set; paths
+    are made absolute since hooks don't all run in the same directory.
+
+    :param version: the Version of the package, which also provides the
+        upstream version if that is not given
+    :return: dictionary with BUILDDEB_* environment variables
+    """
+    env = {}
+    if package is not None:
+        env['BUILDDEB_PACKAGE'] = package
+    if version is not None:
+        env['BUILDDEB_VERSION'] = str(version)
+        if upstream_version is None:
+            upstream_version = getattr(version, 'upstream_version', None)
+    if upstream_version is not None:
+        env['BUILDDEB_UPSTREAM_VERSION'] = str(upstream_version)
+    for name, path in [('BUILDDEB_BUILD_DIR', build_dir),
+                       ('BUILDDEB_RESULT_DIR', result_dir),
+                       ('BUILDDEB_CHANGES_FILE', changes_file)]:
+        if path is not None:
+            env[name] = os.path.abspath(path)
+    if tag is not None:
+        env['BUILDDEB_TAG'] = tag
+    return env
+
 
-    def __init__(self, hook_name):
-        BzrError.__init__(self, hook_name=hook_name)
+def branch_is_trusted(branch_url, patterns):
+    """Check whether a branch is in the list of trusted branches.
 
+    :param branch_url: URL of the branch, or None if it is not known
+    :param patterns: list of URLs, which may contain shell wildcards; local
+        branches can also be given as paths
+    """
+    if branch_url is None:
+        return False
+    locations = [branch_url.rstrip('/')]
+    if branch_url.startswith('file://'):
+        locations.append(urlutils.local_path_from_url(branch_url).rstrip('/'))
+    for pattern in patterns:
+        pattern = os.path.expanduser(pattern.rstrip('/'))
+        for location in locations:
+            if fnmatch.fnmatchcase(location, pattern):
+                return True
+    return False
 
-def run_hook(tree, hook_name, config, wd="."):
-    hook = config.get_hook(hook_name)
-    if hook is None:
+
+def untrusted_hook_action(config):
+    """Determine what to do with hooks from files that are not trusted.
+
+    :return: 'allow' if the branch is in trusted-hook-branches, and otherwise
+        the untrusted-hooks setting: 'refuse' (the default), 'prompt' or
+        'allow'
+    """
+    from .util import config_list
+    if branch_is_trusted(
+            config.branch_url, config_list(config.trusted_hook_branches)):
+        return 'allow'
+    return config.untrusted_hooks or 'refuse'
+
+
+def sandbox_command(sandbox, hook, wd):
+    """Create the command to run a hook in a sandbox without network access.
+
+    :param sandbox: 'bwrap' to run the hook with bubblewrap, which only
+        allows writing to wd, or 'unshare' to only disable the network
+    :param hook: the shell command of the hook
+    :param wd: the directory the hook is run in
+    :return: the command as a list of arguments
+    """
+    if sandbox == 'bwrap':
+        return [
+            'bwrap', '--ro-bind', '/', '/', '--dev', '/dev',
+            '--proc', '/proc', '--tmpfs', '/tmp', '--bind', wd, wd,
+            '--chdir', wd, '--unshare-all', '--die-with-parent',
+            '--new-session', '/bin/sh', '-c', hook]
+    elif sandbox == 'unshare':
+        return [
+            'unshare', '--user', '--map-root-user', '--net', '--',
+            '/bin/sh', '-c', hook]
+    raise ValueError(sandbox)
+
+
+def run_hook(tree, hook_name, config, wd=".", env=None):
+    """Run the hook set for a hook point, if any.
+
+    Hooks from files that are not trusted are only run if the branch is
+    trusted or untrusted-hooks allows it, and then in the sandbox set in
+    hook-sandbox.
+
+    The output of the hook is shown as it runs; the last lines of it are
+    included in the HookFailedError raised if the hook fails.
+
+    Hooks taken from gbp.conf also get the GBP_* equivalents of the
+    variables in env.
+
+    :param env: dictionary with additional environment variables for the
+        hook, as created by hook_environment()
+    """
+    found = config.find_hook(hook_name)
+    if found is None and hook_name in HOOK_ALIASES:
+        found = config.find_hook(HOOK_ALIASES[hook_name])
+    if found is None:
         return
+    hook = found.value
+    cwd = tree.abspath(wd)
+    if found.trusted:
+        args = hook
+    else:
+        action = untrusted_hook_action(config)
+        if action == 'prompt':
+            if not ui.ui_factory.get_boolean(
+                    'Run the untrusted %s hook "%s" from %s' % (
+                        hook_name, hook, found.source)):
+                action = 'refuse'
+        if action == 'refuse':
+            raise UntrustedHookError(
+                hook_name, found.source, config.branch_url)
+        sandbox = config.hook_sandbox
+        if sandbox is None:
+            args = hook
+        else:
+            args = sandbox_command(sandbox, hook, cwd)
+            if shutil.which(args[0]) is None:
+                raise HookSandboxUnavailable(hook_name, sandbox, args[0])
     note("Running %s as %s hook" % (hook, hook_name))
-    proc = subprocess.Popen(hook, shell=True, cwd=tree.abspath(wd))
+    hook_env = dict(os.environ)
+    hook_env['BUILDDEB_HOOK'] = hook_name
+    if env:
+        hook_env.update(env)
+    if isinstance(config.get_layer(found.source), GbpConfig):
+        hook_env.update(gbp_hook_environment(
+            env or {}, getattr(tree, 'branch', None)))
+    proc = subprocess.Popen(
+        args, shell=isinstance(args, str), cwd=cwd, env=hook_env,
+        stdout=subprocess.PIPE, stderr=subprocess.STDOUT)
+    output = deque(maxlen=OUTPUT_TAIL_LINES)
+    for line in proc.stdout:
+        line = line.decode('utf-8', 'replace').rstrip('\n')
+        note("%s", line)
+        output.append(line)
+    proc.stdout.close()
     proc.wait()
     if proc.returncode != 0:
-        raise HookFailedError(hook_name)
+        raise HookFailedError(
+            hook_name, returncode=proc.returncode,
+            output=''.join(line + '\n' for line in output))
+
+
+class BuilddebHooks(Hooks):
+    """Hooks for the operations of builddeb.
+
+    These are the Python counterpart of the hooks in the [HOOKS] section.
+    Hooks are called with a params object that they may change to augment
+    the operation, or raise an exception from to abort it.
+    """
+
+    def __init__(self):
+        Hooks.__init__(self, "breezy.plugins.debian.hooks", "builddeb_hooks")
+        self.add_hook(
+            'pre_distill',
+            "Called before the source of a package is exported to the build "
+            "directory. pre_distill is called with a DistillHookParams "
+            "object.", (2, 8, 52))
+        self.add_hook(
+            'post_distill',
+            "Called after the source of a package has been exported to the "
+            "build directory. post_distill is called with a "
+            "DistillHookParams object, with tarballs set to the upstream "
+            "tarballs that were used.", (2, 8, 52))
+        self.add_hook(
+            'pre_build',
+            "Called before a package is built. pre_build is called with a "
+            "BuildHookParams object; its environment can be changed to "
+            "set environment variables for the build.", (2, 8, 52))
+        self.add_hook(
+            'post_build',
+            "Called after a package has been built successfully. post_build "
+            "is called with a BuildHookParams object.", (2, 8, 52))
+        self.add_hook(
+            'pre_merge_upstream',
+            "Called by merge-upstream before a new upstream version is "
+            "merged. pre_merge_upstream is called with a "
+            "MergeUpstreamHookParams object; its tarballs and "
+            "files_excluded can be changed.", (2, 8, 52))
+        self.add_hook(
+            'post_merge_upstream',
+            "Called by merge-upstream after a new upstream version has been "
+            "merged and added to the changelog. post_merge_upstream is "
+            "called with a MergeUpstreamHookParams object.", (2, 8, 52))
+        self.add_hook(
+            'pre_import_dsc',
+            "Called before a source package is imported into a "
+            "DistributionBranch. pre_import_dsc is called with an "
+            "ImportDscHookParams object.", (2, 8, 52))
+        self.add_hook(
+            'post_import_dsc',
+            "Called after a source package has been imported into a "
+            "DistributionBranch. post_import_dsc is called with an "
+            "ImportDscHookParams object.", (2, 8, 52))
+        self.add_hook(
+            'pre_release',
+            "Called by debrelease before the changelog is marked as "
+            "released. pre_release is called with a ReleaseHookParams "
+            "object.", (2, 8, 52))
+        self.add_hook(
+            'post_release',
+            "Called by debrelease after the package has been built and "
+            "uploaded. post_release is called with a ReleaseHookParams "
+            "object.", (2, 8, 52))
+        self.add_hook(
+            'pre_tag',
+            "Called before a version is tagged in a DistributionBranch. "
+            "pre_tag is called with a TagHookParams object; its tag_name "
+            "can be changed.", (2, 8, 52))
+        self.add_hook(
+            'post_tag',
+            "Called after a version has been tagged in a DistributionBranch. "
+            "post_tag is called with a TagHookParams object.", (2, 8, 52))
+
+
+builddeb_hooks = BuilddebHooks()
+
+
+def run_builddeb_hooks(hook_name, params):
+    """Call the Python hooks installed for a builddeb hook point.
+
+    :param hook_name: name of the hook point, e.g. 'pre_build'
+    :param params: the params object to pass to the hooks
+    """
+    for hook in builddeb_hooks[hook_name]:
+        hook(params)
+
+
+class _HookParams(object):
+
+    def __eq__(self, other):
+        return (type(self) == type(other) and
+                self.__dict__ == other.__dict__)
+
+    def __repr__(self):
+        return "<%s(%s)>" % (
+            self.__class__.__name__,
+            ", ".join("%s=%r" % item for item in sorted(
+                self.__dict__.items())))
+
+
+class DistillHookParams(_HookParams):
+    """Object holding parameters passed to the *_distill hooks.
+
+    :ivar distiller: the SourceDistiller
+    :ivar target_dir: the directory the source is exported to
+    :ivar upstream_provider: the UpstreamProvider of the distiller, or None
+        if it doesn't use upstream tarballs
+    :ivar tarballs: list of (filename, source) tuples for the upstream
+        tarballs that were used, for post_distill
+    """
+
+    def __init__(self, distiller, target_dir, upstream_provider=None):
+        self.distiller = distiller
+        self.target_dir = target_dir
+        self.upstream_provider = upstream_provider
+        self.tarballs = []
+
+
+class BuildHookParams(_HookParams):
+    """Object holding parameters passed to the *_build hooks.
+
+    :ivar builder: the Builder that builds the package
+    :ivar source_dir: the exported source that is built
+    :ivar result_dir: the directory the builder puts the result in
+    :ivar environment: dictionary with extra environment variables for
+        the build
+    """
+
+    def __init__(self, builder, source_dir, result_dir, environment):
+        self.builder = builder
+        self.source_dir = source_dir
+        self.result_dir = result_dir
+        self.environment = environment
+
+
+class MergeUpstreamHookParams(_HookParams):
+    """Object holding parameters passed to the *_merge_upstream hooks.
+
+    :ivar tree: the WorkingTree the upstream version is merged into
+    :ivar subpath: the path of the package in the tree
+    :ivar package: the name of the source package
+    :ivar version: the new upstream version
+    :ivar changelog: the Changelog of the package before the merge, or None
+    :ivar upstream_branch: the upstream Branch, or None
+    :ivar tarballs: list of paths of the upstream tarballs to import;
+        empty if only the changelog is updated
+    :ivar files_excluded: list of patterns of files to leave out of the
+        import
+    :ivar conflicts: the number of conflicts of the merge, for
+        post_merge_upstream
+    """
+
+    def __init__(self, tree, subpath, package, version, changelog,
+                 upstream_branch=None):
+        self.tree = tree
+        self.subpath = subpath
+        self.package = package
+        self.version = version
+        self.changelog = changelog
+        self.upstream_branch = upstream_branch
+        self.tarballs = []
+        self.files_excluded = []
+        self.conflicts = None
+
+
+class ImportDscHookParams(_HookParams):
+    """Object holding parameters passed to the *_import_dsc hooks.
+
+    :ivar distribution_branch: the DistributionBranch imported into
+    :ivar dsc_filename: path to the .dsc file
+    :ivar dsc: the contents of the .dsc file, a deb822.Dsc
+    :ivar version: the Version of the package
+    :ivar changelog: the Changelog of the package
+    :ivar tarballs: list of (path, component, md5) tuples for the upstream
+        tarballs of the package
+    :ivar revid: the revision the package was imported as, for
+        post_import_dsc
+    """
+
+    def __init__(self, distribution_branch, dsc_filename, dsc, version,
+                 changelog, tarballs=None):
+        self.distribution_branch = distribution_branch
+        self.dsc_filename = dsc_filename
+        self.dsc = dsc
+        self.version = version
+        self.changelog = changelog
+        self.tarballs = tarballs or []
+        self.revid = None
+
+
+class ReleaseHookParams(_HookParams):
+    """Object holding parameters passed to the *_release hooks.
+
+    :ivar tree: the tree of the package that is released
+    :ivar subpath: the path of the package in the tree
+    :ivar changelog: the Changelog of the package
+    :ivar changes_file: path to the changes file that was uploaded, for
+        post_release
+    """
+
+    def __init__(self, tree, subpath, changelog):
+        self.tree = tree
+        self.subpath = subpath
+        self.changelog = changelog
+        self.changes_file = None
+
+
+class TagHookParams(_HookParams):
+    """Object holding parameters passed to the *_tag hooks.
+
+    :ivar distribution_branch: the DistributionBranch that is tagged
+    :ivar version: the Version that is tagged
+    :ivar revid: the revision that is tagged
+    :ivar tag_name: the name of the tag
+    """
+
+    def __init__(self, distribution_branch, version, revid, tag_name):
+        self.distribution_branch = distribution_branch
+        self.version = version
+        self.revid = revid
+        self.tag_name = tag_name
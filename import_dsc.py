@@ -30,15 +30,18 @@ from __future__ import absolute_import
 import calendar
 from contextlib import contextmanager, ExitStack
 import os
+import shutil
 import stat
 import tempfile
 
 from debian import deb822
 from debian.changelog import Version, Changelog, VersionError
+from debmutate.vcs import gbp_expand_tag_name
 from debmutate.versions import mangle_version_for_git
 
 from ... import (
     controldir,
+    gpg,
     osutils,
     )
 from ...export import (
@@ -52,6 +55,7 @@ from ...errors import (
     NoRoundtrippingSupport,
     NoWorkingTree,
     UnrelatedBranches,
+    UnsupportedOperation,
     )
 from ...revision import NULL_REVISION
 from ...trace import warning, mutter
@@ -60,10 +64,12 @@ from ...transport import (
     )
 
 from .bzrtools_import import import_dir
-from .errors import (
-    MultipleUpstreamTarballsNotSupported,
-    )
 from .extract import extract
+from .hooks import (
+    ImportDscHookParams,
+    TagHookParams,
+    run_builddeb_hooks,
+    )
 from .util import (
     extract_orig_tarballs,
     get_commit_info_from_changelog,
@@ -94,6 +100,15 @@ class UpstreamAlreadyImported(BzrError):
         BzrError.__init__(self, version=str(version))
 
 
+class UpstreamComponentChanged(BzrError):
+    _fmt = ('The directory "%(path)s" for upstream component '
+            '"%(component)s" has changes that did not come from upstream; '
+            'not replacing it.')
+
+    def __init__(self, component, path):
+        BzrError.__init__(self, component=component, path=path)
+
+
 class DscCache(object):
 
     def __init__(self, transport=None):
@@ -235,7 +250,8 @@ class DistributionBranch(object):
     """
 
     def __init__(self, branch, pristine_upstream_branch, tree=None,
-                 pristine_upstream_tree=None):
+                 pristine_upstream_tree=None, tag_format=None,
+                 sign_tags=False):
         """Create a distribution branch.
 
         You can only import packages on to the DistributionBranch
@@ -247,9 +263,15 @@ class DistributionBranch(object):
         :param tree: an optional tree for the branch.
         :param pristine_upstream_tree: an optional tree for the
             pristine_upstream_branch.
+        :param tag_format: optional format of the tags for versions, as
+            used by gbp, e.g. "debian/%(version)s"
+        :param sign_tags: whether to sign the revisions that are tagged for
+            versions, with the GPG settings of the branch.
         """
         self.branch = branch
         self.tree = tree
+        self.tag_format = tag_format
+        self.sign_tags = sign_tags
         self.pristine_upstream_branch = pristine_upstream_branch
         self.pristine_upstream_tree = pristine_upstream_tree
         if pristine_upstream_branch is not None:
@@ -306,6 +328,9 @@ class DistributionBranch(object):
         """
         if vendor is not None:
             return '%s/%s' % (vendor, version)
+        elif self.tag_format is not None:
+            return gbp_expand_tag_name(
+                self.tag_format, mangle_version_for_git(str(version)))
         else:
             return str(version)
 
@@ -323,6 +348,10 @@ class DistributionBranch(object):
         :return: True if this branch contains the specified version of the
             package. False otherwise.
         """
+        if (self.tag_format is not None and
+                branch_has_debian_version(
+                    self.branch, self.tag_name(version), md5=md5)):
+            return True
         version = mangle_version(self.branch, str(version))
         if branch_has_debian_version(self.branch, str(version), md5=md5):
             return True
@@ -413,6 +442,10 @@ class DistributionBranch(object):
             revision id of. The Version must be present in the branch.
         :return: the revision id corresponding to that version
         """
+        if self.tag_format is not None:
+            tag_name = self.tag_name(version)
+            if branch_has_debian_version(self.branch, tag_name):
+                return self.branch.tags.lookup_tag(tag_name)
         if branch_has_debian_version(self.branch, str(version)):
             return self.branch.tags.lookup_tag(str(version))
         version = mangle_version(self.branch, str(version))
@@ -441,8 +474,35 @@ class DistributionBranch(object):
         tag_name = self.tag_name(version, vendor)
         if revid is None:
             revid = self.branch.last_revision()
-        self.branch.tags.set_tag(tag_name, revid)
-        return tag_name
+        params = TagHookParams(self, version, revid, tag_name)
+        run_builddeb_hooks('pre_tag', params)
+        self.branch.tags.set_tag(params.tag_name, revid)
+        if self.sign_tags:
+            self._sign_revision(revid)
+        run_builddeb_hooks('post_tag', params)
+        return params.tag_name
+
+    def _sign_revision(self, revid):
+        # Tags can't carry a signature themselves, so the revision that is
+        # tagged is signed instead, as "bzr sign-my-commits" does.
+        repository = self.branch.repository
+        if repository.has_signature_for_revision_id(revid):
+            return
+        strategy = gpg.GPGStrategy(self.branch.get_config_stack())
+        with repository.lock_write():
+            repository.start_write_group()
+            try:
+                repository.sign_revision(revid, strategy)
+            except UnsupportedOperation:
+                repository.abort_write_group()
+                warning("The repository of %s does not support signatures, "
+                        "so the tagged revision is not signed.",
+                        self.branch.base)
+            except BaseException:
+                repository.abort_write_group()
+                raise
+            else:
+                repository.commit_write_group()
 
     def is_version_native(self, version):
         """Determines whether the given version is native.
@@ -639,10 +699,15 @@ class DistributionBranch(object):
                    component, version, pull_revision)
             assert self.pristine_upstream_tree is not None, \
                 "Can't pull upstream with no tree"
-            self.pristine_upstream_branch.pull(
-                pull_branch.pristine_upstream_branch,
-                stop_revision=pull_revision)
-            self.pristine_upstream_source.tag_version(version, pull_revision)
+            if component is None:
+                self.pristine_upstream_branch.pull(
+                    pull_branch.pristine_upstream_branch,
+                    stop_revision=pull_revision)
+            else:
+                self.pristine_upstream_branch.fetch(
+                    pull_branch.pristine_upstream_branch, pull_revision)
+            self.pristine_upstream_source.tag_version(
+                version, pull_revision, component=component)
             self.branch.fetch(self.pristine_upstream_branch, pull_revision)
             self.pristine_upstream_branch.tags.merge_to(self.branch.tags)
         checkout_upstream_version(
@@ -796,16 +861,18 @@ class DistributionBranch(object):
         for (tarball, component, md5) in upstream_tarballs:
             parents = upstream_parents.get(component, [])
             if upstream_revisions is not None:
-                revid = upstream_revisions[component]
+                revid = upstream_revisions.get(component)
             else:
                 revid = None
             upstream_trees = [
                 o.pristine_upstream_branch.basis_tree()
                 for o in other_branches]
             target_tree = None
-            if upstream_branch is not None:
+            # The tip of the upstream branch only corresponds to the main
+            # tarball.
+            if upstream_branch is not None and (
+                    revid is not None or component is None):
                 if revid is None:
-                    # FIXME: This is wrong for component tarballs
                     revid = upstream_branch.last_revision()
                 try:
                     self.pristine_upstream_branch.fetch(
@@ -860,6 +927,13 @@ class DistributionBranch(object):
             ret.append((component, tag, revid, pristine_tar_imported))
             self.branch.fetch(self.pristine_upstream_branch)
             self.branch.tags.set_tag(tag, revid)
+        # Leave the upstream branch at the revision for the main tarball,
+        # which is what is merged into the packaging branch.
+        for (component, tag, revid, pristine_tar_imported) in ret:
+            if component is None:
+                self.pristine_upstream_tree.pull(
+                    self.pristine_upstream_tree.branch,
+                    overwrite=True, stop_revision=revid)
         return ret
 
     def import_upstream_tarballs(self, tarballs, package, version, parents,
@@ -1025,10 +1099,12 @@ class DistributionBranch(object):
         The upstream parents will be the last upstream version,
         except for some cases when the last version was native.
 
-        :return: the list of revision ids to use as parents when
+        :return: dictionary mapping component names (None for the main
+            tarball) to the list of revision ids to use as parents when
             importing the specified upstream version.
         """
         parents = []
+        component_parents = {}
         first_parent = self.pristine_upstream_branch.last_revision()
         if first_parent != NULL_REVISION:
             parents = [first_parent]
@@ -1055,19 +1131,21 @@ class DistributionBranch(object):
             if not pull_branch.is_version_native(pull_version):
                 pull_revids = pull_branch.pristine_upstream_source.version_as_revisions(
                     package, pull_version.upstream_version)
-                if list(pull_revids.keys()) != [None]:
-                    raise MultipleUpstreamTarballsNotSupported()
-                pull_revid = pull_revids[None]
                 mutter("Initialising upstream from %s, version %s",
                        str(pull_branch), str(pull_version))
-                parents.append(pull_revid)
-                self.pristine_upstream_branch.fetch(
-                        pull_branch.pristine_upstream_branch,
-                        pull_revid)
+                for component, pull_revid in pull_revids.items():
+                    if component is None:
+                        parents.append(pull_revid)
+                    else:
+                        component_parents[component] = [pull_revid]
+                    self.pristine_upstream_branch.fetch(
+                            pull_branch.pristine_upstream_branch,
+                            pull_revid)
                 pull_branch.pristine_upstream_branch.tags.merge_to(
                         self.pristine_upstream_branch.tags)
         # FIXME: What about other versions ?
-        return {None: parents}
+        component_parents[None] = parents
+        return component_parents
 
     def _fetch_from_branch(self, branch, revid):
         branch.branch.tags.merge_to(self.branch.tags)
@@ -1211,8 +1289,12 @@ class DistributionBranch(object):
             # as some methods assume that, and it's not clear what
             # should happen if it isn't.
 
+            params = ImportDscHookParams(
+                self, dsc_filename, dsc, version, cl,
+                extractor.upstream_tarballs)
+            run_builddeb_hooks('pre_import_dsc', params)
             if extractor.extracted_upstream is not None:
-                return self._import_normal_package(
+                ret = self._import_normal_package(
                     dsc['Source'], version, versions,
                     extractor.extracted_debianised,
                     extractor.unextracted_debian_md5,
@@ -1221,21 +1303,27 @@ class DistributionBranch(object):
                     file_ids_from=file_ids_from, pull_debian=pull_debian,
                     force_pristine_tar=force_pristine_tar)
             else:
-                return self._import_native_package(
+                ret = self._import_native_package(
                     dsc['Source'], version, versions,
                     extractor.extracted_debianised,
                     extractor.unextracted_debian_md5, timestamp=timestamp,
                     file_ids_from=file_ids_from, pull_debian=pull_debian)
+            params.revid = self.branch.last_revision()
+            run_builddeb_hooks('post_import_dsc', params)
+            return ret
 
     def extract_upstream_tree(self, upstream_tips, basedir):
-        """Extract upstream_tip to a tempdir as a working tree."""
+        """Extract upstream_tip to a tempdir as a working tree.
+
+        The working tree is for the main tarball; the revisions for any
+        other components are fetched into its repository, so that they
+        can be used as parents.
+        """
         # TODO: should stack rather than trying to use the repository,
         # as that will be more efficient.
         to_location = os.path.join(basedir, "upstream")
         # Use upstream_branch if it has been set, otherwise self.branch.
         source_branch = self.pristine_upstream_branch or self.branch
-        assert list(upstream_tips.keys()) == [None], \
-            "Upstream tips: %r" % list(upstream_tips.keys())
         # TODO(jelmer): Use colocated branches rather than creating a copy.
         dir_to = source_branch.controldir.sprout(
             to_location, revision_id=upstream_tips[None],
@@ -1246,6 +1334,9 @@ class DistributionBranch(object):
             # Handle shared treeless repo's.
             self.pristine_upstream_tree = dir_to.create_workingtree()
         self.pristine_upstream_branch = self.pristine_upstream_tree.branch
+        for component, revid in upstream_tips.items():
+            if component is not None:
+                self.pristine_upstream_branch.fetch(source_branch, revid)
         self.pristine_upstream_branch.get_config_stack().set(
             'branch.fetch_tags', True)
 
@@ -1292,6 +1383,7 @@ class DistributionBranch(object):
                 previous_version,
                 self.pristine_upstream_source.tag_name(previous_version))
         self.extract_upstream_tree(upstream_tips, tempdir)
+        return upstream_tips
 
     def has_merged_upstream_revisions(
             self, this_revision, upstream_repository, upstream_revisions):
@@ -1304,16 +1396,19 @@ class DistributionBranch(object):
                        previous_version, upstream_branch=None,
                        upstream_revisions=None, merge_type=None, force=False,
                        force_pristine_tar=False, committer=None,
-                       files_excluded=None):
+                       files_excluded=None, subpath=''):
         with ExitStack() as es:
             tempdir = es.enter_context(
                 tempfile.TemporaryDirectory(
                     dir=os.path.join(self.tree.basedir, '..')))
+            previous_tips = {}
             if previous_version is not None:
-                self._export_previous_upstream_tree(
+                previous_tips = self._export_previous_upstream_tree(
                     package, previous_version, tempdir)
             else:
                 self.create_empty_upstream_tree(tempdir)
+            self._check_upstream_components(
+                tarball_filenames, previous_tips, subpath)
             if self.pristine_upstream_source.has_version(package, version):
                 raise UpstreamAlreadyImported(version)
             if upstream_branch is not None:
@@ -1333,6 +1428,9 @@ class DistributionBranch(object):
                 parents = {None: []}
                 if self.pristine_upstream_branch.last_revision() != NULL_REVISION:
                     parents = {None: [self.pristine_upstream_branch.last_revision()]}
+                for component, revid in previous_tips.items():
+                    if component is not None:
+                        parents[component] = [revid]
                 imported_revids = self.import_upstream(
                     tarball_dir, package, version, parents,
                     upstream_tarballs=upstream_tarballs,
@@ -1365,9 +1463,95 @@ class DistributionBranch(object):
                 # from upstream tarball.
                 conflicts = []
                 self.tree.pull(self.pristine_upstream_branch)
+            self._update_upstream_components(imported_revids, subpath)
             self.pristine_upstream_branch.tags.merge_to(self.branch.tags)
             return conflicts, imported_revids
 
+    def _check_upstream_components(self, tarball_filenames, previous_tips,
+                                   subpath):
+        """Check that the component directories can be replaced.
+
+        A component directory may only be replaced if its versioned contents
+        are those of the previous import of that component.
+
+        :param tarball_filenames: list of (filename, component) tuples
+        :param previous_tips: dictionary mapping components to the revisions
+            of the previous upstream import
+        :param subpath: path of the packaging in the tree
+        :raises UpstreamComponentChanged: if a component directory has other
+            changes
+        """
+        for (filename, component) in tarball_filenames:
+            if component is None:
+                continue
+            path = osutils.pathjoin(subpath, component)
+            with self.tree.lock_read():
+                current = _versioned_contents(self.tree, path)
+            if component in previous_tips:
+                previous_tree = (
+                    self.pristine_upstream_branch.repository.revision_tree(
+                        previous_tips[component]))
+                with previous_tree.lock_read():
+                    previous = _versioned_contents(previous_tree, '')
+            else:
+                previous = {}
+            if current != previous:
+                raise UpstreamComponentChanged(component, path)
+
+    def _update_upstream_components(self, imported_revids, subpath=''):
+        """Put the imported upstream components in the packaging tree.
+
+        As with dpkg-source, the contents of each additional tarball replace
+        whatever is in the directory named after its component. The revisions
+        for the components are added as parents of the tree.
+
+        :param imported_revids: list of (component, tag, revid,
+            pristine_tar_imported) tuples, as returned by import_upstream
+        :param subpath: path of the packaging in the tree
+        """
+        for (component, tag, revid, pristine_tar_imported) in imported_revids:
+            if component is None:
+                continue
+            relpath = osutils.pathjoin(subpath, component)
+            path = self.tree.abspath(relpath)
+            if os.path.exists(path):
+                shutil.rmtree(path)
+            export(self.branch.repository.revision_tree(revid), path,
+                   format='dir')
+            missing = [
+                change.path[1] for change in self.tree.iter_changes(
+                    self.tree.basis_tree(), specific_files=[relpath])
+                if change.kind[1] is None and change.path[1] is not None]
+            if missing:
+                self.tree.remove(missing)
+            self.tree.smart_add([path])
+            self.tree.add_parent_tree_id(revid)
+
+
+def _versioned_contents(tree, path):
+    """Return the kinds and contents of the versioned files below a path.
+
+    :param tree: tree to look in
+    :param path: directory in the tree
+    :return: dictionary mapping paths relative to path to (kind, sha1 or
+        symlink target) tuples
+    """
+    if path and not tree.is_versioned(path):
+        return {}
+    contents = {}
+    for (relpath, versioned, kind, entry) in tree.list_files(
+            from_dir=path, recursive=True):
+        if versioned != 'V':
+            continue
+        fullpath = osutils.pathjoin(path, relpath)
+        if kind == 'file':
+            contents[relpath] = (kind, tree.get_file_sha1(fullpath))
+        elif kind == 'symlink':
+            contents[relpath] = (kind, tree.get_symlink_target(fullpath))
+        else:
+            contents[relpath] = (kind, None)
+    return contents
+
 
 @contextmanager
 def _extract_tarballs_to_tempdir(tarballs):
@@ -20,7 +20,7 @@ from __future__ import absolute_import
 
 brz_plugin_name = 'debian'
 
-brz_plugin_version = (2, 8, 51, 'final', 0)
+brz_plugin_version = (2, 8, 52, 'dev', 0)
 
 brz_commands = [
     "builddeb",
@@ -29,7 +29,6 @@ from debian.changelog import Version
 
 from .errors import (
     BzrError,
-    MultipleUpstreamTarballsNotSupported,
     )
 from .import_dsc import DistributionBranch
 from .util import find_changelog
@@ -56,7 +55,8 @@ def _upstream_version_data(branch, revid):
 
     :param branch: The merge branch.
     :param revid: The revision in the branch to consider
-    :param tree: Optional tree for the revision
+    :return: Tuple with the upstream version and a dictionary mapping
+        component names (None for the main tarball) to revision ids
     """
     db = DistributionBranch(branch, branch)
     tree = branch.repository.revision_tree(revid)
@@ -64,10 +64,7 @@ def _upstream_version_data(branch, revid):
     uver = changelog.version.upstream_version
     upstream_revids = db.pristine_upstream_source.version_as_revisions(
         None, uver)
-    if list(upstream_revids.keys()) != [None]:
-        raise MultipleUpstreamTarballsNotSupported()
-    upstream_revid = upstream_revids[None]
-    return (Version(uver), upstream_revid)
+    return (Version(uver), upstream_revids)
 
 
 def fix_ancestry_as_needed(tree, source, source_revid=None):
@@ -110,6 +107,10 @@ def fix_ancestry_as_needed(tree, source, source_revid=None):
 
     At this point we can merge J->L to merge the Debian and Ubuntu changes.
 
+    If the package has additional orig tarballs, the revisions for those
+    components in the merge source are added as parents of K as well, for
+    the components that have diverged.
+
     :param tree: The `WorkingTree` of the merge target branch.
     :param source: The merge source (packaging) branch.
     """
@@ -123,13 +124,28 @@ def fix_ancestry_as_needed(tree, source, source_revid=None):
         with tree.lock_write():
             # "Unpack" the upstream versions and revision ids for the merge
             # source and target branch respectively.
-            (us_ver, us_revid) = _upstream_version_data(source, source_revid)
-            (ut_ver, ut_revid) = _upstream_version_data(
+            (us_ver, us_revids) = _upstream_version_data(
+                source, source_revid)
+            (ut_ver, ut_revids) = _upstream_version_data(
                     target, target.last_revision())
+            us_revid = us_revids[None]
+            ut_revid = ut_revids[None]
 
             # Did the upstream branches of the merge source/target diverge?
             graph = source.repository.get_graph(target.repository)
-            upstreams_diverged = (len(graph.heads([us_revid, ut_revid])) > 1)
+            diverged_components = [
+                component for component in sorted(
+                    us_revids, key=lambda c: (c is not None, c))
+                if component in ut_revids and
+                len(graph.heads([us_revids[component],
+                                 ut_revids[component]])) > 1]
+            # Components that only the merge source has are new upstream,
+            # so they need linking in too.
+            diverged_components.extend(
+                component for component in sorted(
+                    c for c in us_revids if c is not None)
+                if component not in ut_revids)
+            upstreams_diverged = bool(diverged_components)
 
             # No, we're done!
             if not upstreams_diverged:
@@ -156,7 +172,11 @@ def fix_ancestry_as_needed(tree, source, source_revid=None):
                             None, source.repository.revision_tree(us_revid))
                         t_upstream_reverted = True
 
-                    tmp_target_utree.set_parent_ids((ut_revid, us_revid))
+                    parent_ids = [ut_revid]
+                    for component in diverged_components:
+                        if us_revids[component] not in parent_ids:
+                            parent_ids.append(us_revids[component])
+                    tmp_target_utree.set_parent_ids(parent_ids)
                     new_revid = tmp_target_utree.commit(
                         'Prepared upstream tree for merging into target '
                         'branch.')
@@ -165,7 +185,8 @@ def fix_ancestry_as_needed(tree, source, source_revid=None):
                     # hence the call to refresh the data in the /target/ repo.
                     tree.branch.repository.refresh_data()
 
-                    tree.branch.fetch(source, us_revid)
+                    for component in diverged_components:
+                        tree.branch.fetch(source, us_revids[component])
                     tree.branch.fetch(tmp_target_utree.branch, new_revid)
 
                     # Merge shared upstream parent into the target merge
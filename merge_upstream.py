@@ -174,7 +174,8 @@ def do_merge(
         upstream_revisions=upstream_revisions,
         merge_type=merge_type, force=force,
         force_pristine_tar=force_pristine_tar,
-        committer=committer, files_excluded=files_excluded)
+        committer=committer, files_excluded=files_excluded,
+        subpath=subpath)
 
 
 def fetch_tarball(package, version, orig_dir, locations, v3):
@@ -0,0 +1,441 @@
+#    patch_queue.py -- Maintain debian/patches as commits on a branch
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Maintaining debian/patches as a series of commits on a branch.
+
+A patch-queue branch is a branch of the packaging branch with one commit
+for each of the patches in debian/patches/series, in order. The patches
+can be developed as ordinary commits on that branch, after which
+debian/patches is regenerated from it.
+"""
+
+from __future__ import absolute_import
+
+import calendar
+import os
+import re
+import subprocess
+import time
+from email.parser import Parser
+from io import BytesIO, StringIO
+
+from ... import diff
+from ...errors import BzrError
+from ...osutils import is_inside
+from ...trace import mutter, note
+
+from .dep3 import write_dep3_patch_header
+from .util import subprocess_setup
+
+
+PATCHES_DIR = 'debian/patches'
+
+# Revision properties used to keep the DEP-3 fields that can't be
+# represented in the revision itself.
+PATCH_NAME_REVPROP = 'deb-patch-name'
+PATCH_REVPROPS = {
+    'Origin': 'deb-patch-origin',
+    'Forwarded': 'deb-patch-forwarded',
+    'Applied-Upstream': 'deb-patch-applied-upstream',
+    }
+
+
+class PatchApplyFailed(BzrError):
+
+    _fmt = "Unable to apply patch %(patch)s: %(error)s"
+
+    def __init__(self, patch, error):
+        BzrError.__init__(self, patch=patch, error=error)
+
+
+class PatchesAlreadyApplied(BzrError):
+
+    _fmt = ("The patches in %(path)s are applied; unapply them "
+            "with 'quilt pop -a' first.")
+
+    def __init__(self, path):
+        BzrError.__init__(self, path=path)
+
+
+def parse_series(text):
+    """Parse a quilt series file.
+
+    :param text: contents of the series file, as a string
+    :return: list of (name, options) tuples, where options is the list of
+        options for the patch, e.g. ["-p0"]
+    """
+    ret = []
+    for line in text.splitlines():
+        line = line.split('#', 1)[0].strip()
+        if not line:
+            continue
+        fields = line.split()
+        ret.append((fields[0], fields[1:]))
+    return ret
+
+
+def _strip_level(options):
+    for option in options:
+        if option.startswith('-p'):
+            return int(option[2:])
+    return 1
+
+
+_DIFF_START_RE = re.compile(r'^(--- |=== |diff |Index: |---$)')
+
+
+def split_patch(text):
+    """Split a patch into its header and the diff.
+
+    :param text: contents of the patch, as a string
+    :return: tuple with the header and the diff
+    """
+    lines = text.splitlines(True)
+    for i, line in enumerate(lines):
+        if _DIFF_START_RE.match(line):
+            return ''.join(lines[:i]), ''.join(lines[i:])
+    return text, ''
+
+
+def _unfold(value):
+    lines = []
+    for line in value.splitlines():
+        if line.startswith(' '):
+            line = line[1:]
+        if line.strip() == '.':
+            line = ''
+        lines.append(line.rstrip())
+    return '\n'.join(lines)
+
+
+class PatchHeader(object):
+    """The information in the DEP-3 header of a patch.
+
+    :ivar message: commit message for the patch: the description, or the
+        subject followed by the free-form text of a git-style patch
+    :ivar authors: list of the authors of the patch
+    :ivar bugs: list of bug URLs that the patch fixes
+    :ivar last_update: timestamp of the Last-Update field, or None
+    :ivar fields: dictionary with the Origin, Forwarded and
+        Applied-Upstream fields that are set
+    """
+
+    def __init__(self, message=None, authors=None, bugs=None,
+                 last_update=None, fields=None):
+        self.message = message
+        self.authors = authors or []
+        self.bugs = bugs or []
+        self.last_update = last_update
+        self.fields = fields or {}
+
+
+def parse_patch_header(text):
+    """Parse the DEP-3 header of a patch.
+
+    Both DEP-3 headers and the mail headers written by git format-patch
+    are understood.
+
+    :param text: the header of the patch, as returned by split_patch
+    :return: a PatchHeader
+    """
+    if text.startswith('From ') and '\n' in text:
+        # mbox separator written by git format-patch
+        text = text.split('\n', 1)[1]
+    message = Parser().parsestr(text)
+    description = message.get('Description')
+    if description is None:
+        description = message.get('Subject')
+        if description is not None:
+            description = re.sub(r'^\[PATCH[^\]]*\]\s*', '', description)
+    if description is not None:
+        description = _unfold(description).strip('\n')
+    body = message.get_payload()
+    if isinstance(body, str) and body.strip():
+        if description:
+            description += '\n\n' + body.strip('\n')
+        else:
+            description = body.strip('\n')
+    authors = message.get_all('Author', []) + message.get_all('From', [])
+    bugs = []
+    for name, value in message.items():
+        if name.lower().startswith('bug'):
+            bugs.append(value.strip())
+    last_update = None
+    if message.get('Last-Update'):
+        try:
+            last_update = calendar.timegm(
+                time.strptime(message['Last-Update'].strip(), '%Y-%m-%d'))
+        except ValueError:
+            mutter('Unable to parse Last-Update field %r',
+                   message['Last-Update'])
+    fields = {
+        name: _unfold(message[name])
+        for name in PATCH_REVPROPS if message.get(name) is not None}
+    return PatchHeader(
+        message=description or None, authors=[a.strip() for a in authors],
+        bugs=bugs, last_update=last_update, fields=fields)
+
+
+def _read_series(patches_dir):
+    try:
+        with open(os.path.join(patches_dir, 'series'), 'r') as f:
+            return parse_series(f.read())
+    except FileNotFoundError:
+        return []
+
+
+def _remove_missing(tree, subpath):
+    missing = [
+        change.path[0] for change in tree.iter_changes(tree.basis_tree())
+        if change.kind[1] is None and change.path[0] is not None and
+        is_inside(subpath, change.path[0])]
+    if missing:
+        tree.remove(missing)
+
+
+def import_patch_queue(tree, subpath=''):
+    """Turn the patches in debian/patches into commits.
+
+    Every patch in debian/patches/series is applied to the tree and
+    committed separately, with the details from its DEP-3 header.
+
+    :param tree: working tree of the patch-queue branch, with the patches
+        unapplied
+    :param subpath: subpath in the tree where the package lives
+    :return: list of the revision ids that were created
+    """
+    source_dir = tree.abspath(subpath)
+    if os.path.exists(os.path.join(source_dir, '.pc', 'applied-patches')):
+        raise PatchesAlreadyApplied(source_dir)
+    patches_dir = os.path.join(source_dir, PATCHES_DIR)
+    supports_revprops = True
+    revids = []
+    for name, options in _read_series(patches_dir):
+        path = os.path.join(patches_dir, name)
+        with open(path, 'rb') as f:
+            header = parse_patch_header(
+                split_patch(f.read().decode('utf-8', 'replace'))[0])
+        proc = subprocess.Popen(
+            ['patch', '-p%d' % _strip_level(options), '--forward',
+             '--batch', '--no-backup-if-mismatch', '--quiet', '-i', path],
+            cwd=source_dir, stdout=subprocess.PIPE, stderr=subprocess.STDOUT,
+            preexec_fn=subprocess_setup)
+        output = proc.communicate()[0]
+        if proc.returncode != 0:
+            raise PatchApplyFailed(name, output.decode('utf-8', 'replace'))
+        tree.smart_add([source_dir])
+        _remove_missing(tree, subpath)
+        revprops = {}
+        if header.bugs:
+            revprops['bugs'] = "\n".join(
+                "%s fixed" % bug for bug in header.bugs)
+        revprops[PATCH_NAME_REVPROP] = name
+        for field, value in header.fields.items():
+            revprops[PATCH_REVPROPS[field]] = value
+        message = header.message
+        if message is None:
+            message = "Apply %s." % name
+        note("Applied %s", name)
+
+        def commit(revprops):
+            return tree.commit(
+                message, authors=(header.authors or None), revprops=revprops,
+                timestamp=header.last_update,
+                timezone=(0 if header.last_update is not None else None),
+                allow_pointless=True)
+        if supports_revprops:
+            try:
+                revids.append(commit(revprops))
+                continue
+            except NotImplementedError:
+                # Repositories such as git ones can't store custom revision
+                # properties, so only the message and authors are kept.
+                mutter('Unable to store revision properties in %r',
+                       tree.branch.repository)
+                supports_revprops = False
+        revids.append(commit({}))
+    return revids
+
+
+def patch_name_from_message(message):
+    """Derive a patch file name from a commit message.
+
+    :param message: the commit message
+    :return: e.g. "fix-the-build.patch" for "Fix the build."
+    """
+    summary = message.strip().split('\n', 1)[0].lower()
+    name = re.sub(r'[^a-z0-9]+', '-', summary).strip('-')[:60].rstrip('-')
+    return (name or 'patch') + '.patch'
+
+
+def _strip_subpath_labels(diff_text, subpath):
+    if not subpath:
+        return diff_text
+    prefix = (subpath.rstrip('/') + '/').encode('utf-8')
+    return re.sub(
+        br'^(---|\+\+\+) ([ab])/' + re.escape(prefix), br'\1 \2/',
+        diff_text, flags=re.MULTILINE)
+
+
+def queue_patches(branch, base_revid, revision_id=None, subpath=''):
+    """Generate patches from the commits on a patch-queue branch.
+
+    Every mainline revision after base_revid becomes a patch with a DEP-3
+    header. Changes to the debian directory are left out, and revisions
+    that only change the debian directory don't get a patch.
+
+    :param branch: the patch-queue branch
+    :param base_revid: the revision of the packaging branch the queue is
+        based on
+    :param revision_id: the last revision to include, defaults to the tip
+        of the branch
+    :param subpath: subpath in the tree where the package lives
+    :return: list of (name, contents) tuples, in series order
+    """
+    if revision_id is None:
+        revision_id = branch.last_revision()
+    repository = branch.repository
+    graph = repository.get_graph()
+    revids = list(graph.iter_lefthand_ancestry(revision_id, [base_revid]))
+    revids.reverse()
+    debian_dir = os.path.join(subpath, 'debian')
+    patches = []
+    names = set()
+    parent_revid = base_revid
+    for rev in repository.get_revisions(revids):
+        old_tree = repository.revision_tree(parent_revid)
+        new_tree = repository.revision_tree(rev.revision_id)
+        parent_revid = rev.revision_id
+        paths = []
+        for change in new_tree.iter_changes(old_tree):
+            path = change.path[1] or change.path[0]
+            if (is_inside(subpath, path) and
+                    not is_inside(debian_dir, path)):
+                paths.append(path)
+        if not paths:
+            mutter('Skipping %s, which has no changes outside debian/',
+                   rev.revision_id)
+            continue
+        name = rev.properties.get(PATCH_NAME_REVPROP)
+        if name is None:
+            name = patch_name_from_message(rev.message)
+        if name in names:
+            base, ext = os.path.splitext(name)
+            i = 2
+            while "%s-%d%s" % (base, i, ext) in names:
+                i += 1
+            name = "%s-%d%s" % (base, i, ext)
+        names.add(name)
+        f = StringIO()
+        write_dep3_patch_header(
+            f, description=rev.message,
+            origin=rev.properties.get(PATCH_REVPROPS['Origin']),
+            forwarded=rev.properties.get(PATCH_REVPROPS['Forwarded']),
+            bugs=list(rev.iter_bugs()), authors=rev.get_apparent_authors(),
+            last_update=rev.timestamp,
+            applied_upstream=rev.properties.get(
+                PATCH_REVPROPS['Applied-Upstream']))
+        bf = BytesIO()
+        diff.show_diff_trees(
+            old_tree, new_tree, bf, specific_files=paths,
+            old_label='a/', new_label='b/')
+        # The diff is kept as bytes, as the files it touches may be in
+        # any encoding.
+        diff_text = b''.join(
+            line for line in bf.getvalue().splitlines(True)
+            if not line.startswith(b'=== '))
+        patches.append((
+            name, f.getvalue().encode('utf-8') +
+            _strip_subpath_labels(diff_text, subpath)))
+    return patches
+
+
+def _relabel_patch(contents, level):
+    # queue_patches generates patches for -p1; adjust the a/ and b/ labels
+    # for patches that the series file applies with a different level.
+    if level == 1:
+        return contents
+    return re.sub(
+        br'^(---|\+\+\+) ([ab])/', lambda m: m.group(1) + b' ' + (
+            (m.group(2) + b'/') * level),
+        contents, flags=re.MULTILINE)
+
+
+def write_patches(patches, patches_dir):
+    """Replace the patches in a debian/patches directory.
+
+    The patches that are listed in the old series file are removed, other
+    files in the directory are left alone. Options for patches that were
+    already in the series file, such as "-p0", are kept.
+
+    :param patches: list of (name, contents) tuples, as returned by
+        queue_patches
+    :param patches_dir: path to the debian/patches directory
+    :return: tuple with the lists of the names of the files that were
+        added and removed, relative to patches_dir
+    """
+    old_options = dict(_read_series(patches_dir))
+    new_names = [name for (name, contents) in patches]
+    removed = sorted(set(old_options) - set(new_names))
+    for name in removed:
+        os.unlink(os.path.join(patches_dir, name))
+    added = []
+    for name, contents in patches:
+        path = os.path.join(patches_dir, name)
+        if not os.path.isdir(os.path.dirname(path)):
+            os.makedirs(os.path.dirname(path))
+        if not os.path.exists(path):
+            added.append(name)
+        options = old_options.get(name, [])
+        with open(path, 'wb') as f:
+            f.write(_relabel_patch(contents, _strip_level(options)))
+    series_path = os.path.join(patches_dir, 'series')
+    if patches:
+        if not os.path.exists(series_path):
+            added.append('series')
+        with open(series_path, 'w') as f:
+            f.write(''.join(
+                ' '.join([name] + old_options.get(name, [])) + '\n'
+                for name in new_names))
+    elif os.path.exists(series_path):
+        os.unlink(series_path)
+        removed.append('series')
+    return added, removed
+
+
+def export_patch_queue(tree, queue_branch, base_revid, subpath=''):
+    """Regenerate debian/patches in a packaging tree from a patch queue.
+
+    :param tree: working tree of the packaging branch
+    :param queue_branch: the patch-queue branch
+    :param base_revid: the revision of the packaging branch the queue is
+        based on
+    :param subpath: subpath in the tree where the package lives
+    :return: list of the names of the patches
+    """
+    patches = queue_patches(queue_branch, base_revid, subpath=subpath)
+    patches_path = os.path.join(subpath, PATCHES_DIR)
+    added, removed = write_patches(patches, tree.abspath(patches_path))
+    if removed:
+        tree.remove([os.path.join(patches_path, name) for name in removed])
+    if added:
+        tree.smart_add([
+            tree.abspath(os.path.join(patches_path, name)) for name in added])
+    return [name for (name, contents) in patches]
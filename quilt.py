@@ -0,0 +1,151 @@
+#    quilt.py -- Managing the quilt patch series of a package
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Running quilt on the patch series of a package."""
+
+from __future__ import absolute_import
+
+import os
+import shutil
+import subprocess
+
+from ...errors import BzrError
+
+from .util import subprocess_setup
+
+
+# Settings that make quilt write patches the way dpkg-source expects them.
+QUILT_ENV = {
+    'QUILT_PATCHES': 'debian/patches',
+    'QUILT_PATCH_OPTS': '--reject-format=unified',
+    'QUILT_REFRESH_ARGS': '-p ab --no-timestamps --no-index',
+    'QUILT_DIFF_ARGS': '-p ab --no-timestamps --no-index',
+    }
+
+# The actions of "bzr deb-patch" and the quilt commands they run.
+QUILT_ACTIONS = {
+    'new': ['new'],
+    'refresh': ['refresh'],
+    'pop': ['pop'],
+    'push': ['push'],
+    'list': ['series', '-v'],
+    'drop': ['delete', '-r'],
+    }
+
+# Actions that don't change the patches.
+READ_ONLY_ACTIONS = ['list']
+
+# quilt exits with 2 if push or pop had nothing to do.
+NOTHING_TO_DO_ACTIONS = ['push', 'pop']
+
+
+class QuiltError(BzrError):
+
+    _fmt = "quilt %(command)s failed: %(output)s"
+
+    def __init__(self, command, retcode, output):
+        BzrError.__init__(
+            self, command=command, retcode=retcode, output=output)
+
+
+class UnknownQuiltAction(BzrError):
+
+    _fmt = "Unknown action %(action)s; valid actions are %(actions)s."
+
+    def __init__(self, action):
+        BzrError.__init__(
+            self, action=action, actions=", ".join(sorted(QUILT_ACTIONS)))
+
+
+def run_quilt(args, source_dir):
+    """Run quilt in an unpacked source tree.
+
+    :param args: the arguments to quilt, e.g. ["push", "-a"]
+    :param source_dir: the source tree, with the patches in debian/patches
+    :return: tuple with the exit code and the output of quilt
+    """
+    env = dict(os.environ)
+    env.update(QUILT_ENV)
+    proc = subprocess.Popen(
+        ['quilt'] + args, cwd=source_dir, env=env, stdout=subprocess.PIPE,
+        stderr=subprocess.STDOUT, preexec_fn=subprocess_setup)
+    output = proc.communicate()[0]
+    return proc.returncode, output.decode('utf-8', 'replace')
+
+
+def quilt_action(action, args, source_dir):
+    """Run one of the QUILT_ACTIONS.
+
+    :param action: the name of the action, e.g. "refresh"
+    :param args: extra arguments for quilt, e.g. the name of the patch
+    :param source_dir: the source tree, with the patches in debian/patches
+    :return: the output of quilt
+    :raise QuiltError: if quilt failed
+    """
+    try:
+        command = QUILT_ACTIONS[action] + list(args)
+    except KeyError:
+        raise UnknownQuiltAction(action)
+    retcode, output = run_quilt(command, source_dir)
+    if retcode == 0 or (retcode == 2 and action in NOTHING_TO_DO_ACTIONS):
+        return output
+    raise QuiltError(" ".join(command), retcode, output)
+
+
+def _list_files(path):
+    ret = set()
+    for dirpath, dirnames, filenames in os.walk(path):
+        for filename in filenames:
+            ret.add(os.path.relpath(os.path.join(dirpath, filename), path))
+    return ret
+
+
+def copy_patches(source_patches_dir, target_patches_dir):
+    """Make a debian/patches directory a copy of another one.
+
+    :param source_patches_dir: the directory to copy from
+    :param target_patches_dir: the directory to update
+    """
+    if os.path.isdir(target_patches_dir):
+        source_files = _list_files(source_patches_dir)
+        for name in _list_files(target_patches_dir) - source_files:
+            os.unlink(os.path.join(target_patches_dir, name))
+    if os.path.isdir(source_patches_dir):
+        for name in _list_files(source_patches_dir):
+            target = os.path.join(target_patches_dir, name)
+            if not os.path.isdir(os.path.dirname(target)):
+                os.makedirs(os.path.dirname(target))
+            shutil.copy2(os.path.join(source_patches_dir, name), target)
+
+
+def update_patches_versioning(tree, patches_path):
+    """Version new patches and unversion the ones that were removed.
+
+    :param tree: the working tree
+    :param patches_path: path of debian/patches in the tree
+    """
+    missing = [
+        change.path[0] for change in tree.iter_changes(
+            tree.basis_tree(), specific_files=[patches_path])
+        if change.kind[1] is None and change.path[0] is not None]
+    if missing:
+        tree.remove(missing)
+    if os.path.isdir(tree.abspath(patches_path)):
+        tree.smart_add([tree.abspath(patches_path)])
@@ -0,0 +1,266 @@
+#    registry.py -- Fetching upstream sources from language registries
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Fetching upstream sources from the Go module proxy and PyPI.
+
+Packages for Go modules and Python distributions usually follow the
+conventions of dh-make-golang and pybuild closely enough that the upstream
+source can be found from the name and version of the package alone.
+"""
+
+from __future__ import absolute_import
+
+from io import BytesIO
+import os
+import tarfile
+import time
+import zipfile
+
+from ...errors import (
+    BzrError,
+    NoSuchFile,
+    )
+from ...trace import mutter, note
+from ... import urlutils
+
+from .util import (
+    open_file_via_transport,
+    open_transport,
+    )
+
+
+DEFAULT_GO_PROXY = 'https://proxy.golang.org/'
+DEFAULT_PYPI_MIRROR = 'https://files.pythonhosted.org/'
+
+GO_PACKAGE_PREFIX = 'golang-'
+PYTHON_PACKAGE_PREFIXES = ['python3-', 'python-']
+
+# Hosting sites as they appear in the names of Go packages, following the
+# dh-make-golang naming scheme, and whether their paths start with a user.
+GO_HOSTS = [
+    ('github-', 'github.com/', True),
+    ('gitlab-', 'gitlab.com/', True),
+    ('bitbucket-', 'bitbucket.org/', True),
+    ('golang-x-', 'golang.org/x/', False),
+    ('google-', 'google.golang.org/', False),
+    ('gopkg-', 'gopkg.in/', False),
+    ]
+
+
+class RegistryFetchFailed(BzrError):
+
+    _fmt = "Unable to fetch %(name)s %(version)s from %(url)s: %(error)s"
+
+    def __init__(self, name, version, url, error):
+        BzrError.__init__(
+            self, name=name, version=version, url=url, error=error)
+
+
+class UnknownGoImportPath(BzrError):
+
+    _fmt = ("Unable to determine the Go import path for %(package)s. "
+            "Set XS-Go-Import-Path in debian/control.")
+
+    def __init__(self, package):
+        BzrError.__init__(self, package=package)
+
+
+class UnknownPyPIProject(BzrError):
+
+    _fmt = ("Unable to determine the PyPI project for %(package)s. "
+            "Set pypi-project in the configuration, or add a PyPI entry to "
+            "Registry in debian/upstream/metadata.")
+
+    def __init__(self, package):
+        BzrError.__init__(self, package=package)
+
+
+def _strip_repack_suffix(upstream_version):
+    # Suffixes such as +ds or +dfsg are added for repacked tarballs.
+    return upstream_version.split('+', 1)[0]
+
+
+def go_import_path_from_package(package):
+    """Guess the Go import path from the name of a source package.
+
+    :param package: name of the source package, e.g. golang-github-foo-bar
+    :return: the import path, e.g. github.com/foo/bar, or None if it can
+        not be determined
+    """
+    if not package.startswith(GO_PACKAGE_PREFIX):
+        return None
+    name = package[len(GO_PACKAGE_PREFIX):]
+    for prefix, host, has_user in GO_HOSTS:
+        if not name.startswith(prefix):
+            continue
+        path = name[len(prefix):]
+        if not has_user:
+            return host + path
+        # The project name may contain dashes too, so this is a guess.
+        if '-' not in path:
+            return None
+        return host + path.replace('-', '/', 1)
+    return None
+
+
+def registry_from_package(package):
+    """Determine the language registry from the name of a source package.
+
+    Packages of Go and Python modules are named after the module, like
+    the rust-* packages of debcargo are named after the crate.
+
+    :param package: name of the source package
+    :return: "go" for golang-* packages, "pypi" for python3-* and python-*
+        packages, or None
+    """
+    if package.startswith(GO_PACKAGE_PREFIX):
+        return 'go'
+    if python_project_from_package(package) is not None:
+        return 'pypi'
+    return None
+
+
+def go_module_escape(path):
+    """Escape a module path or version the way the Go module proxy does.
+
+    Upper case letters are replaced by an exclamation mark followed by the
+    lower case letter.
+    """
+    return ''.join(
+        '!' + c.lower() if c.isupper() else c for c in path)
+
+
+def go_module_version(upstream_version):
+    """Convert the upstream part of a Debian version to a Go module version.
+
+    :param upstream_version: the upstream version, e.g. 1.2.0~rc1+ds
+    :return: the module version, e.g. v1.2.0-rc1
+    """
+    return 'v' + _strip_repack_suffix(upstream_version).replace('~', '-')
+
+
+def python_project_from_package(package):
+    """Determine the name of the PyPI project from a source package name.
+
+    :return: the project name, or None if the package is not named after
+        a Python project
+    """
+    for prefix in PYTHON_PACKAGE_PREFIXES:
+        if package.startswith(prefix):
+            return package[len(prefix):]
+    return None
+
+
+def pypi_version(upstream_version):
+    """Convert the upstream part of a Debian version to a PyPI version.
+
+    :param upstream_version: the upstream version, e.g. 1.2~rc1
+    :return: the PyPI version, e.g. 1.2rc1
+    """
+    return _strip_repack_suffix(upstream_version).replace('~', '')
+
+
+def _fetch(base_url, relpath):
+    filename, transport = open_transport(urlutils.join(base_url, relpath))
+    f = open_file_via_transport(filename, transport)
+    try:
+        return f.read()
+    finally:
+        f.close()
+
+
+def _orig_tarball_path(target_dir, package, upstream_version):
+    return os.path.join(
+        target_dir, "%s_%s.orig.tar.gz" % (package, upstream_version))
+
+
+def fetch_go_module(proxy, import_path, package, upstream_version,
+                    target_dir):
+    """Fetch a Go module from a module proxy as an orig tarball.
+
+    :param proxy: URL of the module proxy, or a local mirror of one
+    :param import_path: the import path of the module
+    :param package: name of the source package
+    :param upstream_version: upstream part of the version of the package
+    :param target_dir: directory to create the orig tarball in
+    :return: path to the orig tarball
+    """
+    version = go_module_version(upstream_version)
+    relpath = "%s/@v/%s.zip" % (
+        go_module_escape(import_path), go_module_escape(version))
+    note("Fetching Go module %s %s from %s", import_path, version, proxy)
+    try:
+        data = _fetch(proxy, relpath)
+    except NoSuchFile as e:
+        raise RegistryFetchFailed(import_path, version, proxy, e)
+    # Module zips have all files under "<module>@<version>/"; the orig
+    # tarball has them under "<package>-<version>/".
+    zip_prefix = "%s@%s/" % (import_path, version)
+    tar_prefix = "%s-%s/" % (package, upstream_version)
+    path = _orig_tarball_path(target_dir, package, upstream_version)
+    with zipfile.ZipFile(BytesIO(data)) as zf, \
+            tarfile.open(path, 'w:gz') as tf:
+        for info in zf.infolist():
+            if info.filename.endswith('/'):
+                continue
+            if not info.filename.startswith(zip_prefix):
+                raise RegistryFetchFailed(
+                    import_path, version, proxy,
+                    "unexpected file %s in module zip" % info.filename)
+            contents = zf.read(info)
+            tarinfo = tarfile.TarInfo(
+                tar_prefix + info.filename[len(zip_prefix):])
+            tarinfo.size = len(contents)
+            tarinfo.mode = 0o644
+            tarinfo.mtime = time.mktime(info.date_time + (0, 0, -1))
+            tf.addfile(tarinfo, BytesIO(contents))
+    mutter("Created %s from Go module %s", path, import_path)
+    return path
+
+
+def fetch_pypi_sdist(mirror, project, package, upstream_version, target_dir):
+    """Fetch the sdist of a Python project from PyPI as an orig tarball.
+
+    :param mirror: URL of the PyPI file host, or a local mirror of it
+    :param project: name of the project on PyPI
+    :param package: name of the source package
+    :param upstream_version: upstream part of the version of the package
+    :param target_dir: directory to create the orig tarball in
+    :return: path to the orig tarball
+    """
+    version = pypi_version(upstream_version)
+    # Newer sdists use the normalized project name in the filename.
+    names = [project]
+    if project.replace('-', '_') != project:
+        names.append(project.replace('-', '_'))
+    note("Fetching %s %s from %s", project, version, mirror)
+    for name in names:
+        relpath = "packages/source/%s/%s/%s-%s.tar.gz" % (
+            project[0], project, name, version)
+        try:
+            data = _fetch(mirror, relpath)
+        except NoSuchFile as e:
+            mutter("%s not found: %s", relpath, e)
+            continue
+        path = _orig_tarball_path(target_dir, package, upstream_version)
+        with open(path, 'wb') as f:
+            f.write(data)
+        return path
+    raise RegistryFetchFailed(project, version, mirror, "no sdist found")
@@ -35,6 +35,7 @@ from ...errors import (
     BzrError,
     DependencyNotPresent,
     FileExists,
+    NoSuchFile,
     )
 from ...transport import get_transport
 
@@ -189,6 +190,20 @@ def _repack_other(target_transport, new_name, source_name):
             repacker.repack(target_f)
 
 
+def _copy_signature(target_transport, new_name, source_name):
+    # Keep the detached upstream signature next to the tarball, so that it
+    # can be verified and included in the source package.
+    signature_name = new_name + '.asc'
+    if target_transport.has(signature_name):
+        return
+    try:
+        source_f = open_file(source_name + '.asc')
+    except NoSuchFile:
+        return
+    with source_f:
+        target_transport.put_file(signature_name, source_f)
+
+
 def repack_tarball(source_name, new_name, target_dir=None):
     """Repack the file/dir named to a .tar.gz with the chosen name.
 
@@ -203,6 +218,10 @@ def repack_tarball(source_name, new_name, target_dir=None):
     The source must exist, and the target cannot exist, unless it is identical
     to the source.
 
+    If the source has a detached signature (the name of the source with
+    ".asc" appended) and is copied without repacking, the signature is
+    copied along with it.
+
     :param source_name: the current name of the file/dir
     :type source_name: string
     :param new_name: the desired name of the tarball
@@ -226,8 +245,11 @@ def repack_tarball(source_name, new_name, target_dir=None):
         if source_format != target_format:
             raise FileExists(new_name)
         _error_if_exists(target_transport, new_name, source_name)
+        _copy_signature(target_transport, new_name, source_name)
         return
     if os.path.isdir(source_name):
         _repack_directory(target_transport, new_name, source_name)
     else:
         _repack_other(target_transport, new_name, source_name)
+        if get_filetype(source_name) == get_filetype(new_name):
+            _copy_signature(target_transport, new_name, source_name)
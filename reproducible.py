@@ -0,0 +1,279 @@
+#    reproducible.py -- Check whether a package builds reproducibly
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Checking whether packages build reproducibly."""
+
+from __future__ import absolute_import
+
+import email.utils
+import hashlib
+import io
+import os
+import shutil
+import subprocess
+import tarfile
+
+from debian import deb822
+
+from ...errors import BzrError
+from ...trace import note
+
+from .builder import (
+    BuildFailedError,
+    DebBuild,
+    build_log_name,
+    )
+from .util import (
+    find_changes_files,
+    get_parent_dir,
+    subprocess_setup,
+    )
+
+
+class NotAnArArchive(BzrError):
+
+    _fmt = "%(path)s is not an ar archive."
+
+    def __init__(self, path):
+        BzrError.__init__(self, path=path)
+
+
+class ReproducibleBuildFailed(BzrError):
+
+    _fmt = "The %(variation)s build failed: %(error)s"
+
+    def __init__(self, variation, error):
+        BzrError.__init__(self, variation=variation, error=error)
+
+
+class BuildVariation(object):
+    """The environment for one of the builds of a reproducibility check.
+
+    :ivar name: name of the variation, used for its build directory
+    :ivar environment: dictionary with environment variables to set
+    :ivar umask: umask to build with
+    """
+
+    def __init__(self, name, environment, umask):
+        self.name = name
+        self.environment = environment
+        self.umask = umask
+
+
+# The two builds differ in everything that commonly leaks into build
+# results, except for SOURCE_DATE_EPOCH which is set to the same value.
+VARIATIONS = [
+    BuildVariation(
+        'first',
+        {'TZ': 'UTC', 'LANG': 'C.UTF-8', 'LC_ALL': 'C.UTF-8'}, 0o022),
+    BuildVariation(
+        'second-with-a-longer-path',
+        {'TZ': 'Etc/GMT-14', 'LANG': 'fr_CH.UTF-8',
+         'LC_ALL': 'fr_CH.UTF-8'}, 0o002),
+    ]
+
+
+def changelog_timestamp(changelog):
+    """Return the time of the last changelog entry.
+
+    This is what SOURCE_DATE_EPOCH is conventionally set to.
+
+    :param changelog: a Changelog
+    :return: seconds since the epoch, or None if the changelog has no
+        parseable date
+    """
+    if changelog.date is None:
+        return None
+    time_tuple = email.utils.parsedate_tz(changelog.date)
+    if time_tuple is None:
+        return None
+    return email.utils.mktime_tz(time_tuple)
+
+
+def read_ar_members(path):
+    """Read the members of an ar archive, such as a .deb.
+
+    :param path: path to the archive
+    :return: list of (name, contents) tuples
+    """
+    members = []
+    with open(path, 'rb') as f:
+        if f.read(8) != b'!<arch>\n':
+            raise NotAnArArchive(path)
+        while True:
+            header = f.read(60)
+            if len(header) < 60:
+                break
+            name = header[:16].decode('ascii', 'replace').strip()
+            if name.endswith('/'):
+                name = name[:-1]
+            size = int(header[48:58].strip())
+            members.append((name, f.read(size)))
+            if size % 2:
+                f.read(1)
+    return members
+
+
+def _tar_entries(data):
+    entries = {}
+    with tarfile.open(fileobj=io.BytesIO(data), mode='r:*') as tf:
+        for info in tf:
+            if info.isfile():
+                digest = hashlib.sha256(
+                    tf.extractfile(info).read()).hexdigest()
+            else:
+                digest = None
+            entries[info.name] = (
+                info.type, info.mode, info.uid, info.gid, info.uname,
+                info.gname, info.mtime, info.linkname, digest)
+    return entries
+
+
+def _compare_member(data1, data2):
+    try:
+        entries1 = _tar_entries(data1)
+        entries2 = _tar_entries(data2)
+    except (tarfile.TarError, EOFError):
+        # Not a tarball, or compressed in a way tarfile doesn't support
+        return []
+    return sorted(
+        name for name in set(entries1) | set(entries2)
+        if entries1.get(name) != entries2.get(name))
+
+
+def compare_debs(path1, path2):
+    """Compare two binary packages.
+
+    :param path1: path to the first .deb
+    :param path2: path to the second .deb
+    :return: list of (member, paths) tuples for the members of the .deb
+        that differ, where paths lists the files inside the member that
+        differ, if it could be determined.
+    """
+    members1 = dict(read_ar_members(path1))
+    members2 = dict(read_ar_members(path2))
+    differences = []
+    for name in sorted(set(members1) | set(members2)):
+        data1 = members1.get(name)
+        data2 = members2.get(name)
+        if data1 == data2:
+            continue
+        if data1 is None or data2 is None:
+            differences.append((name, []))
+        else:
+            differences.append((name, _compare_member(data1, data2)))
+    return differences
+
+
+def _changes_debs(changes_path):
+    directory = os.path.dirname(changes_path)
+    with open(changes_path, 'rb') as f:
+        changes = deb822.Changes(f)
+    return {
+        file_details['name']: os.path.join(directory, file_details['name'])
+        for file_details in changes['files']
+        if file_details['name'].endswith('.deb')}
+
+
+def run_diffoscope(path1, path2, output_path):
+    """Run diffoscope on two files, writing its report to output_path."""
+    with open(output_path, 'wb') as f:
+        subprocess.call(
+            ['diffoscope', path1, path2], stdout=f,
+            preexec_fn=subprocess_setup)
+
+
+def check_reproducible(source_dir, build_dir, builder, package, version,
+                       source_date_epoch=None, log_dir=None,
+                       diffoscope_dir=None):
+    """Build an exported source tree twice and compare the binary packages.
+
+    Each build gets a copy of source_dir, and of the upstream tarballs next
+    to it, at a different path beneath build_dir, and runs with one of the
+    VARIATIONS.
+
+    :param source_dir: the exported source tree to build.
+    :param build_dir: the directory beneath which to build.
+    :param builder: the Builder or build command to use.
+    :param package: the name of the source package.
+    :param version: the Version of the package.
+    :param source_date_epoch: the value for SOURCE_DATE_EPOCH, or None.
+    :param log_dir: directory in which to write a build log for each
+        build, or None to not keep build logs.
+    :param diffoscope_dir: directory in which to write a diffoscope report
+        for each package that differs, or None to not run diffoscope.
+    :return: tuple with the path of the changes file of the first build
+        and a dictionary mapping the names of the .deb files that differ to
+        the list of differences as returned by compare_debs.
+    """
+    parent_dir = get_parent_dir(source_dir) or '.'
+    tarballs = [
+        entry.path for entry in os.scandir(parent_dir)
+        if '.orig' in entry.name and entry.is_file()]
+    changes_paths = []
+    for variation in VARIATIONS:
+        variation_dir = os.path.join(build_dir, 'reproducible', variation.name)
+        if os.path.exists(variation_dir):
+            shutil.rmtree(variation_dir)
+        os.makedirs(variation_dir)
+        variation_source_dir = os.path.join(
+            variation_dir, os.path.basename(source_dir.rstrip('/')))
+        shutil.copytree(source_dir, variation_source_dir, symlinks=True)
+        for tarball in tarballs:
+            shutil.copy(tarball, variation_dir)
+        environment = dict(variation.environment)
+        if source_date_epoch is not None:
+            environment['SOURCE_DATE_EPOCH'] = str(source_date_epoch)
+        if log_dir is not None:
+            log_path = os.path.join(
+                log_dir, build_log_name(package, version, variation.name))
+        else:
+            log_path = None
+        note("Running the %s build", variation.name)
+        variation_builder = DebBuild(
+            None, variation_source_dir, builder, log_path=log_path,
+            environment=environment, umask=variation.umask)
+        try:
+            variation_builder.build()
+        except BuildFailedError as e:
+            raise ReproducibleBuildFailed(variation.name, e)
+        for kind, entry in find_changes_files(
+                variation_builder.result_dir, package, version):
+            if kind != 'source':
+                changes_paths.append(entry.path)
+                break
+        else:
+            raise ReproducibleBuildFailed(
+                variation.name, "no changes file was found")
+    debs1 = _changes_debs(changes_paths[0])
+    debs2 = _changes_debs(changes_paths[1])
+    differences = {}
+    for name in sorted(set(debs1) | set(debs2)):
+        if name not in debs1 or name not in debs2:
+            differences[name] = [(name, [])]
+            continue
+        deb_differences = compare_debs(debs1[name], debs2[name])
+        if deb_differences:
+            differences[name] = deb_differences
+            if diffoscope_dir is not None:
+                run_diffoscope(
+                    debs1[name], debs2[name],
+                    os.path.join(diffoscope_dir, name + '.diffoscope'))
+    return changes_paths[0], differences
@@ -28,10 +28,11 @@ import sys
 import tempfile
 
 from ... import errors as bzr_errors
+from ... import osutils
 from ...export import (
     export,
     )
-from ...trace import note
+from ...trace import mutter, note
 
 from .util import (
     extract_orig_tarballs,
@@ -68,62 +69,255 @@ class SourceDistiller(object):
         raise NotImplementedError(self.distill)
 
 
+EXPORT_RECORD_SUFFIX = '.bzr-builddeb-export'
+
+
+def _export_record_path(target):
+    """Return the path of the file that records what was exported to target.
+
+    The record is kept next to the export in the build directory, so that it
+    doesn't end up in the source package.
+    """
+    return target.rstrip('/') + EXPORT_RECORD_SUFFIX
+
+
+def _tree_state(tree):
+    """Return the revision a tree is based on and its uncommitted paths."""
+    if getattr(tree, 'last_revision', None) is not None:
+        # A working tree, which may have uncommitted changes
+        revid = tree.last_revision()
+        dirty = set()
+        with tree.lock_read():
+            for change in tree.iter_changes(tree.basis_tree()):
+                dirty.update(p for p in change.path if p is not None)
+        return revid, dirty
+    return tree.get_revision_id(), set()
+
+
+def _tree_repository(tree):
+    branch = getattr(tree, 'branch', None)
+    if branch is not None:
+        return branch.repository
+    return None
+
+
+def record_export(tree, target):
+    """Record which state of a tree was exported to target.
+
+    :param tree: the tree that was exported
+    :param target: the location the tree was exported to
+    """
+    revid, dirty = _tree_state(tree)
+    with open(_export_record_path(target), 'wb') as f:
+        f.write(revid + b'\n')
+        for path in sorted(dirty):
+            f.write(path.encode('utf-8') + b'\n')
+
+
+def remove_export_record(target):
+    """Remove the record of what was exported to target, if there is one.
+
+    :param target: the location the tree was exported to
+    """
+    try:
+        os.unlink(_export_record_path(target))
+    except FileNotFoundError:
+        pass
+
+
+def _sync_path(tree, path, target, subpath):
+    if subpath:
+        if not osutils.is_inside(subpath, path) or path == subpath:
+            return
+        relpath = path[len(subpath):].lstrip('/')
+    else:
+        relpath = path
+    if tree.is_special_path(relpath.split('/')[0]):
+        return
+    if relpath.split('/')[0] == '.bzr-builddeb':
+        return
+    target_path = os.path.join(target, relpath)
+    if tree.has_filename(path) and tree.is_versioned(path):
+        kind = tree.kind(path)
+    else:
+        kind = None
+    if os.path.lexists(target_path):
+        if os.path.isdir(target_path) and not os.path.islink(target_path):
+            if kind == 'directory':
+                return
+            shutil.rmtree(target_path)
+        else:
+            os.unlink(target_path)
+    if kind is None:
+        return
+    parent = os.path.dirname(target_path)
+    if not os.path.isdir(parent):
+        os.makedirs(parent)
+    if kind == 'directory':
+        os.mkdir(target_path)
+    elif kind == 'symlink':
+        os.symlink(tree.get_symlink_target(path), target_path)
+    else:
+        with open(target_path, 'wb') as f:
+            f.write(tree.get_file_text(path))
+        if tree.is_executable(path):
+            os.chmod(target_path, 0o755)
+
+
+def sync_export(tree, target, subpath=''):
+    """Bring an earlier export of a tree up to date.
+
+    Only the files that changed between the exported state of the tree
+    (as recorded by record_export) and the current tree are updated.
+
+    :param tree: the tree to export
+    :param target: the location of the earlier export
+    :param subpath: subpath in the tree that was exported
+    :return: True if the export was brought up to date, False if there was
+        no usable earlier export.
+    """
+    record_path = _export_record_path(target)
+    if not os.path.isdir(target) or not os.path.exists(record_path):
+        return False
+    with open(record_path, 'rb') as f:
+        lines = f.read().splitlines()
+    if not lines:
+        return False
+    old_revid = lines[0]
+    paths = set(line.decode('utf-8') for line in lines[1:])
+    repository = _tree_repository(tree)
+    if repository is not None:
+        try:
+            old_tree = repository.revision_tree(old_revid)
+        except bzr_errors.NoSuchRevision:
+            mutter('Previously exported revision %r no longer present',
+                   old_revid)
+            return False
+    elif old_revid == tree.get_revision_id():
+        # Only the paths that had uncommitted changes when the same
+        # revision was exported can differ.
+        old_tree = None
+    else:
+        return False
+    with tree.lock_read():
+        if old_tree is not None:
+            with old_tree.lock_read():
+                for change in tree.iter_changes(old_tree):
+                    paths.update(p for p in change.path if p is not None)
+        note("Updating %d changed paths in %s", len(paths), target)
+        for path in sorted(paths):
+            _sync_path(tree, path, target, subpath)
+    record_export(tree, target)
+    return True
+
+
 class NativeSourceDistiller(SourceDistiller):
     """A SourceDistiller for unpacking a native package from a branch."""
 
-    def __init__(self, tree, subpath):
+    def __init__(self, tree, subpath, use_existing=False):
         """Create a SourceDistiller to distill from the specified tree.
 
         :param tree: The tree to use as the source.
         :param subpath: subpath in the tree where the package lives
+        :param use_existing: whether the distiller should update an earlier
+            export rather than export from scratch.
         """
         super(NativeSourceDistiller, self).__init__(tree, subpath)
+        self.use_existing = use_existing
 
     def distill(self, target):
         """Extract the source to a tree rooted at the given location.
 
-        The passed location cannot already exist. If it does then
-        FileExists will be raised.
+        The passed location cannot already exist, unless use_existing is
+        set. If it does then FileExists will be raised.
 
         :param target: a string containing the location at which to
             place the tree containing the buildable source.
         """
+        if self.use_existing and sync_export(self.tree, target, self.subpath):
+            return
         if os.path.exists(target):
-            raise bzr_errors.FileExists(target)
+            if not self.use_existing:
+                raise bzr_errors.FileExists(target)
+            note("Unable to update %s, exporting again", target)
+            shutil.rmtree(target)
         export(self.tree, target, subdir=self.subpath)
+        if self.use_existing:
+            record_export(self.tree, target)
+        else:
+            remove_export_record(target)
 
 
 class FullSourceDistiller(SourceDistiller):
     """A SourceDistiller for full-source branches, a.k.a. normal mode"""
 
-    def __init__(self, tree, subpath, upstream_provider):
+    def __init__(self, tree, subpath, upstream_provider, use_existing=False):
         """Create a SourceDistiller to distill from the specified tree.
 
         :param tree: The tree to use as the source.
         :param subpath: subpath in the tree where the package lives
         :param upstream_provider: an UpstreamProvider to provide the upstream
             tarball if needed.
+        :param use_existing: whether the distiller should update an earlier
+            export rather than export from scratch.
         """
         super(FullSourceDistiller, self).__init__(tree, subpath)
         self.upstream_provider = upstream_provider
+        self.use_existing = use_existing
 
     def distill(self, target):
         """Extract the source to a tree rooted at the given location.
 
-        The passed location cannot already exist. If it does then
-        FileExists will be raised.
+        The passed location cannot already exist, unless use_existing is
+        set. If it does then FileExists will be raised.
 
         :param target: a string containing the location at which to
             place the tree containing the buildable source.
         """
-        if os.path.exists(target):
+        if os.path.exists(target) and not self.use_existing:
             raise bzr_errors.FileExists(target)
         parent_dir = get_parent_dir(target)
         self.upstream_provider.provide(parent_dir)
+        if self.use_existing and sync_export(self.tree, target, self.subpath):
+            return
+        if os.path.exists(target):
+            note("Unable to update %s, exporting again", target)
+            shutil.rmtree(target)
         export(self.tree, target, subdir=self.subpath)
+        if self.use_existing:
+            record_export(self.tree, target)
+        else:
+            remove_export_record(target)
         # TODO(jelmer): Unapply patches, if they're applied.
 
 
+class DgitSourceDistiller(FullSourceDistiller):
+    """A SourceDistiller that gives the source as dgit sees it.
+
+    dgit works with the tree that results from unpacking the source
+    package, which has the quilt patches applied. If the branch keeps the
+    patches unapplied they are applied after exporting.
+    """
+
+    def distill(self, target):
+        """Extract the source to a tree rooted at the given location.
+
+        The passed location cannot already exist, unless use_existing is
+        set. If it does then FileExists will be raised.
+
+        :param target: a string containing the location at which to
+            place the tree containing the buildable source.
+        """
+        from .dgit import (
+            apply_patches,
+            patches_applied,
+            )
+        super(DgitSourceDistiller, self).distill(target)
+        if patches_applied(target) is False:
+            note("Applying the quilt patches")
+            apply_patches(target)
+
+
 class MergeModeDistiller(SourceDistiller):
 
     def __init__(self, tree, subpath, upstream_provider, top_level=False,
@@ -144,12 +338,23 @@ class MergeModeDistiller(SourceDistiller):
         self.top_level = top_level
         self.use_existing = use_existing
 
+    def _provide_tarballs(self, target_dir):
+        """Provide the upstream tarballs.
+
+        :param target_dir: the directory to put the tarballs in
+        :return: list of (path, component) tuples
+        """
+        return self.upstream_provider.provide(target_dir)
+
     def distill(self, target):
         """Extract the source to a tree rooted at the given location.
 
         The passed location cannot already exist. If it does then
         FileExists will be raised.
 
+        Additional upstream tarballs (.orig-COMPONENT.tar.*) are extracted
+        into a subdirectory named after their component.
+
         :param target: a string containing the location at which to
             place the tree containing the buildable source.
         """
@@ -165,7 +370,7 @@ class MergeModeDistiller(SourceDistiller):
         if parent_dir != '' and not os.path.exists(parent_dir):
             os.makedirs(parent_dir)
         if not self.use_existing:
-            tarballs = self.upstream_provider.provide(parent_dir)
+            tarballs = self._provide_tarballs(parent_dir)
             if not os.path.exists(target):
                 os.mkdir(target)
             extract_orig_tarballs(tarballs, target)
@@ -190,6 +395,55 @@ class MergeModeDistiller(SourceDistiller):
                 recursive_copy(tempdir, target)
 
 
+class PatchQueueDistiller(SourceDistiller):
+    """A SourceDistiller that takes debian/patches from a patch-queue branch.
+
+    The source is distilled by another distiller, after which the patches
+    in debian/patches are replaced by those generated from the commits on
+    the patch-queue branch.
+    """
+
+    def __init__(self, distiller, queue_branch, base_revid,
+                 queue_revid=None):
+        """Create a SourceDistiller to distill from a patch-queue branch.
+
+        :param distiller: the SourceDistiller for the packaging tree.
+        :param queue_branch: the patch-queue branch.
+        :param base_revid: the revision of the packaging branch that the
+            patch queue is based on.
+        :param queue_revid: the last revision of the patch queue to use,
+            defaults to the tip of queue_branch.
+        """
+        super(PatchQueueDistiller, self).__init__(
+            distiller.tree, distiller.subpath)
+        self.distiller = distiller
+        self.queue_branch = queue_branch
+        self.base_revid = base_revid
+        self.queue_revid = queue_revid
+
+    def distill(self, target):
+        """Extract the source to a tree rooted at the given location.
+
+        The passed location cannot already exist. If it does then
+        FileExists will be raised.
+
+        :param target: a string containing the location at which to
+            place the tree containing the buildable source.
+        """
+        from .patch_queue import (
+            PATCHES_DIR,
+            queue_patches,
+            write_patches,
+            )
+        self.distiller.distill(target)
+        with self.queue_branch.lock_read():
+            patches = queue_patches(
+                self.queue_branch, self.base_revid, self.queue_revid,
+                subpath=self.subpath)
+        write_patches(patches, os.path.join(target, PATCHES_DIR))
+        note("Using %d patches from the patch queue", len(patches))
+
+
 class DebcargoError(bzr_errors.BzrError):
 
     _fmt = "Debcargo failed to run."
@@ -275,3 +529,232 @@ class DebcargoDistiller(SourceDistiller):
                 + ([crate_version] if crate_version else []))
         except subprocess.CalledProcessError:
             raise DebcargoError()
+
+
+GO_RULES = """\
+#!/usr/bin/make -f
+
+%:
+\tdh $@ --builddirectory=_build --buildsystem=golang
+"""
+
+GO_CONTROL = """\
+Source: %(package)s
+Section: golang
+Priority: optional
+Maintainer: %(maintainer)s
+Build-Depends: debhelper-compat (= 13), dh-sequence-golang, golang-any
+Standards-Version: 4.6.2
+Testsuite: autopkgtest-pkg-go
+XS-Go-Import-Path: %(import_path)s
+
+Package: %(package)s-dev
+Architecture: all
+Multi-Arch: foreign
+Depends: ${misc:Depends}
+Description: Go module %(import_path)s
+ This package contains the source of the %(import_path)s Go module.
+"""
+
+PYBUILD_RULES = """\
+#!/usr/bin/make -f
+
+export PYBUILD_NAME=%(module)s
+
+%%:
+\tdh $@ --buildsystem=pybuild
+"""
+
+PYBUILD_CONTROL = """\
+Source: %(package)s
+Section: python
+Priority: optional
+Maintainer: %(maintainer)s
+Build-Depends: debhelper-compat (= 13), dh-sequence-python3,
+ pybuild-plugin-pyproject, python3-all, python3-setuptools
+Standards-Version: 4.6.2
+Testsuite: autopkgtest-pkg-pybuild
+
+Package: python3-%(module_package)s
+Architecture: all
+Depends: ${python3:Depends}, ${misc:Depends}
+Description: Python module %(project)s
+ This package contains the %(project)s Python module.
+"""
+
+
+class RegistryDistiller(MergeModeDistiller):
+    """A SourceDistiller for packages of modules from a language registry.
+
+    The tree only contains the packaging. The upstream tarball is provided
+    as in merge mode, or if it can not be found it is created from the
+    source in the registry. Packaging files that are missing from the tree
+    are generated, following the conventions for the language.
+    """
+
+    def __init__(self, tree, subpath, upstream_provider, package,
+                 upstream_version, top_level=False, use_existing=False):
+        """Create a SourceDistiller to distill from the specified tree.
+
+        :param tree: The tree to use as the source.
+        :param subpath: subpath in the tree where the package lives
+        :param upstream_provider: an UpstreamProvider to provide the upstream
+            tarball if it is available elsewhere.
+        :param package: name of the source package
+        :param upstream_version: upstream part of the version of the package
+        :param top_level: if the tree is in the top level directory instead of
+            inside debian/.
+        :param use_existing: whether the distiller should re-use an existing
+            target if the distiller supports it.
+        """
+        super(RegistryDistiller, self).__init__(
+            tree, subpath, upstream_provider, top_level=top_level,
+            use_existing=use_existing)
+        self.package = package
+        self.upstream_version = upstream_version
+
+    def fetch(self, target_dir):
+        """Fetch the upstream source from the registry.
+
+        :param target_dir: the directory to create the orig tarball in
+        :return: path to the orig tarball
+        """
+        raise NotImplementedError(self.fetch)
+
+    def skeleton(self, maintainer):
+        """Return the packaging files to generate if they are missing.
+
+        :param maintainer: the maintainer of the package
+        :return: dictionary mapping paths relative to debian/ to tuples with
+            the contents and the mode of the file
+        """
+        # dpkg-source treats a package without a source format as 1.0, so
+        # only packages that are generated from scratch get one.
+        if (self._has_packaging_file('control') or
+                self._has_packaging_file('rules')):
+            return {}
+        return {'source/format': ('3.0 (quilt)\n', 0o644)}
+
+    def _packaging_path(self, name):
+        return os.path.join(
+            self.subpath, name if self.top_level else 'debian/' + name)
+
+    def _has_packaging_file(self, name):
+        return self.tree.has_filename(self._packaging_path(name))
+
+    def _read_control(self):
+        path = self._packaging_path('control')
+        try:
+            text = self.tree.get_file_text(path)
+        except bzr_errors.NoSuchFile:
+            return None
+        from debian.deb822 import Deb822
+        return Deb822(text)
+
+    def _provide_tarballs(self, target_dir):
+        from .upstream import MissingUpstreamTarball
+        try:
+            return super(RegistryDistiller, self)._provide_tarballs(
+                target_dir)
+        except MissingUpstreamTarball:
+            return [(self.fetch(target_dir), None)]
+
+    def distill(self, target):
+        super(RegistryDistiller, self).distill(target)
+        debian_dir = os.path.join(target, 'debian')
+        with open(os.path.join(debian_dir, 'changelog'), 'r') as f:
+            maintainer = Changelog(f, max_blocks=1).author
+        for path, (contents, mode) in sorted(
+                self.skeleton(maintainer).items()):
+            path = os.path.join(debian_dir, path)
+            if os.path.exists(path):
+                continue
+            mutter('Generating %s', path)
+            if not os.path.isdir(os.path.dirname(path)):
+                os.makedirs(os.path.dirname(path))
+            with open(path, 'w') as f:
+                f.write(contents)
+            os.chmod(path, mode)
+
+
+class GoModuleDistiller(RegistryDistiller):
+    """A SourceDistiller for Go modules, packaged following dh-make-golang.
+
+    The upstream source is fetched from a Go module proxy.
+    """
+
+    def __init__(self, tree, subpath, upstream_provider, package,
+                 upstream_version, proxy=None, top_level=False,
+                 use_existing=False):
+        from .registry import DEFAULT_GO_PROXY
+        super(GoModuleDistiller, self).__init__(
+            tree, subpath, upstream_provider, package, upstream_version,
+            top_level=top_level, use_existing=use_existing)
+        self.proxy = proxy or DEFAULT_GO_PROXY
+
+    def import_path(self):
+        from .registry import (
+            UnknownGoImportPath,
+            go_import_path_from_package,
+            )
+        control = self._read_control()
+        if control is not None and control.get('XS-Go-Import-Path'):
+            return control['XS-Go-Import-Path'].split(',')[0].strip()
+        import_path = go_import_path_from_package(self.package)
+        if import_path is None:
+            raise UnknownGoImportPath(self.package)
+        return import_path
+
+    def fetch(self, target_dir):
+        from .registry import fetch_go_module
+        return fetch_go_module(
+            self.proxy, self.import_path(), self.package,
+            self.upstream_version, target_dir)
+
+    def skeleton(self, maintainer):
+        ret = super(GoModuleDistiller, self).skeleton(maintainer)
+        ret['rules'] = (GO_RULES, 0o755)
+        ret['control'] = (GO_CONTROL % {
+            'package': self.package, 'maintainer': maintainer,
+            'import_path': self.import_path()}, 0o644)
+        return ret
+
+
+class PyPIDistiller(RegistryDistiller):
+    """A SourceDistiller for Python modules, packaged to build with pybuild.
+
+    The upstream source is the sdist on PyPI.
+    """
+
+    def __init__(self, tree, subpath, upstream_provider, package,
+                 upstream_version, project=None, mirror=None,
+                 top_level=False, use_existing=False):
+        from .registry import DEFAULT_PYPI_MIRROR
+        super(PyPIDistiller, self).__init__(
+            tree, subpath, upstream_provider, package, upstream_version,
+            top_level=top_level, use_existing=use_existing)
+        self._project = project
+        self.mirror = mirror or DEFAULT_PYPI_MIRROR
+
+    def project(self):
+        from .registry import UnknownPyPIProject
+        if self._project is None:
+            raise UnknownPyPIProject(self.package)
+        return self._project
+
+    def fetch(self, target_dir):
+        from .registry import fetch_pypi_sdist
+        return fetch_pypi_sdist(
+            self.mirror, self.project(), self.package, self.upstream_version,
+            target_dir)
+
+    def skeleton(self, maintainer):
+        ret = super(PyPIDistiller, self).skeleton(maintainer)
+        project = self.project()
+        module_package = project.lower().replace('_', '-')
+        ret['rules'] = (PYBUILD_RULES % {
+            'module': module_package.replace('-', '_')}, 0o755)
+        ret['control'] = (PYBUILD_CONTROL % {
+            'package': self.package, 'maintainer': maintainer,
+            'project': project, 'module_package': module_package}, 0o644)
+        return ret
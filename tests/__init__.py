@@ -113,19 +113,27 @@ def make_new_upstream_tarball_lzma(source, dest):
 def load_tests(loader, basic_tests, pattern):
     testmod_names = [
             'blackbox',
+            'test_build_deps',
+            'test_build_failure',
             'test_builder',
             'test_bzrtools_import',
             'test_commit_message',
             'test_config',
             'test_dep3',
+            'test_dgit',
             'test_directory',
             'test_extract',
+            'test_gbp',
             'test_hooks',
             'test_import_dsc',
             'test_merge_changelog',
             'test_merge_package',
             'test_merge_upstream',
+            'test_patch_queue',
+            'test_quilt',
+            'test_registry',
             'test_repack_tarball_extra',
+            'test_reproducible',
             'test_revspec',
             'test_source_distiller',
             'test_upstream',
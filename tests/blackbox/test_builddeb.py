@@ -163,11 +163,26 @@ class TestBuilddeb(BuilddebTestCase):
     os.mkdir('.bzr-builddeb/')
     with open('.bzr-builddeb/default.conf', 'w') as f:
       f.write('[HOOKS]\npre-export = touch pre-export\n')
+      f.write('post-export = touch post-export\n')
       f.write('pre-build = touch pre-build\npost-build = touch post-build\n')
     self.run_bzr('add .bzr-builddeb/default.conf')
+    self.run_bzr(['deb-config', '--layer=user',
+                  'trusted-hook-branches=%s' % tree.branch.user_url])
     self.run_bzr('bd --dont-purge --builder true')
     self.assertPathExists('pre-export')
-    self.assertInBuildDir(['pre-build', 'post-build'])
+    self.assertInBuildDir(['post-export', 'pre-build', 'post-build'])
+
+  def test_untrusted_hooks_refused(self):
+    tree = self.make_unpacked_source()
+    self.make_upstream_tarball()
+    os.mkdir('.bzr-builddeb/')
+    with open('.bzr-builddeb/default.conf', 'w') as f:
+      f.write('[HOOKS]\npre-export = touch pre-export\n')
+    self.run_bzr('add .bzr-builddeb/default.conf')
+    self.run_bzr_error(
+        ['Not running the "pre-export" hook from default.conf, as it is not '
+         'trusted.'], 'bd --dont-purge --builder true')
+    self.assertPathDoesNotExist('pre-export')
 
   def test_utf8_changelog(self):
     from ... import debian_changelog_commit
@@ -0,0 +1,147 @@
+#    test_deb_config.py -- Blackbox tests for deb-config.
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Blackbox tests for "bzr deb-config"."""
+
+from __future__ import absolute_import
+
+from .. import BuilddebTestCase
+
+
+class TestDebConfig(BuilddebTestCase):
+
+    def make_package(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('debian/',),
+            ('debian/bzr-builddeb.conf',
+             '[BUILDDEB]\n'
+             'merge = True\n'
+             'builder = sbuild\n'
+             'buildir = typo\n'),
+            ])
+        tree.smart_add([tree.basedir])
+        tree.commit('Add configuration.')
+        return tree
+
+    def test_list(self):
+        self.make_package()
+        out, err = self.run_bzr('deb-config')
+        self.assertContainsRe(
+            out, r'(?m)^merge = True \(bzr-builddeb.conf \[BUILDDEB\], '
+                 r'untrusted\)$')
+        self.assertContainsRe(out, r'(?m)^build-dir is not set$')
+        self.assertContainsRe(out, r'(?m)^native = False \(default\)$')
+        self.assertContainsRe(
+            err, r"Ignoring builder = sbuild from \[BUILDDEB\] in "
+                 r"bzr-builddeb.conf, as it is not trusted.")
+        self.assertContainsRe(
+            err, r"bzr-builddeb.conf: 'buildir' in \[BUILDDEB\] is not a "
+                 r"known option")
+
+    def test_show_one(self):
+        self.make_package()
+        out, err = self.run_bzr('deb-config merge')
+        self.assertEqual(
+            'merge = True (bzr-builddeb.conf [BUILDDEB], untrusted)\n', out)
+
+    def test_unknown_option(self):
+        self.make_package()
+        self.run_bzr_error(['Unknown option: buildir'], 'deb-config buildir')
+
+    def test_set_and_remove_local(self):
+        tree = self.make_package()
+        self.run_bzr('deb-config build-dir=../build')
+        self.assertFalse(tree.is_versioned('debian/local.conf.local'))
+        out, err = self.run_bzr('deb-config build-dir')
+        self.assertEqual(
+            'build-dir = ../build (local.conf [BUILDDEB], trusted)\n', out)
+        self.run_bzr('deb-config --remove build-dir')
+        out, err = self.run_bzr('deb-config build-dir')
+        self.assertEqual('build-dir is not set\n', out)
+        self.run_bzr_error(
+            ['The "build-dir" configuration option does not exist'],
+            'deb-config --remove build-dir')
+
+    def test_set_package_target(self):
+        tree = self.make_package()
+        self.run_bzr(
+            'deb-config result-dir=../backports --layer=package '
+            '--target=bookworm-backports')
+        self.assertFileEqual(
+            '[BUILDDEB]\n'
+            'merge = True\n'
+            'builder = sbuild\n'
+            'buildir = typo\n'
+            '[BUILDDEB:bookworm-backports]\n'
+            'result-dir = ../backports\n',
+            'debian/bzr-builddeb.conf')
+        out, err = self.run_bzr(
+            'deb-config result-dir --target=bookworm-backports')
+        self.assertEqual(
+            'result-dir = ../backports (bzr-builddeb.conf '
+            '[BUILDDEB:bookworm-backports], untrusted)\n', out)
+
+    def test_set_new_package_conf(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree(['debian/'])
+        tree.add(['debian'])
+        self.run_bzr('deb-config merge=True --layer=package')
+        self.assertTrue(tree.is_versioned('debian/bzr-builddeb.conf'))
+
+    def test_set_trusted_in_package(self):
+        self.make_package()
+        out, err = self.run_bzr(
+            'deb-config quick-builder=true --layer=package')
+        self.assertContainsRe(
+            err, 'quick-builder is only read from trusted files, so it will '
+                 'be ignored in debian/bzr-builddeb.conf.')
+
+    def test_unknown_layer(self):
+        self.make_package()
+        self.run_bzr_error(
+            ['Unknown layer elsewhere, should be one of: local, user, '
+             'package'], 'deb-config build-dir=x --layer=elsewhere')
+
+    def test_show_hook(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('debian/',),
+            ('debian/bzr-builddeb.conf', '[HOOKS]\npre-build = autoconf\n'),
+            ])
+        tree.smart_add([tree.basedir])
+        out, err = self.run_bzr('deb-config pre-build')
+        self.assertEqual(
+            'pre-build hook = autoconf (bzr-builddeb.conf [HOOKS], '
+            'untrusted, refused)\n', out)
+        self.run_bzr('deb-config untrusted-hooks=prompt --layer=user')
+        out, err = self.run_bzr('deb-config')
+        self.assertContainsRe(
+            out, r'(?m)^pre-build hook = autoconf \(bzr-builddeb.conf '
+                 r'\[HOOKS\], untrusted, prompted for\)$')
+        self.run_bzr([
+            'deb-config', '--layer=user',
+            'trusted-hook-branches=%s' % tree.branch.user_url])
+        out, err = self.run_bzr('deb-config pre-build')
+        self.assertEqual(
+            'pre-build hook = autoconf (bzr-builddeb.conf [HOOKS], '
+            'untrusted, allowed)\n', out)
+        out, err = self.run_bzr('deb-config post-build')
+        self.assertEqual('post-build hook is not set\n', out)
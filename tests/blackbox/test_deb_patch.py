@@ -0,0 +1,148 @@
+#    test_deb_patch.py -- Blackbox tests for deb-patch.
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+"""Blackbox tests for "bzr deb-patch"."""
+
+from __future__ import absolute_import
+
+import os
+import tarfile
+
+from .....tests.blackbox import ExternalBase
+from .....tests.features import ExecutableFeature
+
+
+QuiltFeature = ExecutableFeature('quilt')
+
+CHANGELOG = """\
+test (0.1-1) unstable; urgency=low
+
+  * Initial release.
+
+ -- James Westby <jw+debian@jameswestby.net>  Thu,  3 Aug 2006 19:16:22 +0100
+"""
+
+CHANGE_A_PATCH = """\
+Description: Change a.
+--- a/a
++++ b/a
+@@ -1 +1 @@
+-a
++b
+"""
+
+
+class TestDebPatch(ExternalBase):
+
+    def setUp(self):
+        super(TestDebPatch, self).setUp()
+        self.requireFeature(QuiltFeature)
+
+    def make_full_source(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('a', 'a\n'),
+            ('debian/',),
+            ('debian/changelog', CHANGELOG),
+            ('debian/source/',),
+            ('debian/source/format', '3.0 (quilt)\n'),
+            ('debian/patches/',),
+            ('debian/patches/series', 'change-a.patch\n'),
+            ('debian/patches/change-a.patch', CHANGE_A_PATCH),
+            ])
+        tree.smart_add([tree.basedir])
+        tree.commit('Initial packaging.')
+        return tree
+
+    def make_merge_mode(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('debian/',),
+            ('debian/changelog', CHANGELOG),
+            ('debian/source/',),
+            ('debian/source/format', '3.0 (quilt)\n'),
+            ('debian/patches/',),
+            ('debian/patches/series', 'change-a.patch\n'),
+            ('debian/patches/change-a.patch', CHANGE_A_PATCH),
+            ('.bzr-builddeb/',),
+            ('.bzr-builddeb/default.conf', '[BUILDDEB]\nmerge = True\n'),
+            ('test-0.1/',),
+            ('test-0.1/a', 'a\n'),
+            ])
+        with tarfile.open(
+                os.path.join('..', 'test_0.1.orig.tar.gz'), 'w:gz') as tar:
+            tar.add('test-0.1')
+        tree.smart_add(['debian', '.bzr-builddeb'])
+        tree.commit('Initial packaging.')
+        return tree
+
+    def test_registered(self):
+        self.run_bzr("deb-patch --help")
+
+    def test_unknown_action(self):
+        self.make_full_source()
+        self.run_bzr_error(['Unknown action foo'], 'deb-patch foo')
+
+    def test_full_source_push_and_pop(self):
+        self.make_full_source()
+        self.run_bzr('deb-patch push')
+        self.assertFileEqual('b\n', 'a')
+        out, err = self.run_bzr('deb-patch list')
+        self.assertContainsRe(out, r'= debian/patches/change-a.patch')
+        self.run_bzr('deb-patch pop')
+        self.assertFileEqual('a\n', 'a')
+
+    def test_full_source_new_patch(self):
+        tree = self.make_full_source()
+        self.run_bzr('deb-patch push')
+        self.run_bzr('deb-patch new add-c.patch')
+        self.assertFileEqual(
+            'change-a.patch\nadd-c.patch\n', 'debian/patches/series')
+        self.assertTrue(tree.is_versioned('debian/patches/series'))
+
+    def test_full_source_drop(self):
+        tree = self.make_full_source()
+        self.run_bzr('deb-patch drop change-a.patch')
+        self.assertPathDoesNotExist('debian/patches/change-a.patch')
+        self.assertFalse(tree.is_versioned('debian/patches/change-a.patch'))
+
+    def test_merge_mode_refresh(self):
+        self.make_merge_mode()
+        self.run_bzr('deb-patch push')
+        source_dir = os.path.join('..', 'build-area', 'test-0.1')
+        self.assertFileEqual('b\n', os.path.join(source_dir, 'a'))
+        self.build_tree_contents([(os.path.join(source_dir, 'a'), 'c\n')])
+        self.run_bzr('deb-patch refresh')
+        with open('debian/patches/change-a.patch') as f:
+            patch = f.read()
+        self.assertContainsRe(patch, r'^Description: Change a.\n')
+        self.assertContainsRe(patch, r'\n\+c\n')
+
+    def test_merge_mode_failed_refresh_not_copied(self):
+        self.make_merge_mode()
+        self.run_bzr('deb-patch push')
+        source_dir = os.path.join('..', 'build-area', 'test-0.1')
+        self.build_tree_contents([(os.path.join(source_dir, 'a'), 'c\n')])
+        self.run_bzr_error(
+            ['quilt refresh missing.patch failed; not updating '
+             'debian/patches.'],
+            'deb-patch refresh missing.patch')
+        self.assertFileEqual(
+            CHANGE_A_PATCH, 'debian/patches/change-a.patch')
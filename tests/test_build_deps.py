@@ -0,0 +1,184 @@
+#    test_build_deps.py -- Tests for build_deps.py
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+from debian.deb822 import PkgRelation
+
+from ....tests import (
+    TestCase,
+    TestCaseInTempDir,
+    )
+
+from ..build_deps import (
+    MissingBuildDependencies,
+    UnknownBuildDepsMode,
+    build_depends_fields,
+    get_build_dependencies,
+    get_install_command,
+    handle_build_dependencies,
+    relation_applies,
+    unsatisfied_build_dependencies,
+    )
+
+
+CONTROL = """\
+Source: pkg
+Build-Depends: debhelper-compat (= 13),
+ libfoo-dev [amd64],
+ libbar-dev [!amd64],
+ python3-pytest <!nocheck>,
+ gcc-mingw-w64 [linux-any] | mingw
+Build-Depends-Indep: python3-sphinx
+
+Package: pkg
+Architecture: any
+"""
+
+
+class RelationAppliesTests(TestCase):
+
+    def applies(self, text, host_arch='amd64', profiles=()):
+        return relation_applies(
+            PkgRelation.parse_relations(text)[0][0], host_arch, profiles)
+
+    def test_no_restrictions(self):
+        self.assertTrue(self.applies('foo'))
+
+    def test_arch(self):
+        self.assertTrue(self.applies('foo [amd64 i386]'))
+        self.assertFalse(self.applies('foo [arm64]'))
+        self.assertFalse(self.applies('foo [!amd64]'))
+        self.assertTrue(self.applies('foo [linux-any]'))
+        self.assertTrue(self.applies('foo [any-amd64]'))
+        self.assertFalse(self.applies('foo [hurd-any]'))
+
+    def test_profiles(self):
+        self.assertTrue(self.applies('foo <!nocheck>'))
+        self.assertFalse(self.applies('foo <!nocheck>', profiles=['nocheck']))
+        self.assertFalse(self.applies('foo <cross>'))
+        self.assertTrue(self.applies('foo <cross> <stage1>', profiles=[
+            'stage1']))
+
+
+class GetBuildDependenciesTests(TestCaseInTempDir):
+
+    def test_amd64(self):
+        self.build_tree_contents([('control', CONTROL)])
+        self.assertEqual(
+            ['debhelper-compat (= 13)', 'libfoo-dev', 'python3-pytest',
+             'gcc-mingw-w64 | mingw', 'python3-sphinx'],
+            [PkgRelation.str([alternatives]) for alternatives in
+             get_build_dependencies('control', 'amd64')])
+
+    def test_nocheck_arm64(self):
+        self.build_tree_contents([('control', CONTROL)])
+        self.assertEqual(
+            ['debhelper-compat (= 13)', 'libbar-dev',
+             'gcc-mingw-w64 | mingw', 'python3-sphinx'],
+            [PkgRelation.str([alternatives]) for alternatives in
+             get_build_dependencies('control', 'arm64', ['nocheck'])])
+
+    def test_fields(self):
+        self.build_tree_contents([('control', CONTROL)])
+        self.assertEqual(
+            ['debhelper-compat (= 13)', 'libfoo-dev', 'python3-pytest',
+             'gcc-mingw-w64 | mingw'],
+            [PkgRelation.str([alternatives]) for alternatives in
+             get_build_dependencies(
+                 'control', 'amd64',
+                 fields=['Build-Depends', 'Build-Depends-Arch'])])
+
+
+class BuildDependsFieldsTests(TestCase):
+
+    def test_default(self):
+        self.assertEqual(
+            ['Build-Depends', 'Build-Depends-Arch', 'Build-Depends-Indep'],
+            build_depends_fields([]))
+
+    def test_arch_only(self):
+        self.assertEqual(
+            ['Build-Depends', 'Build-Depends-Arch'],
+            build_depends_fields(['-B']))
+        self.assertEqual(
+            ['Build-Depends', 'Build-Depends-Arch'],
+            build_depends_fields(['--build=any']))
+
+    def test_indep_only(self):
+        self.assertEqual(
+            ['Build-Depends', 'Build-Depends-Indep'],
+            build_depends_fields(['-us', '-A']))
+        self.assertEqual(
+            ['Build-Depends', 'Build-Depends-Indep'],
+            build_depends_fields(['--build=source,all']))
+
+
+class UnsatisfiedBuildDependenciesTests(TestCase):
+
+    def test_unsatisfied(self):
+        dependencies = PkgRelation.parse_relations(
+            'debhelper-compat (= 13), libfoo-dev (>= 2.0), '
+            'python3-foo | python3-bar, perl')
+        installed = {
+            'debhelper-compat': [None, '13'],
+            'libfoo-dev': ['1.5-1'],
+            'python3-bar': ['1.0'],
+            }
+        self.assertEqual(
+            ['libfoo-dev (>= 2.0)', 'perl'],
+            unsatisfied_build_dependencies(dependencies, installed))
+
+    def test_unversioned_provides(self):
+        dependencies = PkgRelation.parse_relations('foo (>= 1.0)')
+        self.assertEqual(
+            ['foo (>= 1.0)'],
+            unsatisfied_build_dependencies(dependencies, {'foo': [None]}))
+
+
+class GetInstallCommandTests(TestCase):
+
+    def test_custom(self):
+        self.assertEqual(
+            "my-installer libfoo-dev 'libbar-dev (>= 1.0)'",
+            get_install_command(
+                'my-installer', ['libfoo-dev', 'libbar-dev (>= 1.0)']))
+
+    def test_apt(self):
+        self.assertTrue(get_install_command('apt', ['libfoo-dev']).endswith(
+            "apt-get satisfy --yes --no-install-recommends libfoo-dev"))
+
+
+class HandleBuildDependenciesTests(TestCaseInTempDir):
+
+    def test_unknown_mode(self):
+        self.assertRaises(
+            UnknownBuildDepsMode, handle_build_dependencies, '.', 'ignore',
+            'amd64')
+
+    def test_check_missing(self):
+        self.build_tree_contents([
+            ('debian/',),
+            ('debian/control',
+             'Source: pkg\nBuild-Depends: a-package-that-does-not-exist\n')])
+        e = self.assertRaises(
+            MissingBuildDependencies, handle_build_dependencies, '.',
+            'check', 'amd64')
+        self.assertEqual(['a-package-that-does-not-exist'], e.dependencies)
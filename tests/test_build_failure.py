@@ -0,0 +1,133 @@
+#    test_build_failure.py -- Tests for build failure classification
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+from ....tests import TestCase
+
+from ..build_failure import (
+    BuildFailure,
+    FAILURE_MISSING_BUILD_DEPENDENCIES,
+    FAILURE_MISSING_FILES,
+    FAILURE_MISSING_UPSTREAM_TARBALL,
+    FAILURE_PATCH_APPLICATION,
+    FAILURE_TEST_SUITE,
+    FAILURE_UNREPRESENTABLE_CHANGES,
+    analyse_build_log,
+    parse_unmet_dependencies,
+    )
+
+
+class ParseUnmetDependenciesTests(TestCase):
+
+    def test_simple(self):
+        self.assertEqual(['foo', 'bar'], parse_unmet_dependencies('foo bar'))
+
+    def test_complex(self):
+        self.assertEqual(
+            ['debhelper-compat (= 13)', 'libfoo-dev (>= 1.2)',
+             'python3-bar | python3-baz', 'foo [amd64]', 'bar <!nocheck>'],
+            parse_unmet_dependencies(
+                'debhelper-compat (= 13) libfoo-dev (>= 1.2) '
+                'python3-bar | python3-baz foo [amd64] bar <!nocheck>'))
+
+
+class BuildFailureTests(TestCase):
+
+    def test_eq(self):
+        self.assertEqual(
+            BuildFailure('kind', {'a': 1}, 'hint'),
+            BuildFailure('kind', {'a': 1}, 'hint'))
+        self.assertNotEqual(
+            BuildFailure('kind', {'a': 1}, 'hint'),
+            BuildFailure('kind', {'a': 2}, 'hint'))
+        self.assertNotEqual(
+            BuildFailure('kind', {'a': 1}, 'hint'),
+            BuildFailure('kind', {'a': 1}, 'other hint'))
+
+    def test_unhashable(self):
+        self.assertRaises(TypeError, hash, BuildFailure('kind'))
+
+
+class AnalyseBuildLogTests(TestCase):
+
+    def assertFailure(self, kind, details, lines):
+        failure = analyse_build_log(lines)
+        self.assertIsNot(None, failure)
+        self.assertEqual(
+            BuildFailure(kind, details, failure.hint), failure)
+        self.assertTrue(failure.hint)
+
+    def test_unknown(self):
+        self.assertIs(None, analyse_build_log(['all good\n']))
+
+    def test_missing_build_dependencies(self):
+        self.assertFailure(
+            FAILURE_MISSING_BUILD_DEPENDENCIES,
+            {'dependencies': ['debhelper-compat (= 13)', 'libfoo-dev']},
+            ['dpkg-checkbuilddeps: error: Unmet build dependencies: '
+             'debhelper-compat (= 13) libfoo-dev\n'])
+
+    def test_dpkg_source_patch(self):
+        self.assertFailure(
+            FAILURE_PATCH_APPLICATION, {'patch': 'fix-build.patch'},
+            ['dpkg-source: info: applying fix-build.patch\n',
+             'Hunk #1 FAILED at 3.\n',
+             'dpkg-source: error: LC_ALL=C patch -t -F 0 -N -p1 -u -V never '
+             '-E -b -B .pc/fix-build.patch/ --reject-file=- < '
+             'pkg.orig.x/debian/patches/fix-build.patch subprocess returned '
+             'exit status 1\n'])
+
+    def test_quilt_patch(self):
+        self.assertFailure(
+            FAILURE_PATCH_APPLICATION, {'patch': 'debian/patches/foo'},
+            ['Patch debian/patches/foo does not apply (enforce with -f)\n'])
+
+    def test_dh_missing(self):
+        self.assertFailure(
+            FAILURE_MISSING_FILES, {'files': ['usr/bin/foo']},
+            ['dh_missing: warning: usr/bin/foo exists in debian/tmp but is '
+             'not installed to anywhere\n',
+             'dh_missing: error: missing files, aborting\n'])
+
+    def test_dh_install(self):
+        self.assertFailure(
+            FAILURE_MISSING_FILES, {'files': ['usr/bin/foo']},
+            ['dh_install: error: missing files: usr/bin/foo\n'])
+
+    def test_test_suite(self):
+        self.assertFailure(
+            FAILURE_TEST_SUITE, {'command': 'make -j4 test'},
+            ['dh_auto_test: error: make -j4 test returned exit code 2\n'])
+
+    def test_unrepresentable_change(self):
+        self.assertFailure(
+            FAILURE_UNREPRESENTABLE_CHANGES,
+            {'path': 'foo.png', 'reason': 'binary file contents changed'},
+            ['dpkg-source: error: cannot represent change to foo.png: '
+             'binary file contents changed\n'])
+
+    def test_missing_upstream_tarball(self):
+        self.assertFailure(
+            FAILURE_MISSING_UPSTREAM_TARBALL,
+            {'path': '../foo_1.0.orig.tar.{bz2,gz,lzma,xz}'},
+            ["dpkg-source: error: can't build with source format "
+             "'3.0 (quilt)': no upstream tarball found at "
+             "../foo_1.0.orig.tar.{bz2,gz,lzma,xz}\n"])
@@ -20,15 +20,40 @@
 
 from __future__ import absolute_import
 
+import json
 import os
+import subprocess
+
+from debian.changelog import Version
 
 from ....tests import TestCaseInTempDir
 
+from .. import hooks
 from ..builder import (
+    CommandBuilder,
+    CowbuilderBuilder,
     DebBuild,
+    DebuildBuilder,
+    DockerBuilder,
     BuildFailedError,
+    BuildReport,
+    ConflictingArchitectures,
     NoSourceDirError,
+    PbuilderBuilder,
+    PodmanBuilder,
+    SbuildBuilder,
+    UnsupportedBuilderOption,
+    build_architectures,
+    do_build,
+    get_builder,
     )
+from ..config import DebBuildConfig
+
+
+class MkdirDistiller(object):
+
+    def distill(self, target):
+        os.mkdir(target)
 
 
 class TestDebBuild(TestCaseInTempDir):
@@ -59,9 +84,6 @@ class TestDebBuild(TestCaseInTempDir):
         self.assertPathDoesNotExist('target/sub')
 
     def test_export(self):
-        class MkdirDistiller(object):
-            def distill(self, target):
-                os.mkdir(target)
         builder = DebBuild(MkdirDistiller(), 'target', None)
         builder.export()
         self.assertPathExists('target')
@@ -72,13 +94,434 @@ class TestDebBuild(TestCaseInTempDir):
         builder.build()
         self.assertPathExists('target/built')
 
+    def test_export_hooks(self):
+        calls = []
+        hooks.builddeb_hooks.install_named_hook(
+            'pre_distill',
+            lambda params: calls.append(('pre', params.target_dir)), 'pre')
+        hooks.builddeb_hooks.install_named_hook(
+            'post_distill',
+            lambda params: calls.append(('post', params.tarballs)), 'post')
+        builder = DebBuild(MkdirDistiller(), 'target', None)
+        builder.export()
+        self.assertEqual([('pre', 'target'), ('post', [])], calls)
+
+    def test_build_hooks(self):
+        calls = []
+
+        def pre_build(params):
+            calls.append(('pre', params.source_dir))
+            params.environment['EXTRA'] = 'value'
+        hooks.builddeb_hooks.install_named_hook('pre_build', pre_build, 'pre')
+        hooks.builddeb_hooks.install_named_hook(
+            'post_build',
+            lambda params: calls.append(('post', params.result_dir)), 'post')
+        builder = DebBuild(None, 'target', 'echo $EXTRA > built')
+        self.build_tree(['target/'])
+        builder.build()
+        self.assertEqual([('pre', 'target'), ('post', '.')], calls)
+        self.assertFileEqual(b'value\n', 'target/built')
+
     def test_build_fails(self):
         builder = DebBuild(None, 'target', "false")
         self.build_tree(['target/'])
         self.assertRaises(BuildFailedError, builder.build)
 
+    def test_build_writes_log(self):
+        builder = DebBuild(
+            None, 'target', "echo building", log_path='test.build')
+        self.build_tree(['target/'])
+        builder.build()
+        self.assertFileEqual(b'building\n', 'test.build')
+
+    def test_build_fails_with_log(self):
+        builder = DebBuild(
+            None, 'target', "echo something broke; false",
+            log_path='test.build')
+        self.build_tree(['target/'])
+        e = self.assertRaises(BuildFailedError, builder.build)
+        self.assertEqual('test.build', e.log_path)
+        self.assertEqual([b'something broke\n'], e.tail)
+        self.assertIs(None, e.failure)
+        self.assertContainsRe(str(e), 'something broke')
+
+    def test_build_fails_analysed(self):
+        builder = DebBuild(
+            None, 'target',
+            "echo 'dh_auto_test: error: make check returned exit code 2'; "
+            "false", log_path='test.build')
+        self.build_tree(['target/'])
+        e = self.assertRaises(BuildFailedError, builder.build)
+        self.assertEqual('test-suite-failed', e.failure.kind)
+        self.assertContainsRe(str(e), 'test-suite-failed')
+
     def test_clean(self):
         builder = DebBuild(None, 'target', None)
         self.build_tree(['target/', 'target/foo'])
         builder.clean()
         self.assertPathDoesNotExist('target')
+
+    def test_build_architecture_substituted(self):
+        builder = DebBuild(
+            None, 'target', "touch built-$ARCH", architecture='arm64')
+        self.build_tree(['target/'])
+        builder.build()
+        self.assertPathExists('target/built-arm64')
+
+    def test_build_architecture_appended(self):
+        builder = DebBuild(None, 'target', "debuild", architecture='arm64')
+        self.assertEqual("debuild -aarm64", builder._get_build_command())
+
+    def test_build_architecture_unknown_command(self):
+        builder = DebBuild(
+            None, 'target', "fakeroot debian/rules binary",
+            architecture='arm64')
+        self.assertRaises(
+            UnsupportedBuilderOption, builder._get_build_command)
+
+    def test_result_dir(self):
+        builder = DebBuild(None, 'build/pkg-0.1', "debuild")
+        self.assertEqual('build', builder.result_dir)
+
+
+class TestBuilders(TestCaseInTempDir):
+
+    def test_get_builder_by_name(self):
+        self.assertIsInstance(get_builder('sbuild'), SbuildBuilder)
+
+    def test_get_builder_command(self):
+        builder = get_builder(
+            'fakeroot debian/rules binary', extra_args=['-v'])
+        self.assertIsInstance(builder, CommandBuilder)
+        self.assertEqual(
+            'fakeroot debian/rules binary -v', builder.get_command('pkg'))
+
+    def test_get_builder_from_config(self):
+        with open('user.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'builder-distribution = unstable\n'
+                    'builder-architecture = arm64\n'
+                    'builder-run-tests = False\n')
+        builder = get_builder('sbuild', DebBuildConfig([('user.conf', True)]))
+        self.assertEqual('unstable', builder.distribution)
+        self.assertEqual('arm64', builder.architecture)
+        self.assertFalse(builder.run_tests)
+
+    def test_debuild(self):
+        builder = DebuildBuilder(architecture='arm64', extra_args=['-S'])
+        self.assertEqual('debuild -aarm64 -S', builder.get_command('pkg'))
+        self.assertEqual({}, builder.get_environment())
+
+    def test_debuild_profiles(self):
+        self.overrideEnv('DEB_BUILD_OPTIONS', None)
+        builder = DebuildBuilder(
+            profiles=['nodoc'], build_options=['parallel=4'])
+        self.assertEqual(
+            "debuild '--set-envvar=DEB_BUILD_OPTIONS=parallel=4 nodoc' "
+            "-Pnodoc", builder.get_command('pkg'))
+
+    def test_get_builder_overrides_config(self):
+        with open('user.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'build-profiles = nocheck, nodoc\n'
+                    'build-options = parallel=2\n')
+        config = DebBuildConfig([('user.conf', True)])
+        builder = get_builder('sbuild', config)
+        self.assertEqual(['nocheck', 'nodoc'], builder.profiles)
+        self.assertEqual(['parallel=2'], builder.build_options)
+        builder = get_builder('sbuild', config, profiles=['stage1'])
+        self.assertEqual(['stage1'], builder.profiles)
+        self.assertEqual(['parallel=2'], builder.build_options)
+
+    def test_profiles_environment(self):
+        self.overrideEnv('DEB_BUILD_OPTIONS', 'parallel=8')
+        self.overrideEnv('DEB_BUILD_PROFILES', None)
+        builder = CommandBuilder(
+            'debian/rules binary', profiles=['nocheck'],
+            build_options=['noopt'])
+        self.assertEqual({
+            'DEB_BUILD_OPTIONS': 'parallel=8 noopt nocheck',
+            'DEB_BUILD_PROFILES': 'nocheck'}, builder.get_environment())
+
+    def test_sbuild(self):
+        builder = SbuildBuilder(
+            distribution='unstable', architecture='arm64',
+            extra_repositories=['deb http://example.com/ unstable main'],
+            run_tests=False)
+        self.assertEqual(
+            "sbuild --build-dir=%s --dist=unstable --arch=arm64 "
+            "'--extra-repository=deb http://example.com/ unstable main' "
+            "--profiles=nocheck" % os.path.abspath('build'),
+            builder.get_command('build/pkg-0.1'))
+        self.assertIn(
+            'nocheck',
+            builder.get_environment()['DEB_BUILD_OPTIONS'].split())
+
+    def test_dpkg_buildpackage_host_arch(self):
+        builder = get_builder('dpkg-buildpackage', host_architecture='armhf')
+        self.assertEqual(
+            "dpkg-buildpackage --host-arch=armhf -Pcross,nocheck",
+            builder.get_command('pkg'))
+
+    def test_dpkg_buildpackage_architecture_and_host_arch(self):
+        builder = get_builder(
+            'dpkg-buildpackage', architecture='armhf',
+            host_architecture='armhf')
+        self.assertEqual(
+            "dpkg-buildpackage -aarmhf -Pcross,nocheck",
+            builder.get_command('pkg'))
+        builder = get_builder(
+            'dpkg-buildpackage', host_architecture='armhf')
+        self.assertRaises(
+            ConflictingArchitectures, builder.get_command, 'pkg', 'arm64')
+
+    def test_command_architecture_and_host_arch(self):
+        builder = CommandBuilder('debuild', host_architecture='armhf')
+        self.assertEqual(
+            'debuild -aarmhf', builder.get_command('pkg', 'armhf'))
+        self.assertRaises(
+            ConflictingArchitectures, builder.get_command, 'pkg', 'arm64')
+
+    def test_sbuild_host_arch(self):
+        builder = SbuildBuilder(host_architecture='arm64')
+        self.assertEqual(
+            "sbuild --build-dir=%s --host=arm64 --profiles=cross,nocheck" %
+            os.path.abspath('build'), builder.get_command('build/pkg-0.1'))
+
+    def test_command_host_arch(self):
+        builder = CommandBuilder(
+            'cross-build --for $HOST_ARCH', host_architecture='armhf')
+        self.assertEqual(
+            'cross-build --for armhf', builder.get_command('pkg'))
+
+    def test_command_host_arch_appended(self):
+        builder = CommandBuilder('sbuild -v', host_architecture='armhf')
+        self.assertEqual('sbuild -v --host=armhf', builder.get_command('pkg'))
+        builder = CommandBuilder(
+            'fakeroot debian/rules binary', host_architecture='armhf')
+        self.assertRaises(
+            UnsupportedBuilderOption, builder.get_command, 'pkg')
+
+    def test_sbuild_architecture_override(self):
+        builder = SbuildBuilder(architecture='arm64')
+        self.assertEqual(
+            "sbuild --build-dir=%s --arch=armhf" % os.path.abspath('build'),
+            builder.get_command('build/pkg-0.1', 'armhf'))
+
+    def test_pbuilder(self):
+        builder = PbuilderBuilder(distribution='bookworm', architecture='i386')
+        self.assertEqual(
+            "pdebuild --buildresult %s -- --architecture i386" %
+            os.path.abspath('build'),
+            builder.get_command('build/pkg-0.1'))
+        self.assertEqual({'DIST': 'bookworm'}, builder.get_environment())
+
+    def test_pbuilder_profiles(self):
+        builder = PbuilderBuilder(profiles=['nocheck', 'nodoc'])
+        self.assertEqual(
+            "pdebuild --buildresult %s -- --profiles nocheck,nodoc" %
+            os.path.abspath('build'),
+            builder.get_command('build/pkg-0.1'))
+
+    def test_cowbuilder(self):
+        builder = CowbuilderBuilder()
+        self.assertEqual(
+            "pdebuild --pbuilder cowbuilder --buildresult %s" %
+            os.path.abspath('build'),
+            builder.get_command('build/pkg-0.1'))
+
+    def test_podman(self):
+        self.overrideEnv('DEB_BUILD_OPTIONS', None)
+        self.overrideEnv('DEB_BUILD_PROFILES', None)
+        builder = PodmanBuilder(distribution='bookworm', run_tests=False)
+        command = builder.get_command('build/pkg-0.1')
+        self.assertTrue(command.startswith(
+            "podman run --rm --volume=%s:/build "
+            "--env=DEB_BUILD_OPTIONS=nocheck --env=DEB_BUILD_PROFILES=nocheck "
+            "debian:bookworm sh -c " %
+            os.path.abspath('build')), command)
+        script = builder.get_script('build/pkg-0.1', None).splitlines()
+        self.assertIn("cp -a /build/pkg-0.1 /home/builder/build/", script)
+        self.assertIn("apt-get build-dep -y ./", script)
+        self.assertIn(
+            "runuser -u builder -- dpkg-buildpackage -us -uc", script)
+        self.assertNotIn("chown", script[-1])
+
+    def test_container_copies_back_results(self):
+        builder = PodmanBuilder(distribution='bookworm')
+        copy = builder.get_script('build/pkg-0.1', None).splitlines()[-1]
+        self.build_tree_contents([
+            ('container/',), ('container/pkg-0.1/',),
+            ('container/pkg_0.1-1_all.deb', b'new'),
+            ('container/pkg_0.1.orig.tar.gz', b'rebuilt'),
+            ('result/',),
+            ('result/pkg_0.1-1_all.deb', b'old'),
+            ('result/pkg_0.1.orig.tar.gz', b'original'),
+            ])
+        subprocess.check_call([
+            'sh', '-c',
+            copy.replace('/home/builder/build', 'container').replace(
+                '/build/', '../result/')])
+        self.assertFileEqual(b'new', 'result/pkg_0.1-1_all.deb')
+        self.assertFileEqual(b'original', 'result/pkg_0.1.orig.tar.gz')
+        self.assertPathDoesNotExist('result/pkg-0.1')
+
+    def test_docker_from_config(self):
+        with open('user.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'container-image = example/debian-dev:latest\n'
+                    'container-setup = "echo one", "echo two"\n')
+        builder = get_builder('docker', DebBuildConfig([('user.conf', True)]))
+        self.assertIsInstance(builder, DockerBuilder)
+        self.assertEqual('example/debian-dev:latest', builder.image)
+        self.assertEqual(['echo one', 'echo two'], builder.setup_commands)
+        script = builder.get_script('build/pkg-0.1', 'arm64').splitlines()
+        self.assertIn("dpkg --add-architecture arm64", script)
+        self.assertIn("echo two", script)
+        self.assertIn("apt-get build-dep -y -a arm64 ./", script)
+        self.assertIn(
+            'chown %d:%d "/build/$f"' % (os.getuid(), os.getgid()),
+            script[-1])
+
+
+class TestBuildArchitectures(TestCaseInTempDir):
+
+    def test_builds_each_architecture(self):
+        self.build_tree(['build/', 'build/pkg-0.1/', 'build/pkg-0.1/a'])
+        succeeded, failed = build_architectures(
+            'build/pkg-0.1', 'build',
+            "touch ../pkg_0.1-1_$ARCH.changes", ['amd64', 'arm64'],
+            'pkg', Version('0.1-1'))
+        self.assertEqual([], failed)
+        self.assertEqual(
+            {'amd64': 'build/amd64/pkg_0.1-1_amd64.changes',
+             'arm64': 'build/arm64/pkg_0.1-1_arm64.changes'},
+            succeeded)
+        self.assertPathExists('build/amd64/pkg-0.1/a')
+        self.assertPathExists('build/arm64/pkg-0.1/a')
+
+    def test_reports_failed_architectures(self):
+        self.build_tree(['build/', 'build/pkg-0.1/'])
+        succeeded, failed = build_architectures(
+            'build/pkg-0.1', 'build',
+            'test "$ARCH" = arm64 && touch ../pkg_0.1-1_$ARCH.changes',
+            ['amd64', 'arm64'], 'pkg', Version('0.1-1'))
+        self.assertEqual(['amd64'], failed)
+        self.assertEqual(['arm64'], list(succeeded))
+
+    def test_copies_upstream_tarballs(self):
+        self.build_tree_contents([
+            ('build/',), ('build/pkg-0.1/',),
+            ('build/pkg-0.1/debian/',),
+            ('build/pkg-0.1/debian/source/',),
+            ('build/pkg-0.1/debian/source/format', b'3.0 (quilt)\n'),
+            ('build/pkg_0.1.orig.tar.gz', b'upstream'),
+            ])
+        succeeded, failed = build_architectures(
+            'build/pkg-0.1', 'build',
+            "test -f ../pkg_0.1.orig.tar.gz && "
+            "touch ../pkg_0.1-1_$ARCH.changes", ['amd64', 'arm64'],
+            'pkg', Version('0.1-1'))
+        self.assertEqual([], failed)
+        self.assertFileEqual(b'upstream', 'build/amd64/pkg_0.1.orig.tar.gz')
+        self.assertFileEqual(b'upstream', 'build/arm64/pkg_0.1.orig.tar.gz')
+
+    def test_builds_concurrently(self):
+        self.build_tree(['build/', 'build/pkg-0.1/'])
+        # Each build waits for the other one to have started.
+        succeeded, failed = build_architectures(
+            'build/pkg-0.1', 'build',
+            "touch ../../started-$ARCH; for i in $(seq 100); do "
+            "[ -e ../../started-amd64 ] && [ -e ../../started-arm64 ] && "
+            "touch ../pkg_0.1-1_$ARCH.changes && exit 0; sleep 0.1; done; "
+            "exit 1", ['amd64', 'arm64'], 'pkg', Version('0.1-1'))
+        self.assertEqual([], failed)
+
+    def test_unsupported_architecture(self):
+        self.build_tree(['build/', 'build/pkg-0.1/'])
+        self.assertRaises(
+            UnsupportedBuilderOption, build_architectures,
+            'build/pkg-0.1', 'build', "fakeroot debian/rules binary",
+            ['amd64', 'arm64'], 'pkg', Version('0.1-1'))
+
+
+class MockTree(object):
+
+    def abspath(self, relpath):
+        return os.path.abspath(relpath)
+
+
+class TestBuildReport(TestCaseInTempDir):
+
+    def test_as_dict_defaults(self):
+        report = BuildReport('pkg', Version('0.1-1'))
+        self.assertEqual({
+            'package': 'pkg',
+            'version': '0.1-1',
+            'build-type': None,
+            'distiller': None,
+            'builder': None,
+            'profiles': [],
+            'build-options': [],
+            'start-time': None,
+            'end-time': None,
+            'exit-status': None,
+            'build-log': None,
+            'upstream-tarballs': [],
+            'artifacts': [],
+            }, report.as_dict())
+
+    def test_do_build_writes_report(self):
+        os.mkdir('result')
+        build_command = (
+            "printf 'Files:\\n 0123 4 misc optional pkg_0.1-1_all.deb\\n' "
+            "> ../pkg_0.1-1_all.changes && printf 'deb\\n' "
+            "> ../pkg_0.1-1_all.deb")
+        changes_path = do_build(
+            'pkg', Version('0.1-1'), MkdirDistiller(), MockTree(),
+            DebBuildConfig([]), build_command, target_dir='result',
+            build_type='native', report=True)
+        self.assertEqual('result/pkg_0.1-1_all.changes', changes_path)
+        with open('result/pkg_0.1-1_all.build-report.json') as f:
+            report = json.load(f)
+        self.assertEqual('pkg', report['package'])
+        self.assertEqual('native', report['build-type'])
+        self.assertEqual('MkdirDistiller', report['distiller'])
+        self.assertEqual(0, report['exit-status'])
+        self.assertEqual(
+            ['pkg_0.1-1_all.changes', 'pkg_0.1-1_all.deb'],
+            [artifact['filename'] for artifact in report['artifacts']])
+
+    def test_do_build_report_records_all_changes_files(self):
+        os.mkdir('result')
+        build_command = (
+            "printf 'Files:\\n 0123 4 misc optional pkg_0.1-1_all.deb\\n' "
+            "> ../pkg_0.1-1_all.changes && printf 'deb\\n' "
+            "> ../pkg_0.1-1_all.deb && "
+            "printf 'Files:\\n 0123 4 misc optional pkg_0.1-1_all.deb\\n"
+            " 4567 4 misc optional pkg_0.1-1.dsc\\n' "
+            "> ../pkg_0.1-1_source.changes && printf 'dsc\\n' "
+            "> ../pkg_0.1-1.dsc")
+        do_build(
+            'pkg', Version('0.1-1'), MkdirDistiller(), MockTree(),
+            DebBuildConfig([]), build_command, target_dir='result',
+            report=True)
+        reports = [name for name in os.listdir('result')
+                   if name.endswith('.build-report.json')]
+        self.assertEqual(1, len(reports))
+        with open(os.path.join('result', reports[0])) as f:
+            report = json.load(f)
+        self.assertEqual(
+            ['pkg_0.1-1.dsc', 'pkg_0.1-1_all.changes', 'pkg_0.1-1_all.deb',
+             'pkg_0.1-1_source.changes'],
+            sorted(artifact['filename'] for artifact in report['artifacts']))
+
+    def test_do_build_failure_writes_report(self):
+        os.mkdir('result')
+        self.assertRaises(
+            BuildFailedError, do_build, 'pkg', Version('0.1-1'),
+            MkdirDistiller(), MockTree(), DebBuildConfig([]), 'exit 3',
+            target_dir='result', report=True)
+        with open('result/pkg_0.1-1.build-report.json') as f:
+            report = json.load(f)
+        self.assertEqual(3, report['exit-status'])
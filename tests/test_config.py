@@ -22,10 +22,17 @@ from __future__ import absolute_import
 
 from ....branch import Branch
 
+from ....config import NoSuchConfigOption
 from ..config import (
     BUILD_TYPE_MERGE,
+    ConfigValue,
+    ConfigValueError,
     DebBuildConfig,
+    KNOWN_OPTIONS,
     UpstreamMetadataSyntaxError,
+    describe_options,
+    remove_config_option,
+    set_config_option,
     )
 from . import TestCaseWithTransport
 
@@ -43,6 +50,7 @@ class DebBuildConfigTests(TestCaseWithTransport):
             f.write('build-dir = default build dir\n')
             f.write('orig-dir = default orig dir\n')
             f.write('result-dir = default result dir\n')
+            f.write('go-proxy = http://proxy.example.com/\n')
         with open('user.conf', 'w') as f:
             f.write('['+DebBuildConfig.section+']\n')
             f.write('builder = valid builder\n')
@@ -61,6 +69,12 @@ class DebBuildConfigTests(TestCaseWithTransport):
     def test_secure_not_from_untrusted(self):
         self.assertEqual(self.config.builder, 'valid builder')
 
+    def test_registry_location_not_from_untrusted(self):
+        self.assertIs(None, self.config.go_proxy)
+        self.assertEqual(
+            'http://proxy.example.com/',
+            self.config.ignored_untrusted('go-proxy').value)
+
     def test_secure_not_from_branch(self):
         self.assertEqual(self.config.quick_builder, 'valid quick builder')
 
@@ -99,6 +113,23 @@ class DebBuildConfigTests(TestCaseWithTransport):
         self.assertEquals("http://example.com/foo", cfg.upstream_branch)
         self.assertEquals(
             "tag:exampl-$UPSTREAM_VERSION", cfg.export_upstream_revision)
+        self.assertIs(None, cfg.pypi_project)
+
+    def test_upstream_metadata_pypi_project(self):
+        self.build_tree_contents([
+          ('debian/',),
+          ('debian/upstream/',),
+          ('debian/upstream/metadata',
+           b'Registry:\n'
+           b' - Name: conda:conda-forge\n'
+           b'   Entry: foo\n'
+           b' - Name: PyPI\n'
+           b'   Entry: python-foo\n'
+           )])
+        self.tree.add(
+            ['debian', 'debian/upstream', 'debian/upstream/metadata'])
+        cfg = DebBuildConfig([], tree=self.tree)
+        self.assertEqual("python-foo", cfg.pypi_project)
 
     def test_invalid_upstream_metadata(self):
         cfg = DebBuildConfig([], tree=self.branch.basis_tree())
@@ -116,6 +147,227 @@ class DebBuildConfigTests(TestCaseWithTransport):
         self.assertRaises(
             UpstreamMetadataSyntaxError, DebBuildConfig, [], tree=self.tree)
 
+    def test_upstream_metadata_error_line(self):
+        self.build_tree_contents([
+          ('debian/',),
+          ('debian/upstream/',),
+          ('debian/upstream/metadata',
+           b'Name: example\n'
+           b'Repository: [http://example.com/foo\n'
+           )])
+        self.tree.add(
+            ['debian', 'debian/upstream', 'debian/upstream/metadata'])
+        e = self.assertRaises(
+            UpstreamMetadataSyntaxError, DebBuildConfig, [], tree=self.tree)
+        self.assertStartsWith(e.path, 'debian/upstream/metadata:')
+
+    def test_upstream_metadata_wrong_type(self):
+        self.build_tree_contents([
+          ('debian/',),
+          ('debian/upstream/',),
+          ('debian/upstream/metadata',
+           b'Repository:\n'
+           b' - http://example.com/foo\n'
+           )])
+        self.tree.add(
+            ['debian', 'debian/upstream', 'debian/upstream/metadata'])
+        e = self.assertRaises(
+            UpstreamMetadataSyntaxError, DebBuildConfig, [], tree=self.tree)
+        self.assertContainsRe(str(e), 'Repository should be a string')
+
+    def test_target_sections(self):
+        with open('targets.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'builder = generic builder\n'
+                    'build-dir = generic build dir\n'
+                    'result-dir = generic result dir\n'
+                    'merge = True\n'
+                    '[BUILDDEB:debian]\n'
+                    'build-dir = debian build dir\n'
+                    'result-dir = debian result dir\n'
+                    '[BUILDDEB:bookworm-backports]\n'
+                    'result-dir = backports result dir\n'
+                    'merge = False\n'
+                    '[HOOKS]\n'
+                    'pre-build = generic hook\n'
+                    '[HOOKS:debian]\n'
+                    'pre-build = debian hook\n')
+        cfg = DebBuildConfig([('targets.conf', True)])
+        self.assertEqual('generic result dir', cfg.result_dir)
+        self.assertEqual('generic hook', cfg.get_hook('pre-build'))
+        cfg.set_targets(['bookworm-backports', 'debian'])
+        self.assertEqual('backports result dir', cfg.result_dir)
+        self.assertEqual('debian build dir', cfg.build_dir)
+        self.assertEqual('generic builder', cfg.builder)
+        self.assertEqual(False, cfg.merge)
+        self.assertEqual('debian hook', cfg.get_hook('pre-build'))
+        self.assertEqual(
+            ConfigValue('debian hook', 'targets.conf', 'HOOKS:debian', True),
+            cfg.find_hook('pre-build'))
+        cfg.set_targets(['noble', 'ubuntu'])
+        self.assertEqual('generic result dir', cfg.result_dir)
+        self.assertEqual(True, cfg.merge)
+
+    def test_target_sections_tree_config(self):
+        class TreeConfig(object):
+            options = {
+                ('BUILDDEB:debian', 'build-dir'): 'debian build dir',
+                ('BUILDDEB:debian', 'merge'): True,
+                ('HOOKS', 'pre-build'): 'tree hook',
+                }
+
+            def get_option(self, option, section=None):
+                return self.options.get((section, option))
+        cfg = DebBuildConfig([])
+        cfg._tree_config = TreeConfig()
+        self.assertIs(None, cfg.build_dir)
+        self.assertEqual('tree hook', cfg.get_hook('pre-build'))
+        cfg.set_targets(['unstable', 'debian'])
+        self.assertEqual('debian build dir', cfg.build_dir)
+        self.assertEqual(True, cfg.merge)
+
+    def test_target_sections_per_file(self):
+        with open('local.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'result-dir = local result dir\n')
+        with open('package.conf', 'w') as f:
+            f.write('[BUILDDEB:debian]\n'
+                    'result-dir = debian result dir\n')
+        cfg = DebBuildConfig([('local.conf', True), ('package.conf', True)])
+        cfg.set_targets(['unstable', 'debian'])
+        self.assertEqual('local result dir', cfg.result_dir)
+
+    def test_known_options(self):
+        self.assertTrue(KNOWN_OPTIONS['builder'].trusted)
+        self.assertFalse(KNOWN_OPTIONS['build-dir'].trusted)
+        self.assertIs(None, KNOWN_OPTIONS['build-dir'].default)
+        self.assertEqual(True, KNOWN_OPTIONS['builder-run-tests'].default)
+        self.assertIs(bool, KNOWN_OPTIONS['merge'].type)
+        self.assertIs(list, KNOWN_OPTIONS['build-profiles'].type)
+
+    def test_validate(self):
+        self.assertIs(None, KNOWN_OPTIONS['merge'].validate('yes'))
+        self.assertEqual(
+            'expected a boolean', KNOWN_OPTIONS['merge'].validate('maybe'))
+        self.assertIs(None, KNOWN_OPTIONS['build-dir'].validate('maybe'))
+        self.assertIs(None, KNOWN_OPTIONS['build-deps'].validate('print'))
+
+    def test_lookup(self):
+        self.assertEqual(
+            ConfigValue('valid builder', 'user.conf', 'BUILDDEB', True),
+            self.config.lookup('builder'))
+        self.assertEqual(
+            ConfigValue('default build dir', 'default.conf', 'BUILDDEB',
+                        False),
+            self.config.lookup('build-dir'))
+        self.assertEqual(
+            'branch configuration', self.config.lookup('result-dir').source)
+        self.assertIs(None, self.config.lookup('merge'))
+        self.assertRaises(KeyError, self.config.lookup, 'no-such-option')
+
+    def test_ignored_untrusted(self):
+        self.assertIs(None, self.config.ignored_untrusted('build-dir'))
+        self.assertIs(None, self.config.ignored_untrusted('builder'))
+        self.assertEqual(
+            ConfigValue('invalid quick builder', 'branch configuration',
+                        'BUILDDEB', False),
+            self.config.ignored_untrusted('quick-builder'))
+
+    def test_check(self):
+        with open('check.conf', 'w') as f:
+            f.write('builder = outside\n'
+                    '[BUILDDEB]\n'
+                    'build-dir = build\n'
+                    'buildir = typo\n'
+                    '[BUILDDEB:debian]\n'
+                    'result_dir = typo\n'
+                    '[HOOKS]\n'
+                    'pre-build = true\n'
+                    'post-biuld = true\n'
+                    '[OTHER]\n'
+                    'something = else\n')
+        cfg = DebBuildConfig([('check.conf', True)])
+        self.assertEqual([
+            "check.conf:1: 'builder' is not in a section, so it is ignored",
+            "check.conf:4: 'buildir' in [BUILDDEB] is not a known option",
+            "check.conf:6: 'result_dir' in [BUILDDEB:debian] is not a known "
+            "option",
+            "check.conf:9: 'post-biuld' in [HOOKS] is not a known hook",
+            ], cfg.check())
+        self.assertEqual([], self.config.check())
+
+    def test_check_deprecated(self):
+        with open('deprecated.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'export-upstream = ../upstream\n'
+                    'larstiq = True\n')
+        cfg = DebBuildConfig([('deprecated.conf', True)])
+        self.assertEqual([
+            "deprecated.conf:2: 'export-upstream' is deprecated and "
+            "ignored, use 'upstream-branch' instead",
+            "deprecated.conf:3: 'larstiq' is deprecated and ignored",
+            ], cfg.check())
+        self.assertIs(None, cfg.upstream_branch)
+
+    def test_invalid_bool(self):
+        with open('invalid.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'native = False\n'
+                    '[BUILDDEB:debian]\n'
+                    'merge = maybe\n')
+        e = self.assertRaises(
+            ConfigValueError, DebBuildConfig, [('invalid.conf', True)])
+        self.assertEqual(
+            "invalid.conf:4: invalid value 'maybe' for merge: expected a "
+            "boolean", str(e))
+
+    def test_invalid_choice(self):
+        with open('invalid.conf', 'w') as f:
+            f.write('[BUILDDEB]\n'
+                    'build-deps = fix\n')
+        with open('invalid.conf', 'rb') as f:
+            e = self.assertRaises(
+                ConfigValueError, DebBuildConfig,
+                [(f, False, 'bzr-builddeb.conf')])
+        self.assertEqual(
+            "bzr-builddeb.conf:2: invalid value 'fix' for build-deps: "
+            "expected one of check, print, install", str(e))
+
+    def test_describe_options(self):
+        text = describe_options()
+        self.assertContainsRe(
+            text, r'(?m)^:build-deps:\n    What to do about missing build '
+                  r'dependencies\.\n    One of: check, print, install\.$')
+        self.assertContainsRe(
+            text, r'(?m)^:merge:\n    Run in merge mode\.\n'
+                  r'    A boolean, defaults to False\.$')
+        self.assertContainsRe(
+            text, r'(?m)^:builder:\n.*\n    Only read from trusted files\.$')
+        self.assertContainsRe(
+            text, r'(?m)^:export-upstream: use upstream-branch instead$')
+
+    def test_set_config_option(self):
+        set_config_option('new.conf', 'build-dir', 'build')
+        set_config_option('new.conf', 'result-dir', 'results',
+                          section='BUILDDEB:debian')
+        cfg = DebBuildConfig([('new.conf', True)])
+        self.assertEqual('build', cfg.build_dir)
+        self.assertEqual(None, cfg.result_dir)
+        cfg.set_targets(['debian'])
+        self.assertEqual('results', cfg.result_dir)
+
+    def test_remove_config_option(self):
+        set_config_option('new.conf', 'build-dir', 'build')
+        set_config_option('new.conf', 'result-dir', 'results')
+        remove_config_option('new.conf', 'build-dir')
+        cfg = DebBuildConfig([('new.conf', True)])
+        self.assertEqual(None, cfg.build_dir)
+        self.assertEqual('results', cfg.result_dir)
+        self.assertRaises(
+            NoSuchConfigOption, remove_config_option, 'new.conf',
+            'build-dir')
+
+
 try:
     from ...svn.config import SubversionBuildPackageConfig  # noqa: F401
 except ImportError:
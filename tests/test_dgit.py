@@ -0,0 +1,170 @@
+#    test_dgit.py -- Tests for checking source packages against branches
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+import os
+
+from ....tests import (
+    TestCaseInTempDir,
+    )
+
+from ..dgit import (
+    DIFF_KIND,
+    DIFF_MODE,
+    DIFF_MODIFIED,
+    DIFF_ONLY_IN_BRANCH,
+    DIFF_ONLY_IN_SOURCE,
+    compare_directories,
+    patches_applied,
+    verify_source,
+    )
+from ..source_distiller import NativeSourceDistiller
+
+from . import (
+    ExecutableFeature,
+    TestCaseWithTransport,
+    )
+
+
+DpkgSourceFeature = ExecutableFeature('dpkg-source')
+PatchFeature = ExecutableFeature('patch')
+
+
+PATCH = """\
+--- a/a
++++ b/a
+@@ -1 +1 @@
+-a
++b
+"""
+
+
+class CompareDirectoriesTests(TestCaseInTempDir):
+
+    def test_identical(self):
+        self.build_tree_contents([
+            ('branch/',), ('branch/a', 'a'), ('branch/d/',),
+            ('source/',), ('source/a', 'a'), ('source/d/',),
+            ('source/.pc/',), ('source/.pc/applied-patches', ''),
+            ])
+        self.assertEqual([], compare_directories('branch', 'source'))
+
+    def test_differences(self):
+        self.build_tree_contents([
+            ('branch/',), ('branch/modified', 'a'), ('branch/only-branch', ''),
+            ('branch/kind/',), ('branch/mode', 'x'),
+            ('source/',), ('source/modified', 'b'), ('source/only-source', ''),
+            ('source/kind', ''), ('source/mode', 'x'),
+            ])
+        os.chmod('source/mode', 0o755)
+        self.assertEqual([
+            ('kind', DIFF_KIND),
+            ('mode', DIFF_MODE),
+            ('modified', DIFF_MODIFIED),
+            ('only-branch', DIFF_ONLY_IN_BRANCH),
+            ('only-source', DIFF_ONLY_IN_SOURCE),
+            ], compare_directories('branch', 'source'))
+
+    def test_only_in_branch(self):
+        self.build_tree_contents([
+            ('branch/',), ('branch/debian/',), ('branch/debian/rules', 'a'),
+            ('source/',), ('source/upstream', ''), ('source/debian/',),
+            ('source/debian/rules', 'a'),
+            ])
+        self.assertEqual(
+            [], compare_directories('branch', 'source', only_in_branch=True))
+
+
+class PatchesAppliedTests(TestCaseInTempDir):
+
+    def make_source(self, contents):
+        self.build_tree_contents([
+            ('source/',),
+            ('source/a', contents),
+            ('source/debian/',),
+            ('source/debian/patches/',),
+            ('source/debian/patches/series', 'change-a.patch\n'),
+            ('source/debian/patches/change-a.patch', PATCH),
+            ])
+
+    def test_no_patches(self):
+        self.build_tree_contents([('source/',), ('source/debian/',)])
+        self.assertIs(None, patches_applied('source'))
+
+    def test_pc(self):
+        self.make_source('a\n')
+        self.build_tree_contents([
+            ('source/.pc/',),
+            ('source/.pc/applied-patches', 'change-a.patch\n')])
+        self.assertTrue(patches_applied('source'))
+
+    def test_applied(self):
+        self.requireFeature(PatchFeature)
+        self.make_source('b\n')
+        self.assertTrue(patches_applied('source'))
+
+    def test_unapplied(self):
+        self.requireFeature(PatchFeature)
+        self.make_source('a\n')
+        self.assertFalse(patches_applied('source'))
+
+
+class VerifySourceTests(TestCaseWithTransport):
+
+    def make_native_tree(self):
+        tree = self.make_branch_and_tree('package')
+        self.build_tree_contents([
+            ('package/a', 'a\n'),
+            ('package/debian/',),
+            ('package/debian/changelog',
+             'package (0.1) unstable; urgency=low\n\n'
+             '  * Initial release.\n\n'
+             ' -- Jane Doe <jane@example.com>  '
+             'Thu, 03 Aug 2006 19:16:22 +0100\n'),
+            ('package/debian/control',
+             'Source: package\n\nPackage: package\nArchitecture: all\n'),
+            ('package/debian/source/',),
+            ('package/debian/source/format', '3.0 (native)\n'),
+            ])
+        tree.smart_add([tree.basedir])
+        tree.commit('Initial packaging.')
+        return tree
+
+    def test_matches(self):
+        self.requireFeature(DpkgSourceFeature)
+        tree = self.make_native_tree()
+        os.mkdir('work')
+        state, differences = verify_source(
+            tree, '', NativeSourceDistiller(tree, ''), 'work', 'package',
+            '0.1')
+        self.assertIs(None, state)
+        self.assertEqual([], differences)
+
+    def test_ignored_file(self):
+        self.requireFeature(DpkgSourceFeature)
+        tree = self.make_native_tree()
+        self.build_tree_contents([('package/.gitignore', 'foo\n')])
+        tree.add(['.gitignore'])
+        os.mkdir('work')
+        state, differences = verify_source(
+            tree, '', NativeSourceDistiller(tree, ''), 'work', 'package',
+            '0.1')
+        self.assertEqual([('.gitignore', DIFF_ONLY_IN_BRANCH)], differences)
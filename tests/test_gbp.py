@@ -0,0 +1,180 @@
+#    test_gbp.py -- Tests for reading gbp.conf
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+from ....tests import TestCase
+
+from ..config import DebBuildConfig
+from ..gbp import (
+    GbpConfig,
+    gbp_tag_to_revspec,
+    )
+from ..util import debuild_config
+from . import TestCaseWithTransport
+
+
+GBP_CONF = """\
+[DEFAULT]
+debian-branch = debian/sid
+debian-tag = debian/%(version)s
+pristine-tar = True
+filter = ['.gitignore', '*.orig']
+sign-tags = True
+
+[buildpackage]
+export-dir = ../build-area-gbp
+builder = sbuild -v
+postbuild = lintian $GBP_CHANGES_FILE
+postexport = ./autogen.sh
+compression = xz
+
+[tag]
+debian-tag = release/%(version)s
+posttag = echo $GBP_TAG
+
+[git-import-orig]
+upstream-vcs-tag = v%(version)s
+component = docs
+postimport = dch -v %(version)s
+"""
+
+
+class GbpTagToRevspecTests(TestCase):
+
+    def test_plain(self):
+        self.assertEqual(
+            'tag:v$UPSTREAM_VERSION', gbp_tag_to_revspec('v%(version)s'))
+
+    def test_mangled(self):
+        self.assertEqual(
+            'tag:upstream/$UPSTREAM_VERSION',
+            gbp_tag_to_revspec('upstream/%(version%~%-)s'))
+
+
+class GbpConfigTests(TestCase):
+
+    def setUp(self):
+        super(GbpConfigTests, self).setUp()
+        self.config = GbpConfig(GBP_CONF)
+
+    def test_filename(self):
+        self.assertEqual('debian/gbp.conf', self.config.filename)
+
+    def test_get_gbp_option(self):
+        self.assertEqual(
+            'debian/sid', self.config.get_gbp_option('debian-branch'))
+        self.assertEqual(
+            'release/%(version)s',
+            self.config.get_gbp_option('debian-tag', ['tag']))
+        self.assertEqual(
+            'debian/%(version)s',
+            self.config.get_gbp_option('debian-tag', ['buildpackage']))
+        self.assertEqual(
+            'docs', self.config.get_gbp_option('component', ['import-orig']))
+        self.assertIs(None, self.config.get_gbp_option('upstream-tag'))
+        self.assertEqual(
+            'upstream/%(version)s',
+            self.config.get_gbp_option(
+                'upstream-tag', default='upstream/%(version)s'))
+
+    def test_get_gbp_bool(self):
+        self.assertTrue(self.config.get_gbp_bool('pristine-tar'))
+        self.assertIs(None, self.config.get_gbp_bool('overlay'))
+
+    def test_get_gbp_bool_invalid(self):
+        config = GbpConfig("[DEFAULT]\npristine-tar = perhaps\n")
+        self.assertFalse(config.get_gbp_bool('pristine-tar', default=False))
+
+    def test_options(self):
+        self.assertEqual(
+            '../build-area-gbp',
+            self.config.get_value('BUILDDEB', 'build-dir'))
+        self.assertEqual(
+            'sbuild -v', self.config.get_value('BUILDDEB', 'builder'))
+        self.assertEqual(
+            'debian/sid', self.config.get_value('BUILDDEB', 'debian-branch'))
+        self.assertEqual(
+            'tag:v$UPSTREAM_VERSION',
+            self.config.get_value('BUILDDEB', 'export-upstream-revision'))
+        self.assertEqual(
+            ['.gitignore', '*.orig'],
+            self.config.get_value('BUILDDEB', 'files-excluded'))
+        self.assertEqual(
+            ['docs'], self.config.get_value('BUILDDEB', 'components'))
+        self.assertEqual(
+            'xz', self.config.get_value('BUILDDEB', 'compression'))
+        self.assertTrue(self.config.get_bool('BUILDDEB', 'sign-tags'))
+
+    def test_unmapped(self):
+        self.assertRaises(
+            KeyError, self.config.get_value, 'BUILDDEB', 'merge')
+        self.assertRaises(
+            KeyError, self.config.get_value, 'BUILDDEB:debian', 'builder')
+        self.assertRaises(KeyError, self.config.get_bool, 'BUILDDEB', 'merge')
+
+    def test_hooks(self):
+        self.assertEqual(
+            'lintian $GBP_CHANGES_FILE',
+            self.config.get_value('HOOKS', 'post-build'))
+        self.assertEqual(
+            'dch -v %(version)s',
+            self.config.get_value('HOOKS', 'post-merge-upstream'))
+        self.assertEqual(
+            './autogen.sh', self.config.get_value('HOOKS', 'post-export'))
+        self.assertEqual(
+            'echo $GBP_TAG', self.config.get_value('HOOKS', 'post-tag'))
+        self.assertRaises(
+            KeyError, self.config.get_value, 'HOOKS', 'pre-build')
+
+
+class GbpConfigLayerTests(TestCaseWithTransport):
+
+    def test_precedence(self):
+        with open('builddeb.conf', 'w') as f:
+            f.write('[BUILDDEB]\nbuild-dir = ../build-area-builddeb\n')
+        config = DebBuildConfig([('builddeb.conf', False)])
+        config.add_config(GbpConfig(GBP_CONF), False)
+        self.assertEqual('../build-area-builddeb', config.build_dir)
+        self.assertEqual('debian/sid', config.debian_branch)
+        self.assertTrue(config.sign_tags)
+        self.assertEqual(
+            'lintian $GBP_CHANGES_FILE', config.get_hook('post-build'))
+        # The builder is only taken from trusted files.
+        self.assertIs(None, config.builder)
+        self.assertEqual(
+            'debian/gbp.conf', config.ignored_untrusted('builder').source)
+
+    def test_trusted(self):
+        config = DebBuildConfig([])
+        config.add_config(GbpConfig(GBP_CONF, '~/.gbp.conf'), True)
+        self.assertEqual('sbuild -v', config.builder)
+
+    def test_debuild_config(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('debian/',),
+            ('debian/gbp.conf', GBP_CONF),
+            ])
+        tree.add(['debian', 'debian/gbp.conf'])
+        config = debuild_config(tree, '')
+        self.assertEqual('../build-area-gbp', config.build_dir)
+        self.assertEqual(
+            'gbp.conf', config.lookup('build-dir').source)
@@ -22,8 +22,21 @@ from __future__ import absolute_import
 
 import os
 
+from debian.changelog import Version
+
+from .... import ui
+
 from ..config import DebBuildConfig
-from ..hooks import run_hook, HookFailedError
+from ..gbp import GbpConfig
+from .. import hooks
+from ..hooks import (
+    HookFailedError,
+    UntrustedHookError,
+    branch_is_trusted,
+    hook_environment,
+    run_hook,
+    sandbox_command,
+    )
 from . import TestCaseInTempDir
 
 
@@ -40,25 +53,25 @@ class HookTests(TestCaseInTempDir):
     def test_run_hook_allows_no_hook_defined(self):
         f = open(self.default_conf, 'wb')
         f.close()
-        config = DebBuildConfig([(self.default_conf, False)])
+        config = DebBuildConfig([(self.default_conf, True)])
         run_hook(MockTree(), 'pre-build', config)
 
     def test_run_hook_raises_when_hook_fails(self):
         with open(self.default_conf, 'wb') as f:
             f.write(b'[HOOKS]\npre-build = false\n')
-        config = DebBuildConfig([(self.default_conf, False)])
+        config = DebBuildConfig([(self.default_conf, True)])
         self.assertRaises(HookFailedError, run_hook, MockTree(), 'pre-build', config)
 
     def test_run_hook_when_hook_passes(self):
         with open(self.default_conf, 'wb') as f:
             f.write(b'[HOOKS]\npre-build = true\n')
-        config = DebBuildConfig([(self.default_conf, False)])
+        config = DebBuildConfig([(self.default_conf, True)])
         run_hook(MockTree(), 'pre-build', config)
 
     def test_run_hook_uses_cwd_by_default(self):
         with open(self.default_conf, 'wb') as f:
             f.write(b'[HOOKS]\npre-build = touch a\n')
-        config = DebBuildConfig([(self.default_conf, False)])
+        config = DebBuildConfig([(self.default_conf, True)])
         run_hook(MockTree(), 'pre-build', config)
         self.assertPathExists('a')
 
@@ -66,14 +79,14 @@ class HookTests(TestCaseInTempDir):
         os.mkdir('dir')
         with open(self.default_conf, 'wb') as f:
             f.write(b'[HOOKS]\npre-build = touch a\n')
-        config = DebBuildConfig([(self.default_conf, False)])
+        config = DebBuildConfig([(self.default_conf, True)])
         run_hook(MockTree(), 'pre-build', config, wd='dir')
         self.assertPathExists('dir/a')
 
     def test_run_hook_uses_shell(self):
         with open(self.default_conf, 'wb') as f:
             f.write(b'[HOOKS]\npost-build = touch a && touch b\n')
-        config = DebBuildConfig([(self.default_conf, False)])
+        config = DebBuildConfig([(self.default_conf, True)])
         run_hook(MockTree(), 'post-build', config)
         self.assertPathExists('a')
         self.assertPathExists('b')
@@ -83,8 +96,166 @@ class HookTests(TestCaseInTempDir):
             f.write(b'[HOOKS]\npost-build = touch a\n')
         with open(self.local_conf, 'wb') as f:
             f.write(b'[HOOKS]\npost-build = touch b\n')
-        config = DebBuildConfig([(self.local_conf, False),
-                                 (self.default_conf, False)])
+        config = DebBuildConfig([(self.local_conf, True),
+                                 (self.default_conf, True)])
         run_hook(MockTree(), 'post-build', config)
         self.assertPathDoesNotExist('a')
         self.assertPathExists('b')
+
+    def test_run_hook_error_includes_exit_code_and_output(self):
+        with open(self.default_conf, 'wb') as f:
+            f.write(b'[HOOKS]\npre-build = echo broken; exit 3\n')
+        config = DebBuildConfig([(self.default_conf, True)])
+        e = self.assertRaises(
+            HookFailedError, run_hook, MockTree(), 'pre-build', config)
+        self.assertEqual(3, e.returncode)
+        self.assertEqual('broken\n', e.output)
+        self.assertEqual(
+            'The "pre-build" hook failed with exit code 3.\n'
+            'Last output of the hook:\nbroken', str(e))
+
+    def test_run_hook_passes_environment(self):
+        with open(self.default_conf, 'wb') as f:
+            f.write(b'[HOOKS]\npost-tag = echo $BUILDDEB_HOOK '
+                    b'$BUILDDEB_PACKAGE $BUILDDEB_TAG > a\n')
+        config = DebBuildConfig([(self.default_conf, True)])
+        run_hook(MockTree(), 'post-tag', config, env=hook_environment(
+            'foo', Version('1.0-1'), tag='debian/1.0-1'))
+        with open('a') as f:
+            self.assertEqual('post-tag foo debian/1.0-1\n', f.read())
+
+    def test_run_hook_passes_gbp_environment(self):
+        config = DebBuildConfig([])
+        config.add_config(GbpConfig(
+            '[buildpackage]\npostbuild = echo $GBP_CHANGES_FILE > a\n'),
+            True)
+        run_hook(MockTree(), 'post-build', config, env=hook_environment(
+            changes_file='foo_1.0-1_source.changes'))
+        with open('a') as f:
+            self.assertEqual(
+                os.path.abspath('foo_1.0-1_source.changes') + '\n', f.read())
+
+    def test_run_hook_uses_old_name(self):
+        with open(self.default_conf, 'wb') as f:
+            f.write(b'[HOOKS]\nmerge-upstream = touch a\n')
+        config = DebBuildConfig([(self.default_conf, True)])
+        run_hook(MockTree(), 'post-merge-upstream', config)
+        self.assertPathExists('a')
+
+
+class UntrustedHookTests(TestCaseInTempDir):
+
+    user_conf = 'user.conf'
+    package_conf = 'package.conf'
+
+    def make_config(self, user_settings=b''):
+        with open(self.user_conf, 'wb') as f:
+            f.write(b'[BUILDDEB]\n' + user_settings)
+        with open(self.package_conf, 'wb') as f:
+            f.write(b'[HOOKS]\npre-build = touch a\n')
+        config = DebBuildConfig(
+            [(self.user_conf, True), (self.package_conf, False)])
+        config.branch_url = 'https://example.com/foo/'
+        return config
+
+    def test_refused(self):
+        config = self.make_config()
+        e = self.assertRaises(
+            UntrustedHookError, run_hook, MockTree(), 'pre-build', config)
+        self.assertEqual('https://example.com/foo/', e.branch_url)
+        self.assertPathDoesNotExist('a')
+
+    def test_trusted_branch(self):
+        config = self.make_config(
+            b'trusted-hook-branches = https://example.com/*\n')
+        run_hook(MockTree(), 'pre-build', config)
+        self.assertPathExists('a')
+
+    def test_allow(self):
+        config = self.make_config(b'untrusted-hooks = allow\n')
+        run_hook(MockTree(), 'pre-build', config)
+        self.assertPathExists('a')
+
+    def test_not_allowed_from_package(self):
+        with open(self.package_conf, 'wb') as f:
+            f.write(b'[BUILDDEB]\nuntrusted-hooks = allow\n'
+                    b'[HOOKS]\npre-build = touch a\n')
+        config = DebBuildConfig([(self.package_conf, False)])
+        self.assertRaises(
+            UntrustedHookError, run_hook, MockTree(), 'pre-build', config)
+
+    def test_prompt(self):
+        config = self.make_config(b'untrusted-hooks = prompt\n')
+        self.overrideAttr(
+            ui, 'ui_factory', ui.CannedInputUIFactory([False, True]))
+        self.assertRaises(
+            UntrustedHookError, run_hook, MockTree(), 'pre-build', config)
+        self.assertPathDoesNotExist('a')
+        run_hook(MockTree(), 'pre-build', config)
+        self.assertPathExists('a')
+
+    def test_branch_is_trusted(self):
+        self.assertFalse(branch_is_trusted(None, ['*']))
+        self.assertTrue(branch_is_trusted(
+            'https://example.com/foo/', ['https://example.com/foo']))
+        self.assertFalse(branch_is_trusted(
+            'https://example.com/bar', ['https://example.com/foo']))
+        self.assertTrue(branch_is_trusted(
+            'file:///home/user/foo/', ['/home/user/*']))
+
+    def test_sandbox_command(self):
+        self.assertEqual(
+            ['unshare', '--user', '--map-root-user', '--net', '--',
+             '/bin/sh', '-c', 'make'],
+            sandbox_command('unshare', 'make', '/build'))
+        command = sandbox_command('bwrap', 'make', '/build')
+        self.assertEqual('bwrap', command[0])
+        self.assertIn('--unshare-all', command)
+        self.assertEqual(['--bind', '/build', '/build'], command[10:13])
+        self.assertEqual(['/bin/sh', '-c', 'make'], command[-3:])
+
+
+class BuilddebHooksTests(TestCaseInTempDir):
+
+    def test_hook_points(self):
+        for name in ['distill', 'build', 'merge_upstream', 'import_dsc',
+                     'release', 'tag']:
+            self.assertIn('pre_' + name, hooks.builddeb_hooks)
+            self.assertIn('post_' + name, hooks.builddeb_hooks)
+
+    def test_run_builddeb_hooks(self):
+        calls = []
+        hooks.builddeb_hooks.install_named_hook(
+            'pre_release', calls.append, 'test')
+        params = hooks.ReleaseHookParams(MockTree(), '', None)
+        hooks.run_builddeb_hooks('pre_release', params)
+        hooks.run_builddeb_hooks('post_release', params)
+        self.assertEqual([params], calls)
+
+
+class HookEnvironmentTests(TestCaseInTempDir):
+
+    def test_empty(self):
+        self.assertEqual({}, hook_environment())
+
+    def test_version(self):
+        self.assertEqual({
+            'BUILDDEB_PACKAGE': 'foo',
+            'BUILDDEB_VERSION': '1:1.0-1',
+            'BUILDDEB_UPSTREAM_VERSION': '1.0',
+            }, hook_environment('foo', Version('1:1.0-1')))
+
+    def test_upstream_version(self):
+        self.assertEqual(
+            {'BUILDDEB_UPSTREAM_VERSION': '2.0'},
+            hook_environment(upstream_version='2.0'))
+
+    def test_paths_are_absolute(self):
+        self.assertEqual({
+            'BUILDDEB_BUILD_DIR': os.path.abspath('build/foo-1.0'),
+            'BUILDDEB_RESULT_DIR': os.path.abspath('result'),
+            'BUILDDEB_CHANGES_FILE': os.path.abspath(
+                'result/foo_1.0-1_source.changes'),
+            }, hook_environment(
+                build_dir='build/foo-1.0', result_dir='result',
+                changes_file='result/foo_1.0-1_source.changes'))
@@ -28,6 +28,7 @@ import tarfile
 from debian.changelog import Version
 
 from .... import (
+    gpg,
     revision as _mod_revision,
     tests,
     )
@@ -38,12 +39,14 @@ from ....tests.features import (
 
 from . import make_new_upstream_tarball_xz
 
+from .. import hooks
 from ..import_dsc import (
         DistributionBranch,
         DistributionBranchSet,
         _default_config_for_tree,
         get_changelog_from_source,
         _is_tree_native,
+        UpstreamComponentChanged,
         )
 from ..upstream.pristinetar import (
         PristineTarDeltaTooLarge,
@@ -179,6 +182,67 @@ class DistributionBranchTests(BuilddebTestCase):
         self.assertEqual(
             tree.branch.tags.lookup_tag(db.tag_name(version)), revid)
 
+    def test_tag_version_signed(self):
+        self.overrideAttr(gpg, 'GPGStrategy', gpg.LoopbackGPGStrategy)
+        db = DistributionBranch(self.tree1.branch, None, sign_tags=True)
+        revid = self.tree1.commit("one")
+        db.tag_version(Version("0.1-1"))
+        repository = self.tree1.branch.repository
+        self.assertTrue(repository.has_signature_for_revision_id(revid))
+
+    def test_tag_version_unsigned(self):
+        revid = self.tree1.commit("one")
+        self.db1.tag_version(Version("0.1-1"))
+        repository = self.tree1.branch.repository
+        self.assertFalse(repository.has_signature_for_revision_id(revid))
+
+    def test_tag_name_format(self):
+        db = DistributionBranch(
+            self.tree1.branch, None, tag_format='debian/%(version)s')
+        self.assertEqual(
+            'debian/1%0.1_rc1-1', db.tag_name(Version('1:0.1~rc1-1')))
+        self.assertEqual(
+            'ubuntu/0.1-1', db.tag_name(Version('0.1-1'), vendor='ubuntu'))
+
+    def test_has_version_format(self):
+        db = DistributionBranch(
+            self.tree1.branch, None, tag_format='release/%(version)s')
+        version = Version("0.1-1")
+        self.assertFalse(db.has_version(version))
+        revid = self.tree1.commit("one")
+        self.assertEqual('release/0.1-1', db.tag_version(version))
+        self.assertTrue(db.has_version(version))
+        self.assertEqual(revid, db.revid_of_version(version))
+
+    def test_tag_version_hooks(self):
+        calls = []
+
+        def pre_tag(params):
+            calls.append(('pre', params.version, params.tag_name))
+            params.tag_name = 'release-' + params.tag_name
+
+        def post_tag(params):
+            calls.append(('post', params.revid, params.tag_name))
+        hooks.builddeb_hooks.install_named_hook('pre_tag', pre_tag, 'pre')
+        hooks.builddeb_hooks.install_named_hook('post_tag', post_tag, 'post')
+        version = Version("0.1-1")
+        revid = self.tree1.commit("one")
+        self.assertEqual('release-0.1-1', self.db1.tag_version(version))
+        self.assertEqual(
+            [('pre', version, '0.1-1'), ('post', revid, 'release-0.1-1')],
+            calls)
+        self.assertEqual(
+            revid, self.tree1.branch.tags.lookup_tag('release-0.1-1'))
+
+    def test_tag_version_vetoed(self):
+        def pre_tag(params):
+            raise hooks.HookFailedError('pre_tag')
+        hooks.builddeb_hooks.install_named_hook('pre_tag', pre_tag, 'veto')
+        self.tree1.commit("one")
+        self.assertRaises(
+            hooks.HookFailedError, self.db1.tag_version, Version("0.1-1"))
+        self.assertEqual({}, self.tree1.branch.tags.get_tag_dict())
+
     def test_tag_upstream_version(self):
         db = self.db1
         tree = self.up_tree1
@@ -1630,6 +1694,113 @@ class DistributionBranchTests(BuilddebTestCase):
             [(tarball_filename, None)], "foo", "0.2", "0.1")
         self.assertFalse(conflicts)
 
+    def test_merge_upstream_component(self):
+        tree = self.make_branch_and_tree('work')
+        self.build_tree(['work/a'])
+        tree.add(['a'])
+        orig_upstream_rev = tree.commit("one")
+        tree.branch.tags.set_tag("upstream-0.1", orig_upstream_rev)
+        self.build_tree(['work/debian/'])
+        cl = self.make_changelog(version="0.1-1")
+        self.write_changelog(cl, 'work/debian/changelog')
+        tree.add(['debian/', 'debian/changelog'])
+        tree.commit("two")
+        db = DistributionBranch(tree.branch, tree.branch, tree=tree)
+        dbs = DistributionBranchSet()
+        dbs.add_branch(db)
+        tarball_filename = "foo_0.2.orig.tar.gz"
+        with tarfile.open(tarball_filename, 'w:gz') as tf:
+            with open("a", "wb") as f:
+                f.write(b"aaa")
+            tf.add("a")
+        component_filename = "foo_0.2.orig-extra.tar.gz"
+        with tarfile.open(component_filename, 'w:gz') as tf:
+            with open("x", "wb") as f:
+                f.write(b"xxx")
+            tf.add("x")
+        conflicts, imported_revids = db.merge_upstream(
+            [(tarball_filename, None), (component_filename, "extra")],
+            "foo", "0.2", "0.1")
+        self.assertFalse(conflicts)
+        revids = dict(
+            (component, revid)
+            for (component, tag, revid, pristine_tar_imported)
+            in imported_revids)
+        self.assertEqual([None, "extra"], sorted(revids, key=str))
+        self.assertEqual(
+            revids["extra"], tree.branch.tags.lookup_tag("upstream-0.2/extra"))
+        self.assertEqual(
+            [tree.branch.last_revision(), revids[None], revids["extra"]],
+            tree.get_parent_ids())
+        self.assertFileEqual(b"aaa", "work/a")
+        self.assertFileEqual(b"xxx", "work/extra/x")
+        self.assertTrue(tree.is_versioned("extra/x"))
+        self.assertEqual(
+            {None: revids[None], "extra": revids["extra"]},
+            db.pristine_upstream_source.version_as_revisions("foo", "0.2"))
+
+    def test_merge_upstream_component_subpath(self):
+        tree = self.make_branch_and_tree('work')
+        self.build_tree(['work/a'])
+        tree.add(['a'])
+        orig_upstream_rev = tree.commit("one")
+        tree.branch.tags.set_tag("upstream-0.1", orig_upstream_rev)
+        self.build_tree(['work/pkg/', 'work/pkg/debian/'])
+        cl = self.make_changelog(version="0.1-1")
+        self.write_changelog(cl, 'work/pkg/debian/changelog')
+        tree.add(['pkg', 'pkg/debian/', 'pkg/debian/changelog'])
+        tree.commit("two")
+        db = DistributionBranch(tree.branch, tree.branch, tree=tree)
+        dbs = DistributionBranchSet()
+        dbs.add_branch(db)
+        tarball_filename = "foo_0.2.orig.tar.gz"
+        with tarfile.open(tarball_filename, 'w:gz') as tf:
+            with open("a", "wb") as f:
+                f.write(b"aaa")
+            tf.add("a")
+        component_filename = "foo_0.2.orig-extra.tar.gz"
+        with tarfile.open(component_filename, 'w:gz') as tf:
+            with open("x", "wb") as f:
+                f.write(b"xxx")
+            tf.add("x")
+        db.merge_upstream(
+            [(tarball_filename, None), (component_filename, "extra")],
+            "foo", "0.2", "0.1", subpath="pkg")
+        self.assertFileEqual(b"xxx", "work/pkg/extra/x")
+        self.assertTrue(tree.is_versioned("pkg/extra/x"))
+        self.assertPathDoesNotExist("work/extra")
+
+    def test_merge_upstream_component_changed(self):
+        tree = self.make_branch_and_tree('work')
+        self.build_tree(['work/a'])
+        tree.add(['a'])
+        orig_upstream_rev = tree.commit("one")
+        tree.branch.tags.set_tag("upstream-0.1", orig_upstream_rev)
+        self.build_tree(['work/debian/', 'work/extra/', 'work/extra/local'])
+        cl = self.make_changelog(version="0.1-1")
+        self.write_changelog(cl, 'work/debian/changelog')
+        tree.add(['debian/', 'debian/changelog', 'extra/', 'extra/local'])
+        tree.commit("two")
+        db = DistributionBranch(tree.branch, tree.branch, tree=tree)
+        dbs = DistributionBranchSet()
+        dbs.add_branch(db)
+        tarball_filename = "foo_0.2.orig.tar.gz"
+        with tarfile.open(tarball_filename, 'w:gz') as tf:
+            with open("a", "wb") as f:
+                f.write(b"aaa")
+            tf.add("a")
+        component_filename = "foo_0.2.orig-extra.tar.gz"
+        with tarfile.open(component_filename, 'w:gz') as tf:
+            with open("x", "wb") as f:
+                f.write(b"xxx")
+            tf.add("x")
+        self.assertRaises(
+            UpstreamComponentChanged, db.merge_upstream,
+            [(tarball_filename, None), (component_filename, "extra")],
+            "foo", "0.2", "0.1")
+        self.assertPathExists("work/extra/local")
+        self.assertPathDoesNotExist("work/extra/x")
+
     def test_merge_upstream_initial_with_branch(self):
         """Verify we can go from normal branches to merge-upstream."""
         tree = self.make_branch_and_tree('work')
@@ -66,6 +66,49 @@ class MergePackageTests(TestCaseWithTransport):
         vdata = MP._upstream_version_data(
             ubup_o.branch, ubup_o.last_revision())
         self.assertEquals(vdata[0], Version('1.2'))
+        self.assertEquals(list(vdata[1].keys()), [None])
+
+    def _add_upstream_component(self, packaging, version, component):
+        """Add a revision for an additional orig tarball."""
+        ctree = self.make_branch_and_tree('component-%s' % component)
+        self.build_tree(['component-%s/x' % component])
+        ctree.add(['x'])
+        revid = ctree.commit('Import %s component.' % component)
+        packaging.branch.fetch(ctree.branch, revid)
+        packaging.branch.tags.set_tag(
+            'upstream-%s/%s' % (version, component), revid)
+        return revid
+
+    def test__upstream_version_data_components(self):
+        ubup_o, debp_n, _ubuu, debu_n = self._setup_debian_upstream_newer()
+        extra_revid = self._add_upstream_component(debp_n, '1.10', 'extra')
+        vdata = MP._upstream_version_data(
+            debp_n.branch, debp_n.last_revision())
+        self.assertEquals(vdata[0], Version('1.10'))
+        self.assertEquals(
+            {None: debu_n.branch.last_revision(), 'extra': extra_revid},
+            vdata[1])
+
+    def test_debian_upstream_newer_components(self):
+        """Components of the merge source are linked in as well."""
+        ubup, debp, ubuu, debu = self._setup_debian_upstream_newer()
+        extra_revid = self._add_upstream_component(debp, '1.10', 'extra')
+        ubup_tip_pre_fix = ubup.branch.last_revision()
+
+        upstreams_diverged, t_upstream_reverted = MP.fix_ancestry_as_needed(
+            ubup, debp.branch)
+        self.assertEquals(upstreams_diverged, True)
+
+        ubup_parents_post_fix = ubup.branch.repository.revision_tree(
+            ubup.branch.last_revision()).get_parent_ids()
+        self.assertEquals(ubup_parents_post_fix[0], ubup_tip_pre_fix)
+        ubup_parents_sharedupstream = ubup.branch.repository.revision_tree(
+            ubup_parents_post_fix[1]).get_parent_ids()
+        self.assertEquals(
+            ubup_parents_sharedupstream,
+            [ubuu.branch.last_revision(), debu.branch.last_revision(),
+             extra_revid])
+        self.assertTrue(ubup.branch.repository.has_revision(extra_revid))
 
     def test_debian_upstream_newer(self):
         """Diverging upstreams (debian newer) don't cause merge conflicts.
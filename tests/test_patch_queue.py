@@ -0,0 +1,369 @@
+#    test_patch_queue.py -- Tests for maintaining patches as commits
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+from ....tests import (
+    TestCase,
+    TestCaseInTempDir,
+    )
+from ....uncommit import uncommit
+
+from ..patch_queue import (
+    PatchApplyFailed,
+    PatchesAlreadyApplied,
+    export_patch_queue,
+    import_patch_queue,
+    parse_patch_header,
+    parse_series,
+    patch_name_from_message,
+    queue_patches,
+    split_patch,
+    write_patches,
+    )
+from ..source_distiller import (
+    NativeSourceDistiller,
+    PatchQueueDistiller,
+    )
+
+from . import (
+    TestCaseWithTransport,
+    )
+
+
+FIX_BUILD_PATCH = """\
+Description: Fix the build
+ The build failed with newer compilers.
+ .
+ This fixes it.
+Author: Jane Doe <jane@example.com>
+Origin: upstream, https://example.com/commit/1234
+Forwarded: not-needed
+Bug-Debian: http://bugs.debian.org/424242
+Last-Update: 2020-05-01
+
+--- a/hello.c
++++ b/hello.c
+@@ -1 +1 @@
+-int main() { return 1; }
++int main() { return 0; }
+"""
+
+
+ADD_README_PATCH = """\
+From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001
+From: John Doe <john@example.com>
+Date: Fri, 1 May 2020 12:00:00 +0200
+Subject: [PATCH] Add a README.
+
+---
+ README | 1 +
+ 1 file changed, 1 insertion(+)
+
+--- /dev/null
++++ b/README
+@@ -0,0 +1 @@
++Hello
+"""
+
+
+class ParseSeriesTests(TestCase):
+
+    def test_simple(self):
+        self.assertEqual(
+            [('a.patch', []), ('b.patch', ['-p0'])],
+            parse_series("a.patch\n# comment\n\nb.patch -p0 # trailing\n"))
+
+    def test_empty(self):
+        self.assertEqual([], parse_series(""))
+
+
+class SplitPatchTests(TestCase):
+
+    def test_dep3(self):
+        header, diff = split_patch(FIX_BUILD_PATCH)
+        self.assertTrue(header.startswith("Description: Fix the build\n"))
+        self.assertTrue(diff.startswith("--- a/hello.c\n"))
+
+    def test_no_header(self):
+        self.assertEqual(
+            ("", "--- a/x\n+++ b/x\n"), split_patch("--- a/x\n+++ b/x\n"))
+
+
+class ParsePatchHeaderTests(TestCase):
+
+    def test_dep3(self):
+        header = parse_patch_header(split_patch(FIX_BUILD_PATCH)[0])
+        self.assertEqual(
+            "Fix the build\nThe build failed with newer compilers.\n\n"
+            "This fixes it.", header.message)
+        self.assertEqual(["Jane Doe <jane@example.com>"], header.authors)
+        self.assertEqual(["http://bugs.debian.org/424242"], header.bugs)
+        self.assertEqual(1588291200, header.last_update)
+        self.assertEqual({
+            'Origin': 'upstream, https://example.com/commit/1234',
+            'Forwarded': 'not-needed'}, header.fields)
+
+    def test_git_format_patch(self):
+        header = parse_patch_header(split_patch(ADD_README_PATCH)[0])
+        self.assertEqual("Add a README.", header.message)
+        self.assertEqual(["John Doe <john@example.com>"], header.authors)
+        self.assertEqual([], header.bugs)
+        self.assertIs(None, header.last_update)
+
+    def test_no_header(self):
+        header = parse_patch_header("")
+        self.assertIs(None, header.message)
+        self.assertEqual([], header.authors)
+
+
+class PatchNameFromMessageTests(TestCase):
+
+    def test_simple(self):
+        self.assertEqual(
+            "fix-the-build.patch", patch_name_from_message("Fix the build.\n"))
+
+    def test_first_line(self):
+        self.assertEqual(
+            "add-foo.patch",
+            patch_name_from_message("Add foo\n\nLonger description."))
+
+    def test_empty(self):
+        self.assertEqual("patch.patch", patch_name_from_message("..."))
+
+
+class WritePatchesTests(TestCaseInTempDir):
+
+    def test_replace(self):
+        self.build_tree_contents([
+            ('patches/',),
+            ('patches/series', 'old.patch\nkept.patch\n'),
+            ('patches/old.patch', 'old'),
+            ('patches/kept.patch', 'kept'),
+            ('patches/README', 'unrelated'),
+            ])
+        added, removed = write_patches(
+            [('kept.patch', b'new kept'), ('new.patch', b'new')], 'patches')
+        self.assertEqual(['new.patch'], added)
+        self.assertEqual(['old.patch'], removed)
+        self.assertFileEqual('kept.patch\nnew.patch\n', 'patches/series')
+        self.assertFileEqual('new kept', 'patches/kept.patch')
+        self.assertPathDoesNotExist('patches/old.patch')
+        self.assertPathExists('patches/README')
+
+    def test_no_patches(self):
+        self.build_tree_contents([
+            ('patches/',),
+            ('patches/series', 'old.patch\n'),
+            ('patches/old.patch', 'old'),
+            ])
+        added, removed = write_patches([], 'patches')
+        self.assertEqual([], added)
+        self.assertEqual(['old.patch', 'series'], removed)
+        self.assertPathDoesNotExist('patches/series')
+
+    def test_creates_directory(self):
+        added, removed = write_patches([('a.patch', b'a')], 'patches')
+        self.assertEqual(['a.patch', 'series'], added)
+        self.assertFileEqual('a.patch\n', 'patches/series')
+
+    def test_subdirectory(self):
+        added, removed = write_patches(
+            [('upstream/a.patch', b'a')], 'patches')
+        self.assertEqual(['upstream/a.patch', 'series'], added)
+        self.assertFileEqual('upstream/a.patch\n', 'patches/series')
+        self.assertFileEqual('a', 'patches/upstream/a.patch')
+
+    def test_keeps_options(self):
+        self.build_tree_contents([
+            ('patches/',),
+            ('patches/series', 'a.patch -p0\nb.patch\n'),
+            ('patches/a.patch', 'old a'),
+            ('patches/b.patch', 'old b'),
+            ])
+        contents = b'--- a/foo\n+++ b/foo\n@@ -1 +1 @@\n-a\n+b\n'
+        added, removed = write_patches(
+            [('a.patch', contents), ('b.patch', contents)], 'patches')
+        self.assertEqual([], added)
+        self.assertEqual([], removed)
+        self.assertFileEqual('a.patch -p0\nb.patch\n', 'patches/series')
+        self.assertFileEqual(
+            b'--- foo\n+++ foo\n@@ -1 +1 @@\n-a\n+b\n', 'patches/a.patch')
+        self.assertFileEqual(contents, 'patches/b.patch')
+
+
+class PatchQueueTests(TestCaseWithTransport):
+
+    def make_packaging_tree(self, patches):
+        tree = self.make_branch_and_tree('packaging')
+        contents = [
+            ('packaging/hello.c', 'int main() { return 1; }\n'),
+            ('packaging/debian/',),
+            ('packaging/debian/patches/',),
+            ('packaging/debian/patches/series',
+             ''.join(name + '\n' for (name, patch) in patches)),
+            ]
+        for name, patch in patches:
+            contents.append(('packaging/debian/patches/' + name, patch))
+        self.build_tree_contents(contents)
+        tree.smart_add([tree.basedir])
+        tree.commit('Initial packaging.')
+        return tree
+
+    def make_queue(self, tree, path='queue'):
+        queue = tree.controldir.sprout(path).open_workingtree()
+        self.addCleanup(queue.lock_write().unlock)
+        return queue, import_patch_queue(queue)
+
+    def test_import(self):
+        tree = self.make_packaging_tree([
+            ('fix-build.patch', FIX_BUILD_PATCH),
+            ('readme.patch', ADD_README_PATCH)])
+        queue, revids = self.make_queue(tree)
+        self.assertEqual(2, len(revids))
+        self.assertFileEqual('int main() { return 0; }\n', 'queue/hello.c')
+        self.assertFileEqual('Hello\n', 'queue/README')
+        self.assertTrue(queue.is_versioned('README'))
+        rev1, rev2 = queue.branch.repository.get_revisions(revids)
+        self.assertEqual(
+            "Fix the build\nThe build failed with newer compilers.\n\n"
+            "This fixes it.", rev1.message)
+        self.assertEqual(
+            ["Jane Doe <jane@example.com>"], rev1.get_apparent_authors())
+        self.assertEqual(1588291200, rev1.timestamp)
+        self.assertEqual(
+            [("http://bugs.debian.org/424242", "fixed")],
+            list(rev1.iter_bugs()))
+        self.assertEqual('fix-build.patch', rev1.properties['deb-patch-name'])
+        self.assertEqual(
+            'not-needed', rev1.properties['deb-patch-forwarded'])
+        self.assertEqual("Add a README.", rev2.message)
+        self.assertEqual(
+            ["John Doe <john@example.com>"], rev2.get_apparent_authors())
+
+    def test_import_fails(self):
+        tree = self.make_packaging_tree([
+            ('broken.patch', "--- a/missing\n+++ b/missing\n@@ -1 +1 @@\n"
+             "-a\n+b\n")])
+        queue = tree.controldir.sprout('queue').open_workingtree()
+        self.addCleanup(queue.lock_write().unlock)
+        self.assertRaises(PatchApplyFailed, import_patch_queue, queue)
+
+    def test_import_applied(self):
+        tree = self.make_packaging_tree([('fix-build.patch', FIX_BUILD_PATCH)])
+        self.build_tree_contents([
+            ('packaging/.pc/',),
+            ('packaging/.pc/applied-patches', 'fix-build.patch\n')])
+        self.addCleanup(tree.lock_write().unlock)
+        self.assertRaises(PatchesAlreadyApplied, import_patch_queue, tree)
+
+    def test_round_trip(self):
+        tree = self.make_packaging_tree([('fix-build.patch', FIX_BUILD_PATCH)])
+        queue, revids = self.make_queue(tree)
+        patches = queue_patches(queue.branch, tree.last_revision())
+        self.assertEqual(['fix-build.patch'], [name for (name, p) in patches])
+        header, diff = split_patch(patches[0][1].decode('utf-8'))
+        self.assertEqual(
+            "Description: Fix the build\n"
+            " The build failed with newer compilers.\n"
+            " .\n"
+            " This fixes it.\n"
+            "Origin: upstream, https://example.com/commit/1234\n"
+            "Forwarded: not-needed\n"
+            "Author: Jane Doe <jane@example.com>\n"
+            "Bug-Debian: http://bugs.debian.org/424242\n"
+            "Last-Update: 2020-05-01\n\n", header)
+        self.assertContainsRe(diff, r'^--- a/hello.c')
+        self.assertContainsRe(diff, r'\n\+\+\+ b/hello.c')
+        self.assertContainsRe(diff, r'\n\+int main\(\) { return 0; }\n')
+
+    def test_new_commit_and_debian_changes(self):
+        tree = self.make_packaging_tree([])
+        queue, revids = self.make_queue(tree)
+        self.assertEqual([], revids)
+        self.build_tree_contents([
+            ('queue/hello.c', 'int main() { return 2; }\n')])
+        queue.commit('Return 2.\n\nFor reasons.')
+        self.build_tree_contents([('queue/debian/rules', 'rules\n')])
+        queue.add(['debian/rules'])
+        queue.commit('Only packaging.')
+        patches = queue_patches(queue.branch, tree.last_revision())
+        self.assertEqual(['return-2.patch'], [name for (name, p) in patches])
+        self.assertNotContainsRe(patches[0][1], b'debian/rules')
+
+    def test_non_utf8(self):
+        tree = self.make_packaging_tree([])
+        queue, revids = self.make_queue(tree)
+        self.build_tree_contents([('queue/latin1.txt', b'caf\xe9\n')])
+        queue.add(['latin1.txt'])
+        queue.commit('Add latin1.txt.')
+        patches = queue_patches(queue.branch, tree.last_revision())
+        self.assertEqual(
+            ['add-latin1-txt.patch'], [name for (name, p) in patches])
+        self.assertContainsRe(patches[0][1], b'\n\\+caf\xe9\n')
+        # The patch can be imported again.
+        self.addCleanup(tree.lock_tree_write().unlock)
+        export_patch_queue(tree, queue.branch, tree.last_revision())
+        tree.commit('Add the patch.')
+        queue, revids = self.make_queue(tree, 'queue2')
+        self.assertEqual(1, len(revids))
+        self.assertFileEqual(b'caf\xe9\n', 'queue2/latin1.txt')
+
+    def test_export(self):
+        tree = self.make_packaging_tree([
+            ('fix-build.patch', FIX_BUILD_PATCH),
+            ('readme.patch', ADD_README_PATCH)])
+        queue, revids = self.make_queue(tree)
+        # Drop the last patch
+        uncommit(queue.branch, tree=queue)
+        queue.revert()
+        self.build_tree_contents([
+            ('queue/extra.c', 'extra\n')])
+        queue.add(['extra.c'])
+        queue.commit('Add extra.c.')
+        self.addCleanup(tree.lock_tree_write().unlock)
+        names = export_patch_queue(tree, queue.branch, tree.last_revision())
+        self.assertEqual(['fix-build.patch', 'add-extra-c.patch'], names)
+        self.assertFileEqual(
+            'fix-build.patch\nadd-extra-c.patch\n',
+            'packaging/debian/patches/series')
+        self.assertPathDoesNotExist('packaging/debian/patches/readme.patch')
+        self.assertFalse(tree.is_versioned('debian/patches/readme.patch'))
+        self.assertTrue(tree.is_versioned('debian/patches/add-extra-c.patch'))
+
+    def test_distiller(self):
+        tree = self.make_packaging_tree([('fix-build.patch', FIX_BUILD_PATCH)])
+        queue, revids = self.make_queue(tree)
+        self.build_tree_contents([
+            ('queue/extra.c', 'extra\n')])
+        queue.add(['extra.c'])
+        queue.commit('Add extra.c.')
+        distiller = PatchQueueDistiller(
+            NativeSourceDistiller(tree, ''), queue.branch,
+            tree.last_revision())
+        distiller.distill('target')
+        self.assertFileEqual(
+            'fix-build.patch\nadd-extra-c.patch\n',
+            'target/debian/patches/series')
+        self.assertPathExists('target/debian/patches/add-extra-c.patch')
+        # The patches are not applied
+        self.assertPathDoesNotExist('target/extra.c')
+        self.assertFileEqual('int main() { return 1; }\n', 'target/hello.c')
+        self.assertPathDoesNotExist('target/.pc')
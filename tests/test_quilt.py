@@ -0,0 +1,124 @@
+#    test_quilt.py -- Tests for running quilt on the patch series
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+import os
+
+from ....tests import (
+    TestCaseInTempDir,
+    )
+
+from ..quilt import (
+    QuiltError,
+    UnknownQuiltAction,
+    copy_patches,
+    quilt_action,
+    update_patches_versioning,
+    )
+
+from . import (
+    ExecutableFeature,
+    TestCaseWithTransport,
+    )
+
+
+QuiltFeature = ExecutableFeature('quilt')
+
+
+class CopyPatchesTests(TestCaseInTempDir):
+
+    def test_copy(self):
+        self.build_tree_contents([
+            ('source/',),
+            ('source/series', 'a.patch\nsub/b.patch\n'),
+            ('source/a.patch', 'new a'),
+            ('source/sub/',),
+            ('source/sub/b.patch', 'b'),
+            ('target/',),
+            ('target/series', 'a.patch\nold.patch\n'),
+            ('target/a.patch', 'old a'),
+            ('target/old.patch', 'old'),
+            ])
+        copy_patches('source', 'target')
+        self.assertFileEqual('a.patch\nsub/b.patch\n', 'target/series')
+        self.assertFileEqual('new a', 'target/a.patch')
+        self.assertFileEqual('b', 'target/sub/b.patch')
+        self.assertPathDoesNotExist('target/old.patch')
+
+    def test_new_directory(self):
+        self.build_tree_contents([
+            ('source/',),
+            ('source/series', 'a.patch\n'),
+            ])
+        copy_patches('source', 'target')
+        self.assertFileEqual('a.patch\n', 'target/series')
+
+
+class UpdatePatchesVersioningTests(TestCaseWithTransport):
+
+    def test_add_and_remove(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('debian/',),
+            ('debian/patches/',),
+            ('debian/patches/series', 'old.patch\n'),
+            ('debian/patches/old.patch', 'old'),
+            ])
+        tree.smart_add(['debian'])
+        tree.commit('Add patches.')
+        self.build_tree_contents([
+            ('debian/patches/series', 'new.patch\n'),
+            ('debian/patches/new.patch', 'new'),
+            ])
+        self.build_tree(['unrelated'])
+        tree.lock_tree_write()
+        self.addCleanup(tree.unlock)
+        os.unlink('debian/patches/old.patch')
+        update_patches_versioning(tree, 'debian/patches')
+        self.assertTrue(tree.is_versioned('debian/patches/new.patch'))
+        self.assertFalse(tree.is_versioned('debian/patches/old.patch'))
+        self.assertFalse(tree.is_versioned('unrelated'))
+
+
+class QuiltActionTests(TestCaseInTempDir):
+
+    def test_unknown_action(self):
+        self.assertRaises(UnknownQuiltAction, quilt_action, 'foo', [], '.')
+
+    def test_new_then_push_nothing_to_do(self):
+        self.requireFeature(QuiltFeature)
+        self.build_tree_contents([
+            ('source/',),
+            ('source/a', 'a\n'),
+            ('source/debian/',),
+            ])
+        quilt_action('new', ['change-a.patch'], 'source')
+        # The new patch is applied already, so push has nothing to do
+        quilt_action('push', [], 'source')
+        self.assertFileEqual('change-a.patch\n', 'source/debian/patches/series')
+
+    def test_refresh_without_patch(self):
+        self.requireFeature(QuiltFeature)
+        self.build_tree_contents([
+            ('source/',),
+            ('source/debian/',),
+            ])
+        self.assertRaises(QuiltError, quilt_action, 'refresh', [], 'source')
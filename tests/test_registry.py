@@ -0,0 +1,179 @@
+#    test_registry.py -- Tests for fetching from language registries
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+import os
+import tarfile
+import zipfile
+
+from ....tests import (
+    TestCase,
+    TestCaseInTempDir,
+    )
+
+from ..registry import (
+    RegistryFetchFailed,
+    fetch_go_module,
+    fetch_pypi_sdist,
+    go_import_path_from_package,
+    go_module_escape,
+    go_module_version,
+    pypi_version,
+    python_project_from_package,
+    registry_from_package,
+    )
+
+
+def make_go_module(proxy_dir, import_path, version, files):
+    """Add a module zip to a directory laid out like a Go module proxy."""
+    module_dir = os.path.join(
+        proxy_dir, go_module_escape(import_path), '@v')
+    os.makedirs(module_dir)
+    with zipfile.ZipFile(os.path.join(
+            module_dir, go_module_escape(version) + '.zip'), 'w') as zf:
+        for name, contents in files.items():
+            zf.writestr('%s@%s/%s' % (import_path, version, name), contents)
+
+
+def make_sdist(mirror_dir, project, version, files, name=None):
+    """Add an sdist to a directory laid out like files.pythonhosted.org."""
+    if name is None:
+        name = project
+    sdist_dir = os.path.join(
+        mirror_dir, 'packages', 'source', project[0], project)
+    os.makedirs(sdist_dir)
+    basedir = '%s-%s' % (name, version)
+    with tarfile.open(os.path.join(
+            sdist_dir, basedir + '.tar.gz'), 'w:gz') as tf:
+        for path, contents in files.items():
+            full_path = os.path.join(basedir, path)
+            if not os.path.isdir(os.path.dirname(full_path)):
+                os.makedirs(os.path.dirname(full_path))
+            with open(full_path, 'w') as f:
+                f.write(contents)
+        tf.add(basedir)
+
+
+class RegistryFromPackageTests(TestCase):
+
+    def test_go(self):
+        self.assertEqual('go', registry_from_package('golang-github-foo-bar'))
+
+    def test_python(self):
+        self.assertEqual('pypi', registry_from_package('python3-foo'))
+        self.assertEqual('pypi', registry_from_package('python-foo'))
+
+    def test_other(self):
+        self.assertIs(None, registry_from_package('rust-foo'))
+        self.assertIs(None, registry_from_package('foo'))
+
+
+class GoNamesTests(TestCase):
+
+    def test_import_path_github(self):
+        self.assertEqual(
+            'github.com/foo/bar-baz',
+            go_import_path_from_package('golang-github-foo-bar-baz'))
+
+    def test_import_path_golang_x(self):
+        self.assertEqual(
+            'golang.org/x/text',
+            go_import_path_from_package('golang-golang-x-text'))
+
+    def test_import_path_unknown(self):
+        self.assertIs(None, go_import_path_from_package('golang-foo'))
+        self.assertIs(None, go_import_path_from_package('rust-foo'))
+
+    def test_escape(self):
+        self.assertEqual(
+            'github.com/!burnt!sushi/toml',
+            go_module_escape('github.com/BurntSushi/toml'))
+
+    def test_version(self):
+        self.assertEqual('v1.2.0', go_module_version('1.2.0'))
+        self.assertEqual('v1.2.0-rc1', go_module_version('1.2.0~rc1+ds'))
+
+
+class PythonNamesTests(TestCase):
+
+    def test_project(self):
+        self.assertEqual('foo', python_project_from_package('python-foo'))
+        self.assertEqual('foo', python_project_from_package('python3-foo'))
+        self.assertIs(None, python_project_from_package('golang-foo'))
+
+    def test_version(self):
+        self.assertEqual('1.2rc1', pypi_version('1.2~rc1+dfsg'))
+
+
+class FetchGoModuleTests(TestCaseInTempDir):
+
+    def test_fetch(self):
+        make_go_module(
+            'proxy', 'github.com/Foo/bar', 'v1.0.0',
+            {'go.mod': 'module github.com/Foo/bar\n', 'bar.go': 'package bar\n'})
+        os.mkdir('target')
+        path = fetch_go_module(
+            os.path.abspath('proxy'), 'github.com/Foo/bar',
+            'golang-github-foo-bar', '1.0.0', 'target')
+        self.assertEqual(
+            os.path.join('target', 'golang-github-foo-bar_1.0.0.orig.tar.gz'),
+            path)
+        with tarfile.open(path) as tf:
+            self.assertEqual(
+                ['golang-github-foo-bar-1.0.0/bar.go',
+                 'golang-github-foo-bar-1.0.0/go.mod'],
+                sorted(tf.getnames()))
+
+    def test_missing(self):
+        os.mkdir('proxy')
+        os.mkdir('target')
+        self.assertRaises(
+            RegistryFetchFailed, fetch_go_module, os.path.abspath('proxy'),
+            'github.com/foo/bar', 'golang-github-foo-bar', '1.0.0', 'target')
+
+
+class FetchPyPISdistTests(TestCaseInTempDir):
+
+    def test_fetch(self):
+        make_sdist('mirror', 'foo', '1.0', {'setup.py': ''})
+        os.mkdir('target')
+        path = fetch_pypi_sdist(
+            os.path.abspath('mirror'), 'foo', 'python-foo', '1.0', 'target')
+        self.assertEqual(
+            os.path.join('target', 'python-foo_1.0.orig.tar.gz'), path)
+        with tarfile.open(path) as tf:
+            self.assertIn('foo-1.0/setup.py', tf.getnames())
+
+    def test_fetch_normalized_name(self):
+        make_sdist('mirror', 'foo-bar', '1.0', {'setup.py': ''},
+                   name='foo_bar')
+        os.mkdir('target')
+        path = fetch_pypi_sdist(
+            os.path.abspath('mirror'), 'foo-bar', 'python-foo-bar', '1.0',
+            'target')
+        self.assertPathExists(path)
+
+    def test_missing(self):
+        os.mkdir('mirror')
+        os.mkdir('target')
+        self.assertRaises(
+            RegistryFetchFailed, fetch_pypi_sdist, os.path.abspath('mirror'),
+            'foo', 'python-foo', '1.0', 'target')
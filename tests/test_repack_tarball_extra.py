@@ -137,3 +137,33 @@ class TestRepackTarballExtra(TestCaseInTempDir):
             bz2_tarball_name, target_dir=target_dir)
         self.assertPathExists(bz2_tarball_name)
         self.assertPathExists(os.path.join(target_dir, bz2_tarball_name))
+
+    def test_copies_signature(self):
+        tarball_name = 'package-0.2.tar.gz'
+        create_basedir('package-0.2/', files=['README'])
+        make_new_upstream_tarball_gz(tarball_name)
+        self.build_tree_contents([(tarball_name + '.asc', b'signature')])
+        repack_tarball(
+            tarball_name, 'package_0.2.orig.tar.gz', target_dir='target')
+        self.assertFileEqual(
+            b'signature', 'target/package_0.2.orig.tar.gz.asc')
+
+    def test_copies_signature_same(self):
+        tarball_name = 'package-0.2.tar.gz'
+        create_basedir('package-0.2/', files=['README'])
+        make_new_upstream_tarball_gz(tarball_name)
+        self.build_tree_contents([(tarball_name + '.asc', b'signature')])
+        os.mkdir('target')
+        shutil.copy(tarball_name, 'target')
+        repack_tarball(tarball_name, tarball_name, target_dir='target')
+        self.assertFileEqual(b'signature', 'target/' + tarball_name + '.asc')
+
+    def test_repack_drops_signature(self):
+        bz2_tarball_name = 'package-0.2.tar.bz2'
+        create_basedir('package-0.2/', files=['README'])
+        make_new_upstream_tarball_bz2(bz2_tarball_name)
+        self.build_tree_contents([(bz2_tarball_name + '.asc', b'signature')])
+        repack_tarball(
+            bz2_tarball_name, 'package_0.2.orig.tar.gz', target_dir='target')
+        self.assertPathExists('target/package_0.2.orig.tar.gz')
+        self.assertPathDoesNotExist('target/package_0.2.orig.tar.gz.asc')
@@ -0,0 +1,124 @@
+#    test_reproducible.py -- Tests for reproducible.py
+#    Copyright (C) 2026 agent <agent@local>
+#
+#    This file is part of breezy-debian.
+#
+#    bzr-builddeb is free software; you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation; either version 2 of the License, or
+#    (at your option) any later version.
+#
+#    bzr-builddeb is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with bzr-builddeb; if not, write to the Free Software
+#    Foundation, Inc., 51 Franklin St, Fifth Floor, Boston, MA  02110-1301  USA
+#
+
+from __future__ import absolute_import
+
+import gzip
+import io
+import tarfile
+
+from debian.changelog import Changelog, Version
+
+from ....tests import TestCaseInTempDir
+
+from ..reproducible import (
+    changelog_timestamp,
+    check_reproducible,
+    compare_debs,
+    read_ar_members,
+    )
+
+
+def make_deb(path, files):
+    """Write a minimal binary package containing files."""
+    data = io.BytesIO()
+    with tarfile.open(fileobj=data, mode='w') as tf:
+        for name, (contents, mtime) in sorted(files.items()):
+            info = tarfile.TarInfo(name)
+            info.size = len(contents)
+            info.mtime = mtime
+            tf.addfile(info, io.BytesIO(contents))
+    members = [
+        ('debian-binary', b'2.0\n'),
+        ('data.tar.gz', gzip.compress(data.getvalue(), mtime=0)),
+        ]
+    with open(path, 'wb') as f:
+        f.write(b'!<arch>\n')
+        for name, contents in members:
+            f.write(('%-16s%-12d%-6d%-6d%-8s%-10d`\n' % (
+                name, 0, 0, 0, '100644', len(contents))).encode('ascii'))
+            f.write(contents)
+            if len(contents) % 2:
+                f.write(b'\n')
+
+
+class ChangelogTimestampTests(TestCaseInTempDir):
+
+    def test_timestamp(self):
+        changelog = Changelog()
+        changelog.new_block(
+            package='pkg', version=Version('0.1-1'),
+            distributions='unstable', urgency='low',
+            author='Joe Example <joe@example.com>',
+            date='Thu, 01 Jan 2026 12:00:00 +0100')
+        self.assertEqual(1767265200, changelog_timestamp(changelog))
+
+
+class CompareDebsTests(TestCaseInTempDir):
+
+    def test_read_ar_members(self):
+        make_deb('a.deb', {'usr/bin/foo': (b'foo', 0)})
+        self.assertEqual(
+            ['debian-binary', 'data.tar.gz'],
+            [name for (name, contents) in read_ar_members('a.deb')])
+
+    def test_identical(self):
+        make_deb('a.deb', {'usr/bin/foo': (b'foo', 0)})
+        make_deb('b.deb', {'usr/bin/foo': (b'foo', 0)})
+        self.assertEqual([], compare_debs('a.deb', 'b.deb'))
+
+    def test_different(self):
+        make_deb('a.deb', {
+            'usr/bin/foo': (b'foo', 0), 'usr/bin/bar': (b'bar', 0)})
+        make_deb('b.deb', {
+            'usr/bin/foo': (b'foo', 1), 'usr/bin/bar': (b'bar', 0)})
+        self.assertEqual(
+            [('data.tar.gz', ['usr/bin/foo'])],
+            compare_debs('a.deb', 'b.deb'))
+
+
+class CheckReproducibleTests(TestCaseInTempDir):
+
+    build_command = (
+        "printf 'Files:\\n 0123 4 misc optional pkg_0.1-1_all.deb\\n' "
+        "> ../pkg_0.1-1_all.changes && cp ../../../../$LC_ALL.deb "
+        "../pkg_0.1-1_all.deb")
+
+    def test_reproducible(self):
+        self.build_tree(['build/', 'build/pkg-0.1/'])
+        make_deb('C.UTF-8.deb', {'usr/bin/foo': (b'foo', 0)})
+        make_deb('fr_CH.UTF-8.deb', {'usr/bin/foo': (b'foo', 0)})
+        changes_path, differences = check_reproducible(
+            'build/pkg-0.1', 'build', self.build_command, 'pkg',
+            Version('0.1-1'))
+        self.assertEqual({}, differences)
+        self.assertEqual(
+            'build/reproducible/first/pkg_0.1-1_all.changes', changes_path)
+
+    def test_not_reproducible(self):
+        self.build_tree(['build/', 'build/pkg-0.1/'])
+        make_deb('C.UTF-8.deb', {'usr/bin/foo': (b'foo', 0)})
+        make_deb('fr_CH.UTF-8.deb', {'usr/bin/foo': (b'oof', 0)})
+        changes_path, differences = check_reproducible(
+            'build/pkg-0.1', 'build', self.build_command, 'pkg',
+            Version('0.1-1'))
+        self.assertEqual(
+            {'pkg_0.1-1_all.deb': [('data.tar.gz', ['usr/bin/foo'])]},
+            differences)
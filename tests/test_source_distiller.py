@@ -20,6 +20,8 @@
 from __future__ import absolute_import
 
 import os
+import shutil
+import tarfile
 
 from debian.changelog import Version
 
@@ -27,16 +29,23 @@ from ....errors import (
     FileExists,
     )
 
+from ..registry import UnknownPyPIProject
 from ..upstream import MissingUpstreamTarball
 from ..source_distiller import (
     FullSourceDistiller,
+    GoModuleDistiller,
     MergeModeDistiller,
     NativeSourceDistiller,
+    PyPIDistiller,
     )
 from . import (
     SourcePackageBuilder,
     TestCaseWithTransport,
     )
+from .test_registry import (
+    make_go_module,
+    make_sdist,
+    )
 from .test_upstream import (
     _MissingUpstreamProvider,
     _SimpleUpstreamProvider,
@@ -96,6 +105,86 @@ class NativeSourceDistillerTests(TestCaseWithTransport):
         self.assertPathExists('target/a')
         self.assertPathExists('target/b')
 
+    def test_distill_use_existing(self):
+        wt = self.make_branch_and_tree(".")
+        self.build_tree(['a', 'b', 'c'])
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        wt.add(['a', 'b', 'c'])
+        wt.commit("one")
+        sd = NativeSourceDistiller(wt, '', use_existing=True)
+        sd.distill('target')
+        self.build_tree(['target/build-stamp'])
+        self.build_tree_contents([('a', b'changed\n')])
+        wt.remove(['b'], keep_files=False)
+        self.build_tree(['d'])
+        wt.add(['d'])
+        wt.commit("two")
+        sd = NativeSourceDistiller(wt, '', use_existing=True)
+        sd.distill('target')
+        self.assertFileEqual(b'changed\n', 'target/a')
+        self.assertPathDoesNotExist('target/b')
+        self.assertPathExists('target/c')
+        self.assertPathExists('target/d')
+        # Files that weren't touched by the export are left alone
+        self.assertPathExists('target/build-stamp')
+
+    def test_distill_use_existing_uncommitted(self):
+        wt = self.make_branch_and_tree(".")
+        self.build_tree_contents([('a', b'original\n')])
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        wt.add(['a'])
+        wt.commit("one")
+        self.build_tree_contents([('a', b'uncommitted\n')])
+        sd = NativeSourceDistiller(wt, '', use_existing=True)
+        sd.distill('target')
+        self.assertFileEqual(b'uncommitted\n', 'target/a')
+        wt.revert()
+        sd = NativeSourceDistiller(wt, '', use_existing=True)
+        sd.distill('target')
+        self.assertFileEqual(b'original\n', 'target/a')
+
+    def test_distill_use_existing_no_record(self):
+        wt = self.make_branch_and_tree(".")
+        self.build_tree(['a', 'target/', 'target/stale'])
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        wt.add(['a'])
+        wt.commit("one")
+        sd = NativeSourceDistiller(wt, '', use_existing=True)
+        sd.distill('target')
+        self.assertPathExists('target/a')
+        self.assertPathDoesNotExist('target/stale')
+
+    def test_distill_no_record_without_reuse(self):
+        wt = self.make_branch_and_tree(".")
+        self.build_tree(['a'])
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        wt.add(['a'])
+        wt.commit("one")
+        self.build_tree_contents([('target.bzr-builddeb-export', b'stale\n')])
+        sd = NativeSourceDistiller(wt, '')
+        sd.distill('target')
+        self.assertPathExists('target/a')
+        self.assertPathDoesNotExist('target.bzr-builddeb-export')
+
+    def test_distill_use_existing_revision_tree(self):
+        wt = self.make_branch_and_tree(".")
+        self.build_tree(['a'])
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        wt.add(['a'])
+        revid = wt.commit("one")
+        tree = wt.branch.repository.revision_tree(revid)
+        sd = NativeSourceDistiller(tree, '', use_existing=True)
+        sd.distill('target')
+        self.build_tree(['target/build-stamp'])
+        sd.distill('target')
+        self.assertPathExists('target/a')
+        self.assertPathExists('target/build-stamp')
+
 
 class FullSourceDistillerTests(TestCaseWithTransport):
 
@@ -148,6 +237,27 @@ class FullSourceDistillerTests(TestCaseWithTransport):
         self.assertPathExists('target/a')
         self.assertPathExists('target/b')
 
+    def test_distill_use_existing(self):
+        wt = self.make_branch_and_tree(".")
+        self.build_tree(['a', 'b'])
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        wt.add(['a', 'b'])
+        wt.commit("one")
+        sd = FullSourceDistiller(
+            wt, '', _TouchUpstreamProvider('tarball'), use_existing=True)
+        sd.distill('target')
+        self.assertPathExists('target.bzr-builddeb-export')
+        self.build_tree_contents([('a', b'changed\n')])
+        wt.remove(['b'], keep_files=False)
+        wt.commit("two")
+        sd = FullSourceDistiller(
+            wt, '', _TouchUpstreamProvider('tarball'), use_existing=True)
+        sd.distill('target')
+        self.assertPathExists('tarball')
+        self.assertFileEqual(b'changed\n', 'target/a')
+        self.assertPathDoesNotExist('target/b')
+
 
 class MergeModeDistillerTests(TestCaseWithTransport):
 
@@ -240,6 +350,38 @@ class MergeModeDistillerTests(TestCaseWithTransport):
         self.assertPathDoesNotExist('target/.bzr-builddeb')
         self.assertPathDoesNotExist('target/b')
 
+    def make_component_tarball(self, name, version, component, paths):
+        basedir = "%s-%s" % (name, version.upstream_version)
+        self.build_tree([basedir + "/"] + [
+            "%s/%s" % (basedir, path) for path in paths])
+        if component is None:
+            filename = "%s_%s.orig.tar.gz" % (name, version.upstream_version)
+        else:
+            filename = "%s_%s.orig-%s.tar.gz" % (
+                name, version.upstream_version, component)
+        with tarfile.open(filename, "w:gz") as tf:
+            tf.add(basedir)
+        shutil.rmtree(basedir)
+
+    def test_distill_components(self):
+        wt = self.make_branch_and_tree('.')
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        self.build_tree(['debian/', 'debian/a'])
+        wt.add(['debian/', 'debian/a'])
+        name = "package"
+        version = Version("0.1-1")
+        self.make_component_tarball(
+            name, version, None, ["a", "extra/", "extra/placeholder"])
+        self.make_component_tarball(name, version, "extra", ["b"])
+        sd = MergeModeDistiller(
+            wt, '', _SimpleUpstreamProvider(name, version.upstream_version, "."))
+        sd.distill('target/foo')
+        self.assertPathExists('target/foo/a')
+        self.assertPathExists('target/foo/extra/b')
+        self.assertPathDoesNotExist('target/foo/extra/placeholder')
+        self.assertPathExists('target/foo/debian/a')
+
     def test_distill_use_existing(self):
         wt = self.make_branch_and_tree('.')
         wt.lock_write()
@@ -276,3 +418,111 @@ class MergeModeDistillerTests(TestCaseWithTransport):
         self.assertPathExists('target')
         self.assertPathExists('target/debian/a')
         self.assertPathExists('target/debian/b')
+
+
+_changelog = """\
+%s (%s) unstable; urgency=low
+
+  * Initial release.
+
+ -- Jane Doe <jane@example.com>  Thu, 03 Aug 2006 19:16:22 +0100
+"""
+
+
+class GoModuleDistillerTests(TestCaseWithTransport):
+
+    def make_packaging(self, package, version, control=None):
+        wt = self.make_branch_and_tree('.')
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        self.build_tree_contents(
+            [('debian/',), ('debian/changelog', _changelog % (package, version))])
+        wt.add(['debian', 'debian/changelog'])
+        if control is not None:
+            self.build_tree_contents([('debian/control', control)])
+            wt.add(['debian/control'])
+        return wt
+
+    def test_distill_fetches_module(self):
+        wt = self.make_packaging('golang-github-foo-bar', '1.0.0-1')
+        make_go_module(
+            'proxy', 'github.com/foo/bar', 'v1.0.0', {'bar.go': 'package bar\n'})
+        sd = GoModuleDistiller(
+            wt, '', _MissingUpstreamProvider(), 'golang-github-foo-bar',
+            '1.0.0', proxy=self.test_dir + '/proxy')
+        sd.distill('target/foo')
+        self.assertPathExists('target/golang-github-foo-bar_1.0.0.orig.tar.gz')
+        self.assertFileEqual('package bar\n', 'target/foo/bar.go')
+        self.assertPathExists('target/foo/debian/rules')
+        self.assertFileEqual('3.0 (quilt)\n', 'target/foo/debian/source/format')
+        with open('target/foo/debian/control') as f:
+            control = f.read()
+        self.assertIn('XS-Go-Import-Path: github.com/foo/bar\n', control)
+        self.assertIn('Maintainer: Jane Doe <jane@example.com>\n', control)
+
+    def test_distill_import_path_from_control(self):
+        control = (
+            'Source: golang-foo\n'
+            'XS-Go-Import-Path: example.com/foo\n')
+        wt = self.make_packaging('golang-foo', '1.0.0-1', control)
+        make_go_module(
+            'proxy', 'example.com/foo', 'v1.0.0', {'foo.go': 'package foo\n'})
+        sd = GoModuleDistiller(
+            wt, '', _MissingUpstreamProvider(), 'golang-foo', '1.0.0',
+            proxy=self.test_dir + '/proxy')
+        sd.distill('target/foo')
+        self.assertPathExists('target/foo/foo.go')
+        # The control file from the tree is kept.
+        self.assertFileEqual(control, 'target/foo/debian/control')
+        # An existing package keeps its source format.
+        self.assertPathDoesNotExist('target/foo/debian/source/format')
+
+    def test_distill_prefers_existing_tarball(self):
+        wt = self.make_packaging('golang-github-foo-bar', '0.1-1')
+        builder = SourcePackageBuilder(
+            'golang-github-foo-bar', Version('0.1-1'))
+        builder.add_upstream_file('a')
+        builder.add_default_control()
+        builder.build()
+        sd = GoModuleDistiller(
+            wt, '', _SimpleUpstreamProvider(
+                'golang-github-foo-bar', '0.1', '.'),
+            'golang-github-foo-bar', '0.1', proxy=self.test_dir + '/proxy')
+        sd.distill('target/foo')
+        self.assertPathExists('target/foo/a')
+
+
+class PyPIDistillerTests(TestCaseWithTransport):
+
+    def test_distill_fetches_sdist(self):
+        wt = self.make_branch_and_tree('.')
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        self.build_tree_contents(
+            [('debian/',),
+             ('debian/changelog', _changelog % ('python-foo-bar', '1.0-1'))])
+        wt.add(['debian', 'debian/changelog'])
+        make_sdist('mirror', 'foo-bar', '1.0', {'setup.py': ''})
+        sd = PyPIDistiller(
+            wt, '', _MissingUpstreamProvider(), 'python-foo-bar', '1.0',
+            project='foo-bar', mirror=self.test_dir + '/mirror')
+        sd.distill('target/foo')
+        self.assertPathExists('target/python-foo-bar_1.0.orig.tar.gz')
+        self.assertPathExists('target/foo/setup.py')
+        with open('target/foo/debian/rules') as f:
+            self.assertIn('PYBUILD_NAME=foo_bar\n', f.read())
+        with open('target/foo/debian/control') as f:
+            self.assertIn('Package: python3-foo-bar\n', f.read())
+
+    def test_distill_unknown_project(self):
+        wt = self.make_branch_and_tree('.')
+        wt.lock_write()
+        self.addCleanup(wt.unlock)
+        self.build_tree_contents(
+            [('debian/',),
+             ('debian/changelog', _changelog % ('python-debian', '1.0-1'))])
+        wt.add(['debian', 'debian/changelog'])
+        sd = PyPIDistiller(
+            wt, '', _MissingUpstreamProvider(), 'python-debian', '1.0',
+            mirror=self.test_dir + '/mirror')
+        self.assertRaises(UnknownPyPIProject, sd.distill, 'target/foo')
@@ -27,6 +27,8 @@ from __future__ import absolute_import
 from base64 import standard_b64encode
 
 import bz2
+from hashlib import md5, sha256
+from io import BytesIO
 import os
 import shutil
 import tempfile
@@ -44,6 +46,7 @@ from ....tests import (
     )
 from ....errors import NoSuchFile
 from ....tests.features import (
+    ExecutableFeature,
     ModuleAvailableFeature,
     PluginLoadedFeature,
     )
@@ -58,17 +61,26 @@ from . import (
     TestCaseWithTransport,
     )
 from ..upstream import (
+    CHECKSUMS_FILE,
+    ChecksumsFile,
     MissingUpstreamTarball,
     PackageVersionNotPresent,
     AptSource,
     StackedUpstreamSource,
     TarfileSource,
+    UpstreamChecksumMismatch,
     UpstreamProvider,
+    UpstreamSignatureInvalid,
+    UpstreamSignatureMissing,
+    UpstreamSigningKeys,
     UpstreamSource,
     extract_tarball_version,
     gather_orig_files,
     new_tarball_name,
     )
+from ..errors import (
+    MultipleUpstreamTarballsNotSupported,
+    )
 from ..util import (
     component_from_orig_tarball,
     )
@@ -86,6 +98,7 @@ from ..upstream.branch import (
 from ..upstream.pristinetar import (
     get_pristine_tar_source,
     is_upstream_tag,
+    pristine_tar_delta_sha256sum,
     revision_pristine_tar_format,
     revision_pristine_tar_delta,
     upstream_tag_version,
@@ -100,6 +113,7 @@ from ..upstream.uscan import (
 
 svn_plugin = ModuleAvailableFeature('breezy.plugins.svn.mapping')
 dulwich = ModuleAvailableFeature('dulwich')
+GpgvFeature = ExecutableFeature('gpgv')
 
 
 class MockSources(object):
@@ -304,6 +318,24 @@ class AptSourceTests(TestCase):
         self.assertEqual("apackage", sources.lookup_package)
         self.assertEqual(0, caller.called)
 
+    def test_get_checksums(self):
+        sources = MockSources(["0.1-1", "0.2-1"],
+            [[("abcd", 0, "apackage_0.1.orig.tar.gz", "tar")],
+             [("1234", 0, "apackage_0.2.orig.tar.gz", "tar"),
+              ("5678", 1, "apackage_0.2.orig-extra.tar.gz", "tar"),
+              ("9abc", 1, "apackage_0.2-1.debian.tar.xz", "diff")]])
+        apt_pkg = MockAptPkg(sources)
+        self.assertEqual({
+            "apackage_0.2.orig.tar.gz": [("md5", "1234", "the archive")],
+            "apackage_0.2.orig-extra.tar.gz": [
+                ("md5", "5678", "the archive")],
+            }, AptSource().get_checksums("apackage", "0.2", _apt_pkg=apt_pkg))
+
+    def test_get_checksums_no_package(self):
+        apt_pkg = MockAptPkg(MockSources([], []))
+        self.assertEqual(
+            {}, AptSource().get_checksums("apackage", "0.2", _apt_pkg=apt_pkg))
+
 
 class RecordingSource(UpstreamSource):
 
@@ -387,6 +419,240 @@ class StackedUpstreamSourceTests(TestCase):
         self.assertEquals([("pkg", "1.0", "bla")], a._specific_versions)
 
 
+class ChecksumsFileTests(TestCaseWithTransport):
+
+    def test_parse(self):
+        checksums = ChecksumsFile(
+            "# Checksums of the upstream tarballs\n"
+            "\n"
+            "%s  pkg_1.0.orig.tar.gz\n"
+            "%s *pkg_1.0.orig-extra.tar.xz\n"
+            "%s  pkg_1.1.orig.tar.gz\n"
+            "%s  pkg_1.0.orig.tar.gz\n"
+            "bogus\n" % ("a" * 64, "B" * 40, "c" * 128, "d" * 32),
+            "checksums")
+        self.assertEqual({
+            "pkg_1.0.orig.tar.gz": [
+                ("sha256", "a" * 64, "checksums"),
+                ("md5", "d" * 32, "checksums")],
+            "pkg_1.0.orig-extra.tar.xz": [("sha1", "b" * 40, "checksums")],
+            }, checksums.get_checksums("pkg", "1.0"))
+        self.assertEqual({
+            "pkg_1.1.orig.tar.gz": [("sha512", "c" * 128, "checksums")],
+            }, checksums.get_checksums("pkg", "1.1"))
+        self.assertEqual({}, checksums.get_checksums("otherpkg", "1.0"))
+
+    def test_from_tree(self):
+        tree = self.make_branch_and_tree('.')
+        self.assertIs(None, ChecksumsFile.from_tree(tree))
+        self.build_tree_contents([
+            ('debian/',), ('debian/upstream/',),
+            ('debian/upstream/orig-checksums',
+             "%s  pkg_1.0.orig.tar.gz\n" % ("a" * 64))])
+        tree.add(['debian', 'debian/upstream',
+                  'debian/upstream/orig-checksums'])
+        checksums = ChecksumsFile.from_tree(tree)
+        self.assertEqual(
+            'debian/upstream/orig-checksums', checksums.filename)
+        self.assertEqual(
+            ["pkg_1.0.orig.tar.gz"],
+            list(checksums.get_checksums("pkg", "1.0")))
+
+    def test_from_tree_top_level(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('upstream/',),
+            ('upstream/orig-checksums',
+             "%s  pkg_1.0.orig.tar.gz\n" % ("a" * 64))])
+        tree.add(['upstream', 'upstream/orig-checksums'])
+        self.assertIs(None, ChecksumsFile.from_tree(tree))
+        checksums = ChecksumsFile.from_tree(tree, top_level=True)
+        self.assertEqual('upstream/orig-checksums', checksums.filename)
+
+
+class ContentSource(UpstreamSource):
+
+    def __init__(self, content, checksums=None):
+        self._content = content
+        self._checksums = checksums or {}
+
+    def fetch_tarballs(self, package, version, target_dir, components=None):
+        path = self._tarball_path(package, version, None, target_dir)
+        with open(path, 'wb') as f:
+            f.write(self._content)
+        return [path]
+
+    def get_checksums(self, package, version):
+        return self._checksums
+
+
+class UpstreamProviderVerifyTests(TestCaseWithTransport):
+
+    def test_verified(self):
+        source = ContentSource(b"tarball", {
+            "pkg_1.0.orig.tar.gz": [
+                ("md5", md5(b"tarball").hexdigest(), "the archive")]})
+        checksums = ChecksumsFile(
+            "%s  pkg_1.0.orig.tar.gz\n" % sha256(b"tarball").hexdigest())
+        provider = UpstreamProvider(
+            "pkg", "1.0", "store", [source], checksum_sources=[checksums])
+        os.mkdir('target')
+        provider.provide('target')
+        self.assertEqual(
+            {"pkg_1.0.orig.tar.gz": "ContentSource"},
+            provider.tarball_sources)
+        self.assertEqual(
+            {"pkg_1.0.orig.tar.gz": ["the archive", CHECKSUMS_FILE]},
+            provider.verified_tarballs)
+
+    def test_slow_checksums_only_when_unknown(self):
+        class SlowSource(ContentSource):
+            slow_checksums = True
+            queried = 0
+
+            def get_checksums(self, package, version):
+                self.queried += 1
+                return ContentSource.get_checksums(self, package, version)
+        slow = SlowSource(b"tarball")
+        checksums = ChecksumsFile(
+            "%s  pkg_1.0.orig.tar.gz\n" % sha256(b"tarball").hexdigest())
+        provider = UpstreamProvider(
+            "pkg", "1.0", "store", [slow], checksum_sources=[checksums])
+        self.assertEqual(
+            [CHECKSUMS_FILE],
+            [origin for (algorithm, hexdigest, origin) in
+             provider.known_checksums(["pkg_1.0.orig.tar.gz"])[
+                 "pkg_1.0.orig.tar.gz"]])
+        self.assertEqual(0, slow.queried)
+        provider.known_checksums(["pkg_1.0.orig-docs.tar.gz"])
+        self.assertEqual(1, slow.queried)
+
+    def test_no_known_checksums(self):
+        provider = UpstreamProvider(
+            "pkg", "1.0", "store", [ContentSource(b"tarball")])
+        os.mkdir('target')
+        provider.provide('target')
+        self.assertEqual(
+            {"pkg_1.0.orig.tar.gz": []}, provider.verified_tarballs)
+
+    def test_mismatch(self):
+        checksums = ChecksumsFile(
+            "%s  pkg_1.0.orig.tar.gz\n" % sha256(b"other").hexdigest())
+        provider = UpstreamProvider(
+            "pkg", "1.0", "store", [ContentSource(b"tarball")],
+            checksum_sources=[checksums])
+        os.mkdir('target')
+        e = self.assertRaises(
+            UpstreamChecksumMismatch, provider.provide, 'target')
+        self.assertEqual("pkg_1.0.orig.tar.gz", e.filename)
+        self.assertEqual("sha256", e.algorithm)
+        self.assertEqual(sha256(b"tarball").hexdigest(), e.actual)
+        self.assertEqual("ContentSource", e.source)
+        self.assertEqual(CHECKSUMS_FILE, e.origin)
+        self.assertFalse(os.path.exists('target/pkg_1.0.orig.tar.gz'))
+
+    def test_mismatch_in_store(self):
+        os.mkdir('store')
+        self.build_tree_contents([('store/pkg_1.0.orig.tar.gz', b"other")])
+        provider = UpstreamProvider(
+            "pkg", "1.0", "store", [ContentSource(b"tarball", {
+                "pkg_1.0.orig.tar.gz": [
+                    ("md5", md5(b"tarball").hexdigest(), "the archive")]})])
+        os.mkdir('target')
+        e = self.assertRaises(
+            UpstreamChecksumMismatch, provider.provide, 'target')
+        self.assertEqual("store directory", e.source)
+        self.assertEqual("the archive", e.origin)
+
+    def test_signature(self):
+        class SigningKeys(object):
+            def verify(self, path):
+                return os.path.exists(path + '.asc')
+        source = ContentSource(b"tarball")
+        os.mkdir('store')
+        source.fetch_tarballs("pkg", "1.0", "store")
+        self.build_tree_contents(
+            [('store/pkg_1.0.orig.tar.gz.asc', b"signature")])
+        provider = UpstreamProvider(
+            "pkg", "1.0", "store", [source], signing_keys=SigningKeys())
+        os.mkdir('target')
+        provider.provide('target')
+        self.assertEqual(
+            {"pkg_1.0.orig.tar.gz": ["the upstream signature"]},
+            provider.verified_tarballs)
+        self.assertFileEqual(
+            b"signature", 'target/pkg_1.0.orig.tar.gz.asc')
+
+
+class UpstreamSigningKeysTests(TestCaseWithTransport):
+
+    def test_from_tree(self):
+        tree = self.make_branch_and_tree('.')
+        self.assertIs(None, UpstreamSigningKeys.from_tree(tree))
+        self.build_tree_contents([
+            ('debian/',), ('debian/upstream/',),
+            ('debian/upstream/signing-key.pgp', b"binary key"),
+            ('debian/upstream/signing-key.asc', b"armored key")])
+        tree.add(['debian', 'debian/upstream',
+                  'debian/upstream/signing-key.pgp',
+                  'debian/upstream/signing-key.asc'])
+        keys = UpstreamSigningKeys.from_tree(tree)
+        self.assertEqual('debian/upstream/signing-key.asc', keys.filename)
+        self.assertEqual(b"armored key", keys.keys)
+
+    def test_from_tree_top_level(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('upstream-signing-key.pgp', b"binary key")])
+        tree.add(['upstream-signing-key.pgp'])
+        self.assertIs(None, UpstreamSigningKeys.from_tree(tree))
+        keys = UpstreamSigningKeys.from_tree(tree, top_level=True)
+        self.assertEqual('upstream-signing-key.pgp', keys.filename)
+
+    def test_from_tree_watch_file(self):
+        tree = self.make_branch_and_tree('.')
+        self.build_tree_contents([
+            ('debian/',), ('debian/upstream/',),
+            ('debian/upstream/signing-key.asc', b"armored key"),
+            ('debian/watch',
+             b"version=4\nopts=pgpsigurlmangle=s/$/.asc/ "
+             b"https://example.com/pkg-(.*)\\.tar\\.gz\n")])
+        tree.add(['debian', 'debian/upstream',
+                  'debian/upstream/signing-key.asc', 'debian/watch'])
+        keys = UpstreamSigningKeys.from_tree(tree)
+        self.assertEqual('debian/watch', keys.watch_file)
+        self.build_tree_contents([
+            ('debian/watch',
+             b"version=4\nhttps://example.com/pkg-(.*)\\.tar\\.gz\n")])
+        keys = UpstreamSigningKeys.from_tree(tree)
+        self.assertIs(None, keys.watch_file)
+
+    def test_verify_unsigned(self):
+        self.build_tree_contents([('pkg_1.0.orig.tar.gz', b"tarball")])
+        keys = UpstreamSigningKeys(b"binary key", "signing-key.pgp")
+        self.assertFalse(keys.verify('pkg_1.0.orig.tar.gz'))
+
+    def test_verify_unsigned_required(self):
+        self.build_tree_contents([('pkg_1.0.orig.tar.gz', b"tarball")])
+        keys = UpstreamSigningKeys(
+            b"binary key", "signing-key.pgp", "debian/watch")
+        e = self.assertRaises(
+            UpstreamSignatureMissing, keys.verify, 'pkg_1.0.orig.tar.gz')
+        self.assertEqual("pkg_1.0.orig.tar.gz", e.filename)
+        self.assertEqual("debian/watch", e.watch_file)
+
+    def test_verify_invalid(self):
+        self.requireFeature(GpgvFeature)
+        self.build_tree_contents([
+            ('pkg_1.0.orig.tar.gz', b"tarball"),
+            ('pkg_1.0.orig.tar.gz.asc', b"not a signature")])
+        keys = UpstreamSigningKeys(b"binary key", "signing-key.pgp")
+        e = self.assertRaises(
+            UpstreamSignatureInvalid, keys.verify, 'pkg_1.0.orig.tar.gz')
+        self.assertEqual("pkg_1.0.orig.tar.gz", e.filename)
+        self.assertEqual("signing-key.pgp", e.keyring)
+
+
 class GuessUpstreamRevspecTests(TestCase):
 
     def test_guess_upstream_revspec(self):
@@ -508,6 +774,12 @@ class UpstreamBranchSourceTests(TestCaseWithTransport):
         self.assertEquals(revid2,
             source.version_as_revision("foo", u"2.1+bzr2"))
         self.assertEquals({None: revid1}, source.version_as_revisions("foo", u"2.1"))
+        self.assertEquals({None: revid1}, source.version_as_revisions(
+            "foo", u"2.1", [("foo_2.1.orig.tar.gz", None, "somemd5sum")]))
+        self.assertRaises(MultipleUpstreamTarballsNotSupported,
+            source.version_as_revisions, "foo", u"2.1",
+            [("foo_2.1.orig.tar.gz", None, "somemd5sum"),
+             ("foo_2.1.orig-lib.tar.gz", "lib", "othermd5sum")])
 
     def test_version_as_revision(self):
         revid1 = self.tree.commit("msg")
@@ -953,6 +1225,17 @@ class TestUpstreamTagVersion(TestCase):
             upstream_tag_version('upstream-debian-2.1/lib'))
 
 
+def make_pristine_tar_delta(files):
+    f = BytesIO()
+    with tarfile.open(fileobj=f, mode='w:gz') as tar:
+        for name, contents in files.items():
+            contents = contents.encode('ascii')
+            info = tarfile.TarInfo(name)
+            info.size = len(contents)
+            tar.addfile(info, BytesIO(contents))
+    return f.getvalue()
+
+
 class GenericPristineTarSourceTests(TestCase):
 
     def test_pristine_tar_format_gz(self):
@@ -985,6 +1268,19 @@ class GenericPristineTarSourceTests(TestCase):
         rev.properties[u"deb-pristine-delta"] = standard_b64encode(b"bla")
         self.assertEquals(b"bla", revision_pristine_tar_delta(rev))
 
+    def test_pristine_tar_delta_sha256sum(self):
+        self.assertEqual(
+            "a" * 64,
+            pristine_tar_delta_sha256sum(
+                make_pristine_tar_delta({'sha256sum': "a" * 64 + "\n"})))
+
+    def test_pristine_tar_delta_sha256sum_missing(self):
+        self.assertIs(
+            None,
+            pristine_tar_delta_sha256sum(
+                make_pristine_tar_delta({'version': "2\n"})))
+        self.assertIs(None, pristine_tar_delta_sha256sum(b"bla"))
+
 
 class GitPristineTarSourceTests(TestCaseWithTransport):
 
@@ -1177,6 +1473,16 @@ class BzrPristineTarSourceTests(TestCaseWithTransport):
             ("upstream_2.1.orig.tar.gz", None, "somemd5sum"),
             ("upstream_2.1.orig-lib.tar.gz", "lib", "othermd5sum")]))
 
+    def test_version_as_revisions_tagged_components(self):
+        revid1 = self.tree.commit("msg")
+        revid2 = self.tree.commit("msg")
+        revid3 = self.tree.commit("msg")
+        self.tree.branch.tags.set_tag("upstream-2.1", revid1)
+        self.tree.branch.tags.set_tag("upstream-2.1/lib", revid2)
+        self.tree.branch.tags.set_tag("upstream-2.2/lib", revid3)
+        self.assertEquals({None: revid1, "lib": revid2},
+            self.source.version_as_revisions(None, "2.1"))
+
     def test_version_as_revisions_partially_missing(self):
         revid1 = self.tree.commit("msg")
         self.tree.branch.tags.set_tag("upstream-2.1", revid1)
@@ -1203,6 +1509,27 @@ class BzrPristineTarSourceTests(TestCaseWithTransport):
             ("upstream_2.1.orig.tar.gz", None, "somemd5sum"),
             ("upstream_2.1.orig-lib.tar.gz", "lib", "othermd5sum")]))
 
+    def test_get_checksums(self):
+        delta = make_pristine_tar_delta({'sha256sum': "a" * 64 + "\n"})
+        revid1 = self.tree.commit("msg", revprops={
+            "deb-md5": "somemd5sum",
+            "deb-pristine-delta-xz":
+                standard_b64encode(delta).decode('ascii')})
+        revid2 = self.tree.commit(
+            "msg", revprops={"deb-md5": "othermd5sum"})
+        self.tree.branch.tags.set_tag("upstream-2.1", revid1)
+        self.tree.branch.tags.set_tag("upstream-2.1/lib", revid2)
+        self.assertEqual({
+            "pkg_2.1.orig.tar.xz": [
+                ("md5", "somemd5sum", "the imported revision"),
+                ("sha256", "a" * 64, "the pristine-tar delta")],
+            "pkg_2.1.orig-lib.tar.gz": [
+                ("md5", "othermd5sum", "the imported revision")],
+            }, self.source.get_checksums("pkg", "2.1"))
+
+    def test_get_checksums_missing(self):
+        self.assertEqual({}, self.source.get_checksums("pkg", "2.1"))
+
 
 
 
@@ -46,12 +46,15 @@ from ..util import (
     InconsistentSourceFormatError,
     NoPreviousUpload,
     changelog_find_previous_upload,
+    changelog_target_distribution,
     component_from_orig_tarball,
+    config_targets,
     dget,
     dget_changes,
     extract_orig_tarballs,
     find_bugs_fixed,
     find_changelog,
+    find_changes_files,
     find_extra_authors,
     find_thanks,
     get_build_architecture,
@@ -320,6 +323,23 @@ class LookupDistributionTests(SuiteToDistributionTests):
         self.lookup_ubuntu("Ubuntu")
 
 
+class ConfigTargetsTests(TestCase):
+
+    _test_needs_features = [DistroInfoFeature]
+
+    def test_suite(self):
+        self.assertEqual(
+            ['bookworm-backports', 'debian'],
+            config_targets('bookworm-backports'))
+        self.assertEqual(['noble', 'ubuntu'], config_targets('noble'))
+
+    def test_vendor(self):
+        self.assertEqual(['debian'], config_targets('debian'))
+
+    def test_unknown(self):
+        self.assertEqual(['not-a-target'], config_targets('not-a-target'))
+
+
 class MoveFileTests(TestCaseInTempDir):
 
     def test_move_file_non_extant(self):
@@ -722,6 +742,32 @@ class FindPreviousUploadTests(TestCase):
         self.assertEqual(Version("0.1-1"), changelog_find_previous_upload(cl))
 
 
+class ChangelogTargetDistributionTests(TestCase):
+
+    def make_changelog(self, versions_and_distributions):
+        cl = Changelog()
+        author = "J. Maintainer <maint@example.com>"
+        for version, distro in versions_and_distributions:
+            cl.new_block(changes=["  * Something"], author=author,
+                         distributions=distro, version=version)
+        return cl
+
+    def test_released(self):
+        cl = self.make_changelog(
+            [("0.1-1", "unstable"), ("0.1-1~bpo12+1", "bookworm-backports")])
+        self.assertEqual(
+            "bookworm-backports", changelog_target_distribution(cl))
+
+    def test_unreleased(self):
+        cl = self.make_changelog(
+            [("0.1-1", "noble"), ("0.1-2", "UNRELEASED")])
+        self.assertEqual("noble", changelog_target_distribution(cl))
+
+    def test_only_unreleased(self):
+        cl = self.make_changelog([("0.1-1", "UNRELEASED")])
+        self.assertIs(None, changelog_target_distribution(cl))
+
+
 class SourceFormatTests(TestCaseWithTransport):
 
     def test_no_source_format_file(self):
@@ -907,6 +953,35 @@ class TestExtractOrigTarballs(TestCaseInTempDir):
             sorted(os.listdir("target")),
             sorted(["README", "extra"]))
 
+    def test_multiple_tarballs_main_last(self):
+        base_tar_path = self.create_tarball("package", "0.1", "bz2")
+        tar_path_extra = self.create_tarball(
+            "package", "0.1", "bz2", part="extra")
+        os.mkdir("target")
+        extract_orig_tarballs(
+            [(tar_path_extra, "extra"), (base_tar_path, None)], "target",
+            strip_components=1)
+        self.assertEquals(
+            sorted(os.listdir("target")),
+            sorted(["README", "extra"]))
+        self.assertEquals(os.listdir("target/extra"), ["README"])
+
+    def test_component_replaces_directory(self):
+        self.build_tree(
+            ["package-0.1/", "package-0.1/README", "package-0.1/extra/",
+             "package-0.1/extra/placeholder"])
+        base_tar_path = os.path.abspath("package_0.1.orig.tar.gz")
+        with tarfile.open(base_tar_path, "w:gz") as tf:
+            tf.add("package-0.1")
+        shutil.rmtree("package-0.1")
+        tar_path_extra = self.create_tarball(
+            "package", "0.1", "gz", part="extra")
+        os.mkdir("target")
+        extract_orig_tarballs(
+            [(base_tar_path, None), (tar_path_extra, "extra")], "target",
+            strip_components=1)
+        self.assertEquals(os.listdir("target/extra"), ["README"])
+
 
 class ComponentFromOrigTarballTests(TestCase):
 
@@ -987,6 +1062,27 @@ class BuildArchitectureTests(TestCase):
         self.assertIsInstance(get_build_architecture(), text_type)
 
 
+class FindChangesFilesTests(TestCaseInTempDir):
+
+    def test_find_all(self):
+        self.build_tree([
+            'pkg_1.0-1_source.changes', 'pkg_1.0-1_armhf.changes',
+            'pkg_1.0-1_amd64.changes', 'other_1.0-1_amd64.changes'])
+        self.assertEqual(
+            ['amd64', 'armhf', 'source'],
+            sorted(kind for (kind, entry) in find_changes_files(
+                '.', 'pkg', Version('1:1.0-1'))))
+
+    def test_find_architecture(self):
+        self.build_tree([
+            'pkg_1.0-1_source.changes', 'pkg_1.0-1_armhf.changes',
+            'pkg_1.0-1_amd64.changes'])
+        self.assertEqual(
+            [('armhf', 'pkg_1.0-1_armhf.changes')],
+            [(kind, entry.name) for (kind, entry) in find_changes_files(
+                '.', 'pkg', Version('1.0-1'), architecture='armhf')])
+
+
 class FilesExcludedTests(TestCaseWithTransport):
 
     def test_file_missing(self):
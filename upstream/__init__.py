@@ -34,6 +34,7 @@ from ....errors import BzrError, DependencyNotPresent, NoSuchFile
 from .... import osutils
 from ....export import export
 from ....trace import (
+    mutter,
     note,
     warning,
     )
@@ -43,6 +44,7 @@ from ..repack_tarball import (
     repack_tarball,
     )
 from ..util import (
+    checksum_filename,
     component_from_orig_tarball,
     tarball_name,
     )
@@ -56,6 +58,34 @@ class PackageVersionNotPresent(BzrError):
                           upstream=upstream)
 
 
+class UpstreamChecksumMismatch(BzrError):
+    _fmt = ("The %(algorithm)s checksum of %(filename)s obtained from "
+            "%(source)s is %(actual)s, but %(origin)s has %(expected)s.")
+
+    def __init__(self, filename, algorithm, expected, actual, source,
+                 origin):
+        BzrError.__init__(self, filename=filename, algorithm=algorithm,
+                          expected=expected, actual=actual, source=source,
+                          origin=origin)
+
+
+class UpstreamSignatureInvalid(BzrError):
+    _fmt = ("Unable to verify the signature of %(filename)s with the keys "
+            "in %(keyring)s: %(error)s")
+
+    def __init__(self, filename, keyring, error):
+        BzrError.__init__(self, filename=filename, keyring=keyring,
+                          error=error)
+
+
+class UpstreamSignatureMissing(BzrError):
+    _fmt = ("%(filename)s has no signature, but %(watch_file)s says that "
+            "upstream signs its releases.")
+
+    def __init__(self, filename, watch_file):
+        BzrError.__init__(self, filename=filename, watch_file=watch_file)
+
+
 class MissingUpstreamTarball(BzrError):
     _fmt = ("Unable to find the needed upstream tarball for package "
             "%(package)s, version %(version)s.")
@@ -77,6 +107,10 @@ def new_tarball_name(package, version, old_name):
 class UpstreamSource(object):
     """A source for upstream versions (uscan, debian/rules, etc)."""
 
+    # Whether get_checksums is slow, e.g. because it has to query apt; it is
+    # then only called for tarballs no other source knows the checksums of.
+    slow_checksums = False
+
     def get_latest_version(self, package, current_version):
         """Check what the latest upstream version is.
 
@@ -130,6 +164,16 @@ class UpstreamSource(object):
         """
         raise NotImplementedError(self.fetch_tarballs)
 
+    def get_checksums(self, package, version):
+        """Retrieve the known checksums of the tarballs for a version.
+
+        :param package: Name of the package
+        :param version: Version string
+        :return: dictionary mapping tarball basenames to lists of
+            (algorithm, hexdigest, origin) tuples
+        """
+        return {}
+
     def _tarball_path(self, package, version, component, target_dir,
                       format=None):
         return os.path.join(
@@ -140,6 +184,8 @@ class UpstreamSource(object):
 class AptSource(UpstreamSource):
     """Upstream source that uses apt-source."""
 
+    slow_checksums = True
+
     def fetch_tarballs(self, package, upstream_version, target_dir,
                        _apt_pkg=None, components=None):
         if _apt_pkg is None:
@@ -179,6 +225,33 @@ class AptSource(UpstreamSource):
         note("apt could not find %s/%s.", package, upstream_version)
         raise PackageVersionNotPresent(package, upstream_version, self)
 
+    def get_checksums(self, package, upstream_version, _apt_pkg=None):
+        if _apt_pkg is None:
+            try:
+                import apt_pkg
+            except ImportError as e:
+                raise DependencyNotPresent('apt_pkg', e)
+        else:
+            apt_pkg = _apt_pkg
+        apt_pkg.init()
+        try:
+            sources = apt_pkg.SourceRecords()
+        except SystemError:
+            return {}
+        ret = {}
+        sources.restart()
+        while sources.lookup(package):
+            for (checksum, size, filename, filekind) in sources.files:
+                if filekind != "tar":
+                    continue
+                filename = os.path.basename(filename)
+                if filename.startswith(
+                        "%s_%s.orig" % (package, upstream_version)):
+                    entry = ('md5', checksum, 'the archive')
+                    if entry not in ret.setdefault(filename, []):
+                        ret[filename].append(entry)
+        return ret
+
     def _get_command(self, package, version_str):
         return 'apt-get source -y --only-source --tar-only %s=%s' % \
             (package, version_str)
@@ -223,6 +296,9 @@ class StackedUpstreamSource(UpstreamSource):
 
     def __init__(self, sources):
         self._sources = sources
+        # Maps the basenames of fetched tarballs to the source that
+        # provided them.
+        self.fetched_from = {}
 
     def __repr__(self):
         return "%s(%r)" % (self.__class__.__name__, self._sources)
@@ -238,6 +314,8 @@ class StackedUpstreamSource(UpstreamSource):
                 warning('not checking %r due to missing dependency: %s',
                         source, e)
             else:
+                for path in paths:
+                    self.fetched_from[os.path.basename(path)] = source
                 return paths
         raise PackageVersionNotPresent(package, version, self)
 
@@ -276,6 +354,67 @@ class StackedUpstreamSource(UpstreamSource):
         return False
 
 
+CHECKSUMS_FILE = 'upstream/orig-checksums'
+
+# Hash algorithms in checksums files, by the length of their hex digests.
+CHECKSUM_ALGORITHMS = {32: 'md5', 40: 'sha1', 64: 'sha256', 128: 'sha512'}
+
+
+class ChecksumsFile(object):
+    """Known checksums of upstream tarballs, kept in the packaging branch.
+
+    The file is in the format of sha256sum(1) and friends; the hash
+    algorithm of each line is determined from the length of the digest.
+    """
+
+    def __init__(self, text, filename=CHECKSUMS_FILE):
+        self.filename = filename
+        self.checksums = {}
+        for line in text.splitlines():
+            line = line.strip()
+            if not line or line.startswith('#'):
+                continue
+            try:
+                (digest, name) = line.split(None, 1)
+            except ValueError:
+                warning('Ignoring malformed line in %s: %s', filename, line)
+                continue
+            algorithm = CHECKSUM_ALGORITHMS.get(len(digest))
+            if algorithm is None:
+                warning('Ignoring malformed line in %s: %s', filename, line)
+                continue
+            # sha256sum marks files that were read in binary mode with '*'.
+            name = os.path.basename(name.lstrip('*'))
+            self.checksums.setdefault(name, []).append(
+                (algorithm, digest.lower(), filename))
+
+    @classmethod
+    def from_tree(cls, tree, subpath='', top_level=False):
+        """Load the checksums file from a packaging tree.
+
+        :return: a ChecksumsFile, or None if the tree does not have one
+        """
+        if top_level:
+            path = CHECKSUMS_FILE
+        else:
+            path = 'debian/' + CHECKSUMS_FILE
+        if subpath:
+            path = osutils.pathjoin(subpath, path)
+        if not tree.has_filename(path):
+            return None
+        return cls(tree.get_file_text(path).decode('utf-8'), path)
+
+    def __repr__(self):
+        return "%s(%r)" % (self.__class__.__name__, self.filename)
+
+    def get_checksums(self, package, version):
+        prefix = "%s_%s.orig" % (package, version)
+        return dict(
+            (name, list(entries))
+            for (name, entries) in self.checksums.items()
+            if name.startswith(prefix))
+
+
 def gather_orig_files(package, version, path):
     """Grab the orig files for a particular package.
 
@@ -307,17 +446,31 @@ class UpstreamProvider(object):
     instance using pristine-tar, or using apt.
     """
 
-    def __init__(self, package, version, store_dir, sources):
+    def __init__(self, package, version, store_dir, sources,
+                 checksum_sources=None, signing_keys=None):
         """Create an UpstreamProvider.
 
         :param package: the name of the source package that is being built.
         :param version: the Version of the package that is being built.
         :param store_dir: A directory to cache the tarballs.
+        :param checksum_sources: Additional objects with a get_checksums
+            method, such as a ChecksumsFile, that only provide the known
+            checksums of the tarballs.
+        :param signing_keys: An UpstreamSigningKeys to verify the
+            signatures of the tarballs with, if they have one.
         """
         self.package = package
         self.version = version
         self.store_dir = os.path.abspath(store_dir)
         self.source = StackedUpstreamSource(sources)
+        self.checksum_sources = list(sources) + list(checksum_sources or [])
+        self.signing_keys = signing_keys
+        # Maps the basenames of the provided tarballs to a description of
+        # where they were obtained from.
+        self.tarball_sources = {}
+        # Maps the basenames of the provided tarballs to the descriptions
+        # of the known checksums they were verified against.
+        self.verified_tarballs = {}
 
     def provide(self, target_dir):
         """Provide the upstream tarball(s) any way possible.
@@ -337,7 +490,10 @@ class UpstreamProvider(object):
              the tarball.
 
         If the tarball can't be found at all then MissingUpstreamTarball
-        will be raised.
+        will be raised. The tarballs are checked against the checksums that
+        are known for them, and UpstreamChecksumMismatch is raised if one
+        of them does not match. If signing keys were given, the signatures
+        of the tarballs are verified as well.
 
         :param target_dir: The directory to place the tarball in.
         :return: The path to the tarball.
@@ -347,6 +503,9 @@ class UpstreamProvider(object):
         if in_target is not None:
             note("Upstream tarball already exists in build directory, "
                  "using that")
+            for p in in_target:
+                self.tarball_sources[os.path.basename(p)] = "build directory"
+            self.verify(in_target)
             return [
                 (p, component_from_orig_tarball(p, self.package, self.version))
                 for p in in_target]
@@ -359,14 +518,93 @@ class UpstreamProvider(object):
             except PackageVersionNotPresent:
                 raise MissingUpstreamTarball(self.package, self.version)
             assert isinstance(paths, list)
+            for p in paths:
+                source = self.source.fetched_from.get(os.path.basename(p))
+                self.tarball_sources[os.path.basename(p)] = (
+                    source.__class__.__name__ if source is not None
+                    else "unknown")
+            self.verify(paths)
         else:
             note("Using the upstream tarball that is present in %s" %
                  self.store_dir)
+            in_store = self.already_exists_in_store()
+            for p in in_store:
+                self.tarball_sources[os.path.basename(p)] = "store directory"
+            self.verify(in_store)
         paths = self.provide_from_store_dir(target_dir)
         assert paths is not None
         return [(p, component_from_orig_tarball(p, self.package, self.version))
                 for p in paths]
 
+    def known_checksums(self, filenames=None):
+        """Collect the known checksums of the upstream tarballs.
+
+        :param filenames: basenames of the tarballs that checksums are
+            needed for; sources that are slow to query are only asked if
+            one of them has no known checksums yet
+        :return: dictionary mapping tarball basenames to lists of
+            (algorithm, hexdigest, origin) tuples
+        """
+        ret = {}
+        sources = sorted(
+            self.checksum_sources,
+            key=lambda source: getattr(source, 'slow_checksums', False))
+        for source in sources:
+            get_checksums = getattr(source, 'get_checksums', None)
+            if get_checksums is None:
+                continue
+            if (getattr(source, 'slow_checksums', False) and
+                    filenames is not None and
+                    all(filename in ret for filename in filenames)):
+                continue
+            try:
+                checksums = get_checksums(self.package, self.version)
+            except PackageVersionNotPresent:
+                continue
+            except DependencyNotPresent as e:
+                mutter('not checking %r due to missing dependency: %s',
+                       source, e)
+                continue
+            for filename, entries in checksums.items():
+                ret.setdefault(filename, []).extend(entries)
+        return ret
+
+    def verify(self, paths):
+        """Check tarballs against their known checksums and signatures.
+
+        :param paths: paths of the tarballs to check
+        :raise UpstreamChecksumMismatch: if a tarball does not match one of
+            its known checksums
+        :raise UpstreamSignatureInvalid: if the signature of a tarball
+            could not be verified
+        """
+        known = self.known_checksums(
+            [os.path.basename(path) for path in paths])
+        for path in paths:
+            filename = os.path.basename(path)
+            source = self.tarball_sources.get(filename, "unknown")
+            digests = {}
+            origins = []
+            for (algorithm, expected, origin) in known.get(filename, []):
+                if algorithm not in digests:
+                    digests[algorithm] = checksum_filename(path, algorithm)
+                if digests[algorithm] != expected.lower():
+                    raise UpstreamChecksumMismatch(
+                        filename, algorithm, expected, digests[algorithm],
+                        source, origin)
+                if origin not in origins:
+                    origins.append(origin)
+            if (self.signing_keys is not None and
+                    self.signing_keys.verify(path)):
+                origins.append("the upstream signature")
+            self.verified_tarballs[filename] = origins
+            if origins:
+                note("Using %s from %s, verified against %s.", filename,
+                     source, ", ".join(origins))
+            else:
+                note("Using %s from %s; no known checksums to verify it "
+                     "against.", filename, source)
+
     def already_exists_in_target(self, target_dir):
         return gather_orig_files(self.package, self.version, target_dir)
 
@@ -383,6 +621,111 @@ class UpstreamProvider(object):
         return paths
 
 
+# The files with the keys upstream signs its releases with, as used by
+# uscan, in order of preference.
+SIGNING_KEY_FILES = [
+    'upstream/signing-key.asc',
+    'upstream/signing-key.pgp',
+    'upstream-signing-key.pgp',
+    ]
+
+
+# The watch file options that make uscan download the signatures of the
+# tarballs.
+_WATCH_SIGNATURE_RE = re.compile(
+    br'\b(pgpsigurlmangle\s*=|pgpmode\s*=\s*(mangle|next|previous)\b)')
+
+
+class UpstreamSigningKeys(object):
+    """The keys upstream signs its releases with, kept in the packaging branch.
+
+    Signatures are expected next to the tarballs, with ".asc" appended to
+    the name of the tarball, as uscan stores them.
+
+    :ivar watch_file: path of the watch file, if it says that upstream signs
+        its releases; tarballs without a signature are then refused
+    """
+
+    def __init__(self, keys, filename, watch_file=None):
+        self.keys = keys
+        self.filename = filename
+        self.watch_file = watch_file
+
+    @classmethod
+    def from_tree(cls, tree, subpath='', top_level=False):
+        """Load the signing keys from a packaging tree.
+
+        :return: an UpstreamSigningKeys, or None if the tree has no keys
+        """
+        def tree_path(name):
+            if not top_level:
+                name = 'debian/' + name
+            if subpath:
+                name = osutils.pathjoin(subpath, name)
+            return name
+        for name in SIGNING_KEY_FILES:
+            path = tree_path(name)
+            if tree.has_filename(path):
+                break
+        else:
+            return None
+        watch_file = tree_path('watch')
+        if (not tree.has_filename(watch_file) or
+                not _WATCH_SIGNATURE_RE.search(
+                    tree.get_file_text(watch_file))):
+            watch_file = None
+        return cls(tree.get_file_text(path), path, watch_file)
+
+    def __repr__(self):
+        return "%s(%r)" % (self.__class__.__name__, self.filename)
+
+    def verify(self, path):
+        """Verify the detached signature of a tarball.
+
+        :param path: path of the tarball
+        :return: True if the signature was verified, False if the tarball
+            has no signature
+        :raise UpstreamSignatureInvalid: if the signature could not be
+            verified
+        :raise UpstreamSignatureMissing: if the tarball has no signature,
+            but the watch file says upstream signs its releases
+        """
+        signature = path + '.asc'
+        filename = os.path.basename(path)
+        if not os.path.exists(signature):
+            if self.watch_file is not None:
+                raise UpstreamSignatureMissing(filename, self.watch_file)
+            warning("%s has no signature, so it can not be verified against "
+                    "the keys in %s.", filename, self.filename)
+            return False
+        with tempfile.TemporaryDirectory(prefix='builddeb-gpg-') as homedir:
+            keyring = os.path.join(homedir, 'trustedkeys.gpg')
+            try:
+                if self.keys.lstrip().startswith(b'-----BEGIN'):
+                    subprocess.run(
+                        ['gpg', '--homedir', homedir, '--no-options',
+                         '--batch', '--quiet', '--output', keyring,
+                         '--dearmor'],
+                        input=self.keys, stdout=subprocess.PIPE,
+                        stderr=subprocess.STDOUT, check=True)
+                else:
+                    with open(keyring, 'wb') as f:
+                        f.write(self.keys)
+                subprocess.run(
+                    ['gpgv', '--homedir', homedir, '--keyring', keyring,
+                     signature, path],
+                    stdout=subprocess.PIPE, stderr=subprocess.STDOUT,
+                    check=True)
+            except subprocess.CalledProcessError as e:
+                raise UpstreamSignatureInvalid(
+                    filename, self.filename,
+                    e.output.decode('utf-8', 'replace').strip())
+            except OSError as e:
+                raise UpstreamSignatureInvalid(
+                    filename, self.filename, e)
+        return True
+
+
 def extract_tarball_version(path, packagename):
     """Extract a version from a tarball path.
 
@@ -467,6 +467,15 @@ except ImportError:  # breezy < 3.1.1
                     return self._partial_revision_history_cache[distance_from_last]
 
 
+# The compression options, and the export and tarball formats for them.
+EXPORT_FORMATS = {
+    'gzip': ('tgz', 'gz'),
+    'bzip2': ('tbz2', 'bz2'),
+    'xz': ('txz', 'xz'),
+    'lzma': ('tlzma', 'lzma'),
+    }
+
+
 class UpstreamBranchSource(UpstreamSource):
     """Upstream source that uses the upstream branch.
 
@@ -548,7 +557,8 @@ class UpstreamBranchSource(UpstreamSource):
     def version_as_revisions(self, package, version, tarballs=None):
         # FIXME: Support multiple upstream locations if there are multiple
         # components
-        if tarballs is not None and tarballs.keys() != [None]:
+        if tarballs is not None and set(
+                component for (tarball, component, md5) in tarballs) != {None}:
             raise MultipleUpstreamTarballsNotSupported()
         return {None: self.version_as_revision(package, version, tarballs)}
 
@@ -655,10 +665,12 @@ class UpstreamBranchSource(UpstreamSource):
                         repack_tarball(os.path.join(td, fn), nfn, target_dir)
                         return [os.path.join(target_dir, nfn)]
             tarball_base = "%s-%s" % (package, version)
+            (export_format, tarball_format) = EXPORT_FORMATS.get(
+                getattr(self.config, 'compression', None), ('tgz', 'gz'))
             target_filename = self._tarball_path(
-                package, version, None, target_dir)
+                package, version, None, target_dir, format=tarball_format)
             try:
-                export(rev_tree, target_filename, 'tgz', tarball_base)
+                export(rev_tree, target_filename, export_format, tarball_base)
             except UnsupportedOperation as e:
                 note('Not exporting revision from upstream branch: %s', e)
                 raise PackageVersionNotPresent(package, version, self)
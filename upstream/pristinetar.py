@@ -24,7 +24,6 @@ from base64 import (
     standard_b64decode,
     standard_b64encode,
     )
-import configparser
 from debian.copyright import globs_to_re
 from debian.changelog import Version
 import errno
@@ -32,7 +31,7 @@ from io import BytesIO
 import os
 import re
 import subprocess
-from tarfile import TarFile
+from tarfile import TarError, TarFile
 import tempfile
 
 from .... import debug
@@ -43,6 +42,7 @@ from . import (
 from ....export import export
 from ..util import (
     subprocess_setup,
+    tarball_name,
     )
 
 from .... import (
@@ -317,10 +317,15 @@ class BasePristineTarSource(UpstreamSource):
 
     def version_as_revisions(self, package, version, tarballs=None):
         if tarballs is None:
-            # FIXME: What if there are multiple tarballs?
-            return {
+            ret = {
                 None: self.version_component_as_revision(
                     package, version, component=None)}
+            # Additional tarballs can only be found by their tags.
+            components = self._components_by_version().get(str(version), {})
+            for component, revid in components.items():
+                if component is not None:
+                    ret[component] = revid
+            return ret
         ret = {}
         for (tarball, component, md5) in tarballs:
             ret[component] = self.version_component_as_revision(
@@ -600,6 +605,46 @@ class BzrPristineTarSource(BasePristineTarSource):
                 package, version, component, target_dir)
             for component in components]
 
+    def get_checksums(self, package, version):
+        revids = {}
+        with self.branch.lock_read():
+            for tag_name in self.possible_tag_names(
+                    package, version, component=None):
+                try:
+                    revids[None] = self.branch.tags.lookup_tag(tag_name)
+                except NoSuchTag:
+                    continue
+                else:
+                    break
+            components = self._components_by_version().get(str(version), {})
+            for component, revid in components.items():
+                revids.setdefault(component, revid)
+            ret = {}
+            for component, revid in revids.items():
+                try:
+                    rev = self.branch.repository.get_revision(revid)
+                except NoSuchRevision:
+                    continue
+                checksums = []
+                if 'deb-md5' in rev.properties:
+                    checksums.append(
+                        ('md5', rev.properties['deb-md5'],
+                         'the imported revision'))
+                if revision_has_pristine_tar_delta(rev):
+                    format = revision_pristine_tar_format(rev)
+                    sha256sum = pristine_tar_delta_sha256sum(
+                        revision_pristine_tar_delta(rev))
+                    if sha256sum is not None:
+                        checksums.append(
+                            ('sha256', sha256sum, 'the pristine-tar delta'))
+                else:
+                    format = 'gz'
+                if checksums:
+                    ret[tarball_name(
+                        package, version, component, format=format)] = (
+                            checksums)
+        return ret
+
     def _search_for_upstream_version(
             self, package, version, component, md5=None):
         start_revids = []
@@ -625,6 +670,20 @@ def get_pristine_tar_source(packaging_tree, packaging_branch):
     return BzrPristineTarSource(packaging_branch)
 
 
+def pristine_tar_delta_sha256sum(delta):
+    """Find the checksum of the tarball a pristine-tar delta recreates.
+
+    :param delta: contents of the delta
+    :return: hex digest of the sha256 checksum, or None if the delta does
+        not record it
+    """
+    try:
+        sha256sum = PristineTarDelta.from_bytes(delta).sha256sum
+    except (KeyError, TarError):
+        return None
+    return sha256sum.decode('ascii')
+
+
 class PristineTarDelta(object):
 
     def __init__(self, tar):
@@ -676,36 +735,17 @@ class GitPristineTarSource(BasePristineTarSource):
 
     @classmethod
     def from_tree(cls, tree, packaging_branch=None):
-        if tree and tree.has_filename('debian/gbp.conf'):
-            parser = configparser.ConfigParser(defaults={
-                'pristine-tar': 'false',
-                'upstream-branch': 'upstream',
-                'upstream-tag': 'upstream/%(version)s'},
-                strict=False)
-            parser.read_string(
-                tree.get_file_text('debian/gbp.conf').decode(
-                    'utf-8', errors='replace'),
-                'debian/gbp.conf')
-            try:
-                gbp_tag_format = parser.get(
-                    'import-orig', 'upstream-tag', raw=True)
-            except configparser.Error:
-                try:
-                    gbp_tag_format = parser.get(
-                        'DEFAULT', 'upstream-tag', raw=True)
-                except configparser.Error:
-                    gbp_tag_format = None
-            try:
-                pristine_tar = parser.getboolean(
-                    'import-orig', 'pristine-tar')
-            except configparser.Error:
-                try:
-                    pristine_tar = parser.getboolean(
-                        'DEFAULT', 'pristine-tar')
-                except configparser.Error:
-                    pristine_tar = None
-            upstream_branch = parser.get(
-                'DEFAULT', 'upstream-branch', raw=True)
+        from ..gbp import GBP_CONF, GbpConfig
+        if tree and tree.has_filename(GBP_CONF):
+            gbp_config = GbpConfig(
+                tree.get_file_text(GBP_CONF).decode(
+                    'utf-8', errors='replace'))
+            gbp_tag_format = gbp_config.get_gbp_option(
+                'upstream-tag', ['import-orig'], 'upstream/%(version)s')
+            pristine_tar = gbp_config.get_gbp_bool(
+                'pristine-tar', ['import-orig'], False)
+            upstream_branch = gbp_config.get_gbp_option(
+                'upstream-branch', default='upstream')
         else:
             gbp_tag_format = None
             upstream_branch = 'upstream'
@@ -927,8 +967,35 @@ class GitPristineTarSource(BasePristineTarSource):
                     cwd=self.branch.repository.user_transport.local_abspath('.'))
             except subprocess.CalledProcessError as e:
                 raise PristineTarError(str(e))
+            if delta_sig is not None:
+                with open(dest_filename + '.asc', 'wb') as f:
+                    f.write(delta_sig)
             return dest_filename
 
+    def get_checksums(self, package, version):
+        try:
+            pristine_tar_branch = self.branch.controldir.open_branch(
+                'pristine-tar')
+        except NotBranchError:
+            return {}
+        revtree = pristine_tar_branch.repository.revision_tree(
+            pristine_tar_branch.last_revision())
+        components = self._components_by_pristine_tar(package).get(
+            str(version), {})
+        ret = {}
+        for basename in components.values():
+            if not basename.startswith(package + '_'):
+                continue
+            try:
+                delta = revtree.get_file_text(basename + '.delta')
+            except NoSuchFile:
+                continue
+            sha256sum = pristine_tar_delta_sha256sum(delta)
+            if sha256sum is not None:
+                ret[basename] = [
+                    ('sha256', sha256sum, 'the pristine-tar delta')]
+        return ret
+
     def _components_by_pristine_tar(self, package=None):
         ret = {}
         try:
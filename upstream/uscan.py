@@ -184,11 +184,18 @@ class UScanSource(UpstreamSource):
                     raise PackageVersionNotPresent(package, version, self)
             ret = []
             for src in orig_files:
+                if src.endswith('.asc'):
+                    continue
+                src = os.path.join(tmpdir, src)
                 dst = os.path.join(target_dir, os.path.basename(src))
                 ret.append(dst)
-                shutil.copy(os.path.join(tmpdir, src), dst)
+                shutil.copy(src, dst)
+                # uscan downloads the signature if the watch file sets
+                # pgpsigurlmangle; keep it next to the tarball.
+                if os.path.exists(src + '.asc'):
+                    shutil.copy(src + '.asc', dst + '.asc')
 
-            return [src for src in ret if not src.endswith('.asc')]
+            return ret
 
 
 def _xml_report_extract_upstream_version(text):
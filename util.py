@@ -68,6 +68,11 @@ from .config import (
 from .errors import (
     BzrError,
     )
+from .gbp import (
+    GBP_CONF,
+    USER_GBP_CONF,
+    GbpConfig,
+    )
 
 BUILDDEB_DIR = '.bzr-builddeb'
 
@@ -297,6 +302,33 @@ def md5sum_filename(filename):
     return m.hexdigest()
 
 
+def sha256sum_filename(filename):
+    """Calculate the sha256sum of a file by name.
+
+    :param filename: Path of the file to checksum
+    :return: SHA256 Checksum as hex digest
+    """
+    m = hashlib.sha256()
+    with open(filename, 'rb') as f:
+        for chunk in iter(lambda: f.read(65536), b''):
+            m.update(chunk)
+    return m.hexdigest()
+
+
+def checksum_filename(filename, algorithm):
+    """Calculate a checksum of a file by name.
+
+    :param filename: Path of the file to checksum
+    :param algorithm: Name of the hash algorithm, e.g. "sha256"
+    :return: Checksum as hex digest
+    """
+    m = hashlib.new(algorithm)
+    with open(filename, 'rb') as f:
+        for chunk in iter(lambda: f.read(65536), b''):
+            m.update(chunk)
+    return m.hexdigest()
+
+
 def move_file_if_different(source, target, md5sum):
     """Overwrite a file if its new contents would be different from the current
     contents.
@@ -502,10 +534,68 @@ def subprocess_setup():
     signal.signal(signal.SIGPIPE, signal.SIG_DFL)
 
 
-def debuild_config(tree, subpath):
+def config_list(value):
+    """Convert the value of a list option to a list.
+
+    :param value: a string, a list of strings or None
+    """
+    if value is None:
+        return []
+    if isinstance(value, str):
+        return [value]
+    return list(value)
+
+
+def config_targets(distribution):
+    """Determine the configuration targets for a distribution or suite.
+
+    :param distribution: a distribution or suite, e.g. "bookworm-backports"
+    :return: list of target names, most specific first, e.g.
+        ["bookworm-backports", "debian"]
+    """
+    targets = [distribution]
+    vendor = lookup_distribution(distribution)
+    if vendor is not None and vendor != distribution:
+        targets.append(vendor)
+    return targets
+
+
+def changelog_target_distribution(changelog):
+    """Find the distribution a package is targetted at.
+
+    :param changelog: a Changelog
+    :return: the suite of the most recent entry that isn't UNRELEASED, or
+        None
+    """
+    for block in changelog:
+        distributions = (block.distributions or '').split()
+        if distributions and distributions[0] != 'UNRELEASED':
+            return distributions[0]
+    return None
+
+
+def _tree_target_distribution(tree, subpath):
+    for path in ('debian/changelog', 'changelog'):
+        path = osutils.pathjoin(subpath, path)
+        if not tree.has_filename(path):
+            continue
+        try:
+            changelog = Changelog(
+                tree.get_file_text(path), max_blocks=10, strict=False)
+        except ChangelogParseError:
+            continue
+        return changelog_target_distribution(changelog)
+    return None
+
+
+def debuild_config(tree, subpath, target=None, branch=None):
     """Obtain the Debuild configuration object.
 
     :param tree: A Tree object, can be a WorkingTree or RevisionTree.
+    :param target: the distribution to use the configuration sections of,
+        defaults to the distribution in the changelog
+    :param branch: the Branch the tree is from, which decides whether hooks
+        from the tree are trusted; defaults to the branch of the tree
     """
     config_files = []
     user_config = None
@@ -540,6 +630,25 @@ def debuild_config(tree, subpath):
             (tree.get_file(default_conf), False, "default.conf"))
     config = DebBuildConfig(config_files, tree=tree)
     config.set_user_config(user_config)
+    if branch is None:
+        branch = getattr(tree, 'branch', None)
+    if branch is not None:
+        config.branch_url = branch.user_url
+    # The gbp configuration has the lowest precedence.
+    user_gbp_conf = os.path.expanduser(USER_GBP_CONF)
+    if os.path.exists(user_gbp_conf):
+        with open(user_gbp_conf, 'rb') as f:
+            config.add_config(GbpConfig(
+                f.read().decode('utf-8', 'replace'), user_gbp_conf), True)
+    gbp_conf = osutils.pathjoin(subpath, GBP_CONF)
+    if tree.has_filename(gbp_conf):
+        config.add_config(GbpConfig(
+            tree.get_file_text(gbp_conf).decode('utf-8', 'replace'),
+            "gbp.conf"), False)
+    if target is None:
+        target = _tree_target_distribution(tree, subpath)
+    if target is not None:
+        config.set_targets(config_targets(target))
     return config
 
 
@@ -792,6 +901,12 @@ def extract_orig_tarball(tarball_filename, component, target,
         tf.close()
     if component is not None:
         target_path = os.path.join(target, component)
+        # Like dpkg-source, let the component replace whatever the main
+        # tarball had in its place.
+        if os.path.isdir(target_path) and not os.path.islink(target_path):
+            shutil.rmtree(target_path)
+        elif os.path.lexists(target_path):
+            os.unlink(target_path)
         os.mkdir(target_path)
     else:
         target_path = target
@@ -808,9 +923,14 @@ def extract_orig_tarball(tarball_filename, component, target,
 def extract_orig_tarballs(tarballs, target, strip_components=None):
     """Extract orig tarballs to a directory.
 
-    :param tarballs: List of tarball filenames
+    The main tarball is extracted first, and each of the other components
+    into a subdirectory named after it.
+
+    :param tarballs: List of (tarball filename, component) tuples
     :param target: Target directory (must already exist)
     """
+    tarballs = sorted(
+        tarballs, key=lambda tarball: (tarball[1] is not None, tarball[1]))
     for tarball_filename, component in tarballs:
         extract_orig_tarball(
             tarball_filename, component, target,
@@ -832,7 +952,17 @@ def debsign(path, keyid=None):
     subprocess.check_call(args, cwd=bd)
 
 
-def find_changes_files(path, package, version):
+def find_changes_files(path, package, version, architecture=None):
+    """Find the changes files for a package version.
+
+    :param path: the directory to look in
+    :param package: the name of the source package
+    :param version: the Version of the package
+    :param architecture: if not None, only find changes files for this
+        (host) architecture, or that cover several architectures.
+    :return: iterator over (kind, entry) tuples, where kind is the
+        architecture part of the file name, e.g. "source" or "amd64".
+    """
     non_epoch_version = version.upstream_version
     if version.debian_version is not None:
         non_epoch_version += "-%s" % version.debian_version
@@ -840,6 +970,9 @@ def find_changes_files(path, package, version):
     for entry in os.scandir(path):
         m = c.match(entry.name)
         if m:
+            if (architecture is not None and
+                    m.group(1) not in (architecture, 'multi')):
+                continue
             yield m.group(1), entry
 
 